@@ -0,0 +1,12 @@
+/* STM32F401CC (256K flash, 64K RAM)
+
+   Sectors 1 and 2 (16K each, 0x08004000-0x0800BFFF) hold the settings
+   journal. The vector table stays at the start of sector 0 and the code
+   starts at sector 3 (_stext, see cortex-m-rt), leaving 208K for the code. */
+MEMORY
+{
+  FLASH : ORIGIN = 0x08000000, LENGTH = 256K
+  RAM : ORIGIN = 0x20000000, LENGTH = 64K
+}
+
+_stext = 0x0800C000;
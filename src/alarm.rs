@@ -0,0 +1,403 @@
+use crate::tz::TimeZone;
+use chrono::{Datelike, NaiveDateTime, NaiveTime, TimeDelta, Timelike, Weekday};
+use core::fmt;
+use heapless::{String, Vec};
+
+// Day masks for `Schedule::Days` (bit 0 = Monday)
+pub const WEEKDAYS: u8 = 0b0001_1111;
+pub const WEEKENDS: u8 = 0b0110_0000;
+pub const ALL_DAYS: u8 = 0b0111_1111;
+
+pub fn day_bit(day: Weekday) -> u8 {
+    1 << day.num_days_from_monday()
+}
+
+// Recurring (or one-shot) alarm schedule
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Schedule {
+    Daily(NaiveTime),
+    // Day mask (bit 0 = Monday) and time
+    Days(u8, NaiveTime),
+    Once(NaiveDateTime),
+    // Every hour at minute
+    Hourly(u8),
+}
+
+// Hardware alarm match modes (DS3231 Alarm 1)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlarmMatch {
+    // Minutes and seconds match (every hour)
+    MinuteSecond(u8, u8),
+    // Hours, minutes and seconds match (every day)
+    Time(NaiveTime),
+    // Day of week (1 = Monday - 7) and time match
+    DayTime(u8, NaiveTime),
+    // Day of month and time match
+    DateTime(u8, NaiveTime),
+}
+
+impl AlarmMatch {
+    pub fn matches(&self, dt: &NaiveDateTime) -> bool {
+        let hms = |t: &NaiveTime| {
+            t.hour() == dt.hour() && t.minute() == dt.minute() && t.second() == dt.second()
+        };
+        match self {
+            AlarmMatch::MinuteSecond(m, s) => *m as u32 == dt.minute() && *s as u32 == dt.second(),
+            AlarmMatch::Time(t) => hms(t),
+            AlarmMatch::DayTime(d, t) => *d as u32 == dt.weekday().number_from_monday() && hms(t),
+            AlarmMatch::DateTime(d, t) => *d as u32 == dt.day() && hms(t),
+        }
+    }
+}
+
+impl Schedule {
+    // Next time the alarm fires strictly after `now`
+    pub fn next_after(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        match *self {
+            Schedule::Daily(t) => Schedule::Days(ALL_DAYS, t).next_after(now),
+            Schedule::Days(mask, t) => (0..=7)
+                .map(|i| (now.date() + TimeDelta::days(i)).and_time(t))
+                .find(|dt| mask & day_bit(dt.weekday()) != 0 && *dt > now),
+            Schedule::Once(dt) => (dt > now).then_some(dt),
+            Schedule::Hourly(m) => {
+                let at = now.date().and_hms_opt(now.hour(), m as u32, 0)?;
+                Some(if at > now {
+                    at
+                } else {
+                    at + TimeDelta::hours(1)
+                })
+            }
+        }
+    }
+
+    // Best effort reconstruction from hardware (date matches cannot be recovered)
+    pub fn from_alarm_match(m: AlarmMatch) -> Option<Schedule> {
+        match m {
+            AlarmMatch::MinuteSecond(m, 0) => Some(Schedule::Hourly(m)),
+            AlarmMatch::Time(t) => Some(Schedule::Daily(t)),
+            AlarmMatch::DayTime(d @ 1..=7, t) => Some(Schedule::Days(1 << (d - 1), t)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hms = |f: &mut fmt::Formatter<'_>, t: &NaiveTime| {
+            write!(f, "{:02}:{:02}:{:02}", t.hour(), t.minute(), t.second())
+        };
+        match self {
+            Schedule::Daily(t) => hms(f, t),
+            Schedule::Days(mask, t) => {
+                match *mask {
+                    WEEKDAYS => write!(f, "Mon-Fri ")?,
+                    mask => {
+                        let mut sep = "";
+                        for d in 0..7 {
+                            if mask & (1 << d) != 0 {
+                                write!(f, "{}{}", sep, Weekday::try_from(d).unwrap())?;
+                                sep = ",";
+                            }
+                        }
+                        write!(f, " ")?;
+                    }
+                }
+                hms(f, t)
+            }
+            Schedule::Once(dt) => {
+                write!(f, "{:02}/{:02}/{:04} ", dt.day(), dt.month(), dt.year())?;
+                hms(f, &dt.time())
+            }
+            Schedule::Hourly(m) => write!(f, "Hourly :{:02}", m),
+        }
+    }
+}
+
+// Alarm table size (firmware and saved settings)
+pub const MAX_ALARMS: usize = 16;
+
+// Alarm names are short identifiers ([A-Za-z0-9_-])
+pub const ALARM_NAME_LEN: usize = 12;
+pub type AlarmName = String<ALARM_NAME_LEN>;
+
+pub fn alarm_name(name: &str) -> Option<AlarmName> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    valid.then(|| AlarmName::try_from(name).ok()).flatten()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alarm {
+    pub name: AlarmName,
+    pub schedule: Schedule,
+    pub enabled: bool,
+}
+
+impl fmt::Display for Alarm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<12} {}", self.name.as_str(), self.schedule)?;
+        if !self.enabled {
+            write!(f, " (off)")?;
+        }
+        Ok(())
+    }
+}
+
+// Next alarm due (the one programmed into the hardware alarm)
+#[derive(Clone, Debug, PartialEq)]
+pub struct NextAlarm {
+    pub name: AlarmName,
+    pub at: NaiveDateTime,
+}
+
+impl NextAlarm {
+    // Date match for the exact occurrence - re-armed after each fire. `at` is
+    // local time, the hardware alarm runs in UTC.
+    pub fn alarm_match(&self, tz: &TimeZone) -> AlarmMatch {
+        let at = tz.to_utc(self.at);
+        AlarmMatch::DateTime(at.day() as u8, at.time())
+    }
+}
+
+impl fmt::Display for NextAlarm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {:02}:{:02}",
+            self.name.as_str(),
+            self.at.weekday(),
+            self.at.hour(),
+            self.at.minute()
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TableError {
+    Full,
+    Exists,
+    NotFound,
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TableError::Full => write!(f, "Alarm table full"),
+            TableError::Exists => write!(f, "Alarm exists"),
+            TableError::NotFound => write!(f, "Alarm not found"),
+        }
+    }
+}
+
+// Named alarms matched in software. Only the next one due is programmed into
+// the hardware alarm, so the number of alarms is limited only by `N`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AlarmTable<const N: usize> {
+    alarms: Vec<Alarm, N>,
+}
+
+impl<const N: usize> AlarmTable<N> {
+    pub const fn new() -> Self {
+        AlarmTable { alarms: Vec::new() }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Alarm> {
+        self.alarms.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.alarms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.alarms.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Alarm> {
+        self.alarms.iter().find(|a| a.name == name)
+    }
+
+    fn get_mut(&mut self, name: &str) -> Result<&mut Alarm, TableError> {
+        self.alarms
+            .iter_mut()
+            .find(|a| a.name == name)
+            .ok_or(TableError::NotFound)
+    }
+
+    pub fn add(&mut self, name: AlarmName, schedule: Schedule) -> Result<(), TableError> {
+        if self.get(&name).is_some() {
+            return Err(TableError::Exists);
+        }
+        self.alarms
+            .push(Alarm {
+                name,
+                schedule,
+                enabled: true,
+            })
+            .map_err(|_| TableError::Full)
+    }
+
+    // Add or replace (and enable) an alarm
+    pub fn set(&mut self, name: AlarmName, schedule: Schedule) -> Result<(), TableError> {
+        match self.get_mut(&name) {
+            Ok(alarm) => {
+                alarm.schedule = schedule;
+                alarm.enabled = true;
+                Ok(())
+            }
+            Err(_) => self.add(name, schedule),
+        }
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<Alarm, TableError> {
+        let i = self
+            .alarms
+            .iter()
+            .position(|a| a.name == name)
+            .ok_or(TableError::NotFound)?;
+        Ok(self.alarms.remove(i))
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<(), TableError> {
+        self.get_mut(name).map(|alarm| alarm.enabled = enabled)
+    }
+
+    // Earliest enabled alarm strictly after `now`
+    pub fn next(&self, now: NaiveDateTime) -> Option<NextAlarm> {
+        self.alarms
+            .iter()
+            .filter(|a| a.enabled)
+            .filter_map(|a| a.schedule.next_after(now).map(|at| (a, at)))
+            .min_by_key(|(_, at)| *at)
+            .map(|(a, at)| NextAlarm {
+                name: a.name.clone(),
+                at,
+            })
+    }
+
+    // Disable one-shot alarms which have fired. Returns true if any were.
+    pub fn retire(&mut self, now: NaiveDateTime) -> bool {
+        let mut retired = false;
+        for alarm in self.alarms.iter_mut() {
+            if alarm.enabled && alarm.schedule.next_after(now).is_none() {
+                alarm.enabled = false;
+                retired = true;
+            }
+        }
+        retired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    // 2026-03-02 is a Monday
+    fn dt(d: u32, h: u32, m: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 3, d)
+            .unwrap()
+            .and_hms_opt(h, m, s)
+            .unwrap()
+    }
+
+    fn t(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn next_occurrence() {
+        let weekdays = Schedule::Days(WEEKDAYS, t(7, 0));
+        assert_eq!(weekdays.next_after(dt(2, 6, 0, 0)), Some(dt(2, 7, 0, 0)));
+        assert_eq!(weekdays.next_after(dt(6, 7, 0, 0)), Some(dt(9, 7, 0, 0)));
+        let saturday = Schedule::Days(day_bit(Weekday::Sat), t(9, 30));
+        assert_eq!(saturday.next_after(dt(7, 9, 30, 0)), Some(dt(14, 9, 30, 0)));
+        let once = Schedule::Once(dt(24, 18, 0, 0));
+        assert_eq!(once.next_after(dt(2, 0, 0, 0)), Some(dt(24, 18, 0, 0)));
+        assert_eq!(once.next_after(dt(24, 18, 0, 0)), None);
+        let hourly = Schedule::Hourly(15);
+        assert_eq!(hourly.next_after(dt(2, 23, 15, 0)), Some(dt(3, 0, 15, 0)));
+    }
+
+    #[test]
+    fn hardware_match_modes() {
+        assert!(AlarmMatch::DayTime(6, t(9, 30)).matches(&dt(7, 9, 30, 0)));
+        assert!(!AlarmMatch::DayTime(6, t(9, 30)).matches(&dt(8, 9, 30, 0)));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            Schedule::Days(WEEKDAYS, t(7, 0)).to_string(),
+            "Mon-Fri 07:00:00"
+        );
+        assert_eq!(
+            Schedule::Days(WEEKENDS, t(9, 30)).to_string(),
+            "Sat,Sun 09:30:00"
+        );
+        assert_eq!(
+            Schedule::Once(dt(24, 18, 0, 0)).to_string(),
+            "24/03/2026 18:00:00"
+        );
+        assert_eq!(Schedule::Hourly(5).to_string(), "Hourly :05");
+    }
+
+    #[test]
+    fn alarm_table() {
+        let name = |s| alarm_name(s).unwrap();
+        let mut table: AlarmTable<3> = AlarmTable::new();
+        assert_eq!(table.next(dt(2, 12, 0, 0)), None);
+        table
+            .add(name("wake"), Schedule::Days(WEEKDAYS, t(7, 0)))
+            .unwrap();
+        table
+            .add(
+                name("bins"),
+                Schedule::Days(day_bit(Weekday::Tue), t(6, 30)),
+            )
+            .unwrap();
+        table
+            .add(name("party"), Schedule::Once(dt(6, 20, 0, 0)))
+            .unwrap();
+        assert_eq!(
+            table.add(name("wake"), Schedule::Daily(t(8, 0))),
+            Err(TableError::Exists)
+        );
+        assert_eq!(
+            table.add(name("extra"), Schedule::Daily(t(8, 0))),
+            Err(TableError::Full)
+        );
+        // Monday noon - bins on Tuesday 06:30 before wake at 07:00
+        let next = table.next(dt(2, 12, 0, 0)).unwrap();
+        assert_eq!(next.name, "bins");
+        assert_eq!(next.at, dt(3, 6, 30, 0));
+        assert_eq!(
+            next.alarm_match(&TimeZone::utc()),
+            AlarmMatch::DateTime(3, t(6, 30))
+        );
+        let cet = TimeZone::parse("CET-1").unwrap();
+        assert_eq!(next.alarm_match(&cet), AlarmMatch::DateTime(3, t(5, 30)));
+        assert_eq!(next.to_string(), "bins Tue 06:30");
+        table.set_enabled("bins", false).unwrap();
+        assert_eq!(table.next(dt(2, 12, 0, 0)).unwrap().name, "wake");
+        assert_eq!(table.set_enabled("nope", true), Err(TableError::NotFound));
+        // One-shot disabled once fired
+        assert!(!table.retire(dt(6, 19, 0, 0)));
+        assert!(table.retire(dt(6, 20, 0, 0)));
+        assert!(!table.get("party").unwrap().enabled);
+        assert!(!table.retire(dt(6, 20, 0, 0)));
+        assert_eq!(table.delete("party").unwrap().name, "party");
+        assert_eq!(table.delete("party"), Err(TableError::NotFound));
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    fn alarm_names() {
+        assert!(alarm_name("wake_up-2").is_some());
+        assert!(alarm_name("").is_none());
+        assert!(alarm_name("two words").is_none());
+        assert!(alarm_name("much_too_long_name").is_none());
+    }
+}
@@ -0,0 +1,137 @@
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::i2c::I2c;
+
+pub mod emulator;
+
+// AT24C32 (4K x 8) - address 0x57 on the common DS3231 modules (A0-A2 pulled up)
+pub const ADDRESS: u8 = 0x57;
+pub const CAPACITY: usize = 4096;
+pub const PAGE_SIZE: usize = 32;
+
+// Self-timed write cycle is 10ms max - the device NACKs until it completes
+const POLL_INTERVAL_US: u32 = 500;
+const MAX_POLLS: u32 = 40;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error<E> {
+    Comm(E),
+    OutOfRange,
+}
+
+// Paged EEPROM driver over the embedded-hal-async I2c trait. `delay` paces the
+// ACK polling after a page write, leaving the bus free for other devices.
+pub struct At24c32<I2C, D> {
+    i2c: I2C,
+    delay: D,
+    address: u8,
+}
+
+impl<I2C, D, E> At24c32<I2C, D>
+where
+    I2C: I2c<Error = E>,
+    D: DelayNs,
+{
+    pub fn new(i2c: I2C, delay: D) -> Self {
+        Self::with_address(i2c, delay, ADDRESS)
+    }
+
+    pub fn with_address(i2c: I2C, delay: D, address: u8) -> Self {
+        At24c32 {
+            i2c,
+            delay,
+            address,
+        }
+    }
+
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    fn check_range(offset: u32, len: usize) -> Result<(), Error<E>> {
+        match (offset as usize).checked_add(len) {
+            Some(end) if end <= CAPACITY => Ok(()),
+            _ => Err(Error::OutOfRange),
+        }
+    }
+
+    // Sequential read from `offset`
+    pub async fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), Error<E>> {
+        Self::check_range(offset, buf.len())?;
+        self.i2c
+            .write_read(self.address, &(offset as u16).to_be_bytes(), buf)
+            .await
+            .map_err(Error::Comm)
+    }
+
+    // Write split into page writes (a page write wraps within the page)
+    pub async fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Error<E>> {
+        Self::check_range(offset, data.len())?;
+        let mut offset = offset as usize;
+        let mut data = data;
+        let mut buf = [0; 2 + PAGE_SIZE];
+        while !data.is_empty() {
+            let n = data.len().min(PAGE_SIZE - offset % PAGE_SIZE);
+            let (page, rest) = data.split_at(n);
+            buf[..2].copy_from_slice(&(offset as u16).to_be_bytes());
+            buf[2..2 + n].copy_from_slice(page);
+            self.i2c
+                .write(self.address, &buf[..2 + n])
+                .await
+                .map_err(Error::Comm)?;
+            self.wait_ready(offset as u16).await?;
+            offset += n;
+            data = rest;
+        }
+        Ok(())
+    }
+
+    // ACK polling - the address is rewritten (harmless) until the device responds
+    async fn wait_ready(&mut self, offset: u16) -> Result<(), Error<E>> {
+        let mut polls = 0;
+        loop {
+            match self.i2c.write(self.address, &offset.to_be_bytes()).await {
+                Ok(_) => return Ok(()),
+                Err(e) if polls >= MAX_POLLS => return Err(Error::Comm(e)),
+                Err(_) => polls += 1,
+            }
+            self.delay.delay_us(POLL_INTERVAL_US).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::emulator::{At24c32Emulator, NoDelay};
+    use super::*;
+    use crate::block_on;
+
+    #[test]
+    fn read_write_across_pages() {
+        let mut eeprom = At24c32::new(At24c32Emulator::new(), NoDelay);
+        let data: [u8; 100] = core::array::from_fn(|i| i as u8);
+        block_on(eeprom.write(20, &data)).unwrap();
+        let mut buf = [0; 100];
+        block_on(eeprom.read(20, &mut buf)).unwrap();
+        assert_eq!(buf, data);
+        let emulator = eeprom.release();
+        // Neighbouring bytes untouched
+        assert_eq!(emulator.memory()[19], 0xff);
+        assert_eq!(emulator.memory()[120], 0xff);
+        // Four page writes, each polled until the write cycle completed
+        assert_eq!(emulator.page_writes(), 4);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let mut eeprom = At24c32::new(At24c32Emulator::new(), NoDelay);
+        assert_eq!(
+            block_on(eeprom.write(4090, &[0; 8])),
+            Err(Error::OutOfRange)
+        );
+        assert_eq!(
+            block_on(eeprom.read(4096, &mut [0; 1])),
+            Err(Error::OutOfRange)
+        );
+        assert_eq!(block_on(eeprom.write(4088, &[0; 8])), Ok(()));
+    }
+}
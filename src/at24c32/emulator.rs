@@ -0,0 +1,116 @@
+use super::*;
+use embedded_hal::i2c::{ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation};
+
+// Write cycle emulated as a number of NACKed transactions
+const WRITE_CYCLE_POLLS: u32 = 3;
+
+// AT24C32 emulator
+//
+// Implements the embedded-hal I2c traits (address 0x57). The first two bytes
+// written set the 12-bit address pointer; further bytes are a page write which
+// wraps within the 32 byte page. The device NACKs while a write cycle is busy.
+pub struct At24c32Emulator {
+    mem: [u8; CAPACITY],
+    pointer: usize,
+    busy: u32,
+    page_writes: u32,
+}
+
+impl Default for At24c32Emulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl At24c32Emulator {
+    // Erased (0xff) memory
+    pub fn new() -> Self {
+        At24c32Emulator {
+            mem: [0xff; CAPACITY],
+            pointer: 0,
+            busy: 0,
+            page_writes: 0,
+        }
+    }
+
+    pub fn memory(&self) -> &[u8; CAPACITY] {
+        &self.mem
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [u8; CAPACITY] {
+        &mut self.mem
+    }
+
+    pub fn page_writes(&self) -> u32 {
+        self.page_writes
+    }
+}
+
+impl ErrorType for At24c32Emulator {
+    type Error = ErrorKind;
+}
+
+impl I2c for At24c32Emulator {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if address != ADDRESS || self.busy > 0 {
+            self.busy = self.busy.saturating_sub(1);
+            return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+        }
+        // Bytes written after a START (adjacent writes are merged)
+        let mut written = 0;
+        let mut page_write = false;
+        for op in operations {
+            match op {
+                Operation::Write(bytes) => {
+                    for &b in bytes.iter() {
+                        match written {
+                            0 => self.pointer = (b as usize & 0x0f) << 8,
+                            1 => self.pointer = (self.pointer | b as usize) % CAPACITY,
+                            _ => {
+                                self.mem[self.pointer] = b;
+                                let page = self.pointer - self.pointer % PAGE_SIZE;
+                                self.pointer = page + (self.pointer + 1) % PAGE_SIZE;
+                                page_write = true;
+                            }
+                        }
+                        written += 1;
+                    }
+                }
+                Operation::Read(buf) => {
+                    for b in buf.iter_mut() {
+                        *b = self.mem[self.pointer];
+                        self.pointer = (self.pointer + 1) % CAPACITY;
+                    }
+                    written = 0;
+                }
+            }
+        }
+        if page_write {
+            self.page_writes += 1;
+            self.busy = WRITE_CYCLE_POLLS;
+        }
+        Ok(())
+    }
+}
+
+// Transactions complete immediately
+impl embedded_hal_async::i2c::I2c for At24c32Emulator {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        I2c::transaction(self, address, operations)
+    }
+}
+
+// The emulated write cycle is counted in polls, so the driver needn't wait
+pub struct NoDelay;
+
+impl embedded_hal_async::delay::DelayNs for NoDelay {
+    async fn delay_ns(&mut self, _ns: u32) {}
+}
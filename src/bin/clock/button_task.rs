@@ -1,16 +1,32 @@
 use defmt::*;
+use embassy_futures::select::{select, Either};
 use embassy_stm32::{
     exti::{AnyChannel, ExtiInput},
     gpio::{AnyPin, Pull},
 };
-use portable_atomic::Ordering;
+use embassy_time::Timer;
+use stm32f401_embassy::ringer::{RingEvent, LONG_PRESS_MS};
 
+// Short press snoozes (or changes the display page), long press dismisses
 #[embassy_executor::task]
 pub async fn button(button: AnyPin, exti: AnyChannel) {
     let mut button = ExtiInput::new(button, exti, Pull::Up);
     loop {
         button.wait_for_falling_edge().await;
-        info!("Clearing Alarm");
-        crate::ALARM.store(false, Ordering::Relaxed);
+        // Debounce
+        Timer::after_millis(20).await;
+        let event = match select(button.wait_for_high(), Timer::after_millis(LONG_PRESS_MS)).await {
+            Either::First(_) => RingEvent::Snooze,
+            Either::Second(_) => RingEvent::Dismiss,
+        };
+        info!("Button: {}", event);
+        crate::RING_EVENTS.send(event).await;
+        if event == RingEvent::Snooze {
+            crate::MSG_BUS
+                .immediate_publisher()
+                .publish_immediate(crate::Msg::NextPage);
+        }
+        button.wait_for_high().await;
+        Timer::after_millis(20).await;
     }
 }
@@ -1,179 +1,152 @@
-use chrono::{Datelike, Timelike};
-use chrono::{NaiveDate, NaiveTime};
-use core::fmt::Write;
-use nom::branch::alt;
-use nom::bytes::complete::tag;
-use nom::character::complete::{multispace0, multispace1, one_of};
-use nom::combinator::{map_opt, rest, value};
-use nom::error::Error;
-use nom::sequence::{pair, tuple};
-use nom::IResult;
+use chrono::{NaiveDateTime, TimeDelta, Timelike};
+use core::fmt::{self, Write};
+use embassy_time::{with_timeout, Duration, Instant};
+use stm32f401_embassy::alarm::{alarm_name, AlarmTable, TableError, MAX_ALARMS};
+use stm32f401_embassy::cli::{help_topic, parse_command, CliMsg, TOPIC_LEN};
+use stm32f401_embassy::settings::Settings;
+use stm32f401_embassy::sync::{SyncRequest, SyncResult};
+use stm32f401_embassy::temp_log::{Summary, CSV_HEADER};
 // use defmt::info;
 
-#[derive(Clone, Debug)]
-enum CliMsg {
-    Hello,
-    GetTime,
-    GetDate,
-    GetTemp,
-    GetAlarm,
-    SetTime(NaiveTime),
-    SetDate(NaiveDate),
-    SetAlarm(NaiveTime),
-}
-
-fn _digit_parser(input: &str) -> IResult<&str, u32, Error<&str>> {
-    map_opt(
-        pair(one_of("0123456789"), one_of("0123456789")),
-        |(d1, d2)| {
-            d1.to_digit(10)
-                .and_then(|n1| d2.to_digit(10).and_then(|n2| Some(n1 * 10 + n2)))
-        },
-    )(input)
-}
-
-fn set_date_parser(input: &str) -> IResult<&str, CliMsg, Error<&str>> {
-    map_opt(
-        tuple((
-            multispace0,
-            tag("set"),
-            multispace1,
-            tag("date"),
-            multispace1,
-            rest,
-        )),
-        |(_, _, _, _, _, date): (_, _, _, _, _, &str)| match NaiveDate::parse_from_str(
-            date.trim(),
-            "%d/%m/%Y",
-        ) {
-            Ok(d) => Some(CliMsg::SetDate(d)),
-            Err(_) => None,
-        },
-    )(input)
-}
-
-fn set_time_parser(input: &str) -> IResult<&str, CliMsg, Error<&str>> {
-    map_opt(
-        tuple((
-            multispace0,
-            tag("set"),
-            multispace1,
-            tag("time"),
-            multispace1,
-            rest,
-        )),
-        |(_, _, _, _, _, time): (_, _, _, _, _, &str)| match NaiveTime::parse_from_str(
-            time.trim(),
-            "%H:%M:%S",
-        ) {
-            Ok(t) => Some(CliMsg::SetTime(t)),
-            Err(_) => None,
-        },
-    )(input)
+// Edit the alarm table and re-arm the next alarm
+async fn update_alarms(
+    out: &mut heapless::String<512>,
+    f: impl FnOnce(&mut AlarmTable<MAX_ALARMS>) -> Result<(), TableError>,
+) {
+    match crate::SETTINGS.lock(|s| f(&mut s.borrow_mut().alarms)) {
+        Ok(_) => {
+            let msg_pub = crate::MSG_BUS.publisher().unwrap();
+            msg_pub.publish(crate::Msg::AlarmsChanged).await;
+            out.push_str("OK").ok();
+        }
+        Err(e) => {
+            write!(out, "{}", e).ok();
+        }
+    }
 }
 
-fn set_alarm_parser(input: &str) -> IResult<&str, CliMsg, Error<&str>> {
-    map_opt(
-        tuple((
-            multispace0,
-            tag("set"),
-            multispace1,
-            tag("alarm"),
-            multispace1,
-            rest,
-        )),
-        |(_, _, _, _, _, time): (_, _, _, _, _, &str)| match NaiveTime::parse_from_str(
-            time.trim(),
-            "%H:%M:%S",
-        ) {
-            Ok(t) => Some(CliMsg::SetAlarm(t)),
-            Err(_) => None,
-        },
-    )(input)
+// Update a setting and notify (settings_task saves the change)
+async fn update_settings(
+    out: &mut heapless::String<512>,
+    f: impl FnOnce(&mut Settings),
+    msg: crate::Msg,
+) {
+    crate::SETTINGS.lock(|s| f(&mut s.borrow_mut()));
+    let msg_pub = crate::MSG_BUS.publisher().unwrap();
+    msg_pub.publish(msg).await;
+    out.push_str("OK").ok();
 }
 
-fn get_time_parser(input: &str) -> IResult<&str, CliMsg, Error<&str>> {
-    value(
-        CliMsg::GetTime,
-        tuple((
-            multispace0,
-            tag("get"),
-            multispace1,
-            tag("time"),
-            multispace0,
-        )),
-    )(input)
+async fn publish(out: &mut heapless::String<512>, msg: crate::Msg) {
+    let msg_pub = crate::MSG_BUS.publisher().unwrap();
+    msg_pub.publish(msg).await;
+    out.push_str("OK").ok();
 }
 
-fn get_date_parser(input: &str) -> IResult<&str, CliMsg, Error<&str>> {
-    value(
-        CliMsg::GetDate,
-        tuple((
-            multispace0,
-            tag("get"),
-            multispace1,
-            tag("date"),
-            multispace0,
-        )),
-    )(input)
+// Output too long for one response, written a chunk at a time after it
+pub enum Stream {
+    TempHistory {
+        after: Option<NaiveDateTime>,
+    },
+    TempCsv {
+        after: Option<NaiveDateTime>,
+    },
+    // Commands listed so far
+    Help {
+        topic: heapless::String<TOPIC_LEN>,
+        next: usize,
+    },
 }
 
-fn get_temp_parser(input: &str) -> IResult<&str, CliMsg, Error<&str>> {
-    value(
-        CliMsg::GetTemp,
-        tuple((
-            multispace0,
-            tag("get"),
-            multispace1,
-            tag("temp"),
-            multispace0,
-        )),
-    )(input)
-}
+// Room needed for another row in a chunk
+const ROW_LEN: usize = 48;
 
-fn get_alarm_parser(input: &str) -> IResult<&str, CliMsg, Error<&str>> {
-    value(
-        CliMsg::GetAlarm,
-        tuple((
-            multispace0,
-            tag("get"),
-            multispace1,
-            tag("alarm"),
-            multispace0,
-        )),
-    )(input)
+// Append the rows that start after `after` while there is room
+fn fill(
+    out: &mut heapless::String<512>,
+    rows: impl Iterator<Item = Summary>,
+    after: &mut Option<NaiveDateTime>,
+    row: impl Fn(&mut heapless::String<512>, &Summary) -> fmt::Result,
+) {
+    let from = *after;
+    for s in rows.filter(|s| from.map_or(true, |from| s.start > from)) {
+        if out.capacity() - out.len() < ROW_LEN {
+            break;
+        }
+        out.push_str("\r\n").ok();
+        row(out, &s).ok();
+        *after = Some(s.start);
+    }
 }
 
-fn hello_parser(input: &str) -> IResult<&str, CliMsg, Error<&str>> {
-    value(
-        CliMsg::Hello,
-        tuple((multispace0, tag("hello"), multispace0)),
-    )(input)
+// Help rows from `next` while there is room. A single command is described
+// in full.
+fn help_chunk(topic: &str, next: &mut usize) -> Option<heapless::String<512>> {
+    let mut out: heapless::String<512> = heapless::String::new();
+    let detail = help_topic(topic).nth(1).is_none();
+    for command in help_topic(topic).skip(*next) {
+        let mut row: heapless::String<256> = heapless::String::new();
+        if *next > 0 {
+            row.push_str("\r\n").ok();
+        }
+        command.write_help(&mut row, detail).ok();
+        if out.push_str(&row).is_err() {
+            break;
+        }
+        *next += 1;
+    }
+    (!out.is_empty()).then_some(out)
 }
 
-fn cli_parser(input: &str) -> IResult<&str, CliMsg, Error<&str>> {
-    alt((
-        hello_parser,
-        get_time_parser,
-        set_time_parser,
-        get_date_parser,
-        set_date_parser,
-        get_alarm_parser,
-        set_alarm_parser,
-        get_temp_parser,
-    ))(input)
+impl Stream {
+    // Next chunk of output (None when done)
+    pub fn next_chunk(&mut self) -> Option<heapless::String<512>> {
+        if let Stream::Help { topic, next } = self {
+            return help_chunk(topic, next);
+        }
+        let tz = crate::SETTINGS.lock(|s| s.borrow().tz.clone());
+        let mut out: heapless::String<512> = heapless::String::new();
+        crate::TEMP_LOG.lock(|log| {
+            let log = log.borrow();
+            match self {
+                Stream::TempHistory { after } => fill(&mut out, log.hourly(), after, |out, s| {
+                    let t = tz.to_local(s.start);
+                    write!(
+                        out,
+                        "{:02}:{:02}  {:5.1} {:5.1} {:5.1}",
+                        t.hour(),
+                        t.minute(),
+                        s.min,
+                        s.max,
+                        s.avg
+                    )
+                }),
+                Stream::TempCsv { after } => fill(&mut out, log.records(), after, |out, s| {
+                    s.write_csv(out, &tz)
+                }),
+                Stream::Help { .. } => {}
+            }
+        });
+        (!out.is_empty()).then_some(out)
+    }
 }
 
-pub async fn cli(line: &heapless::String<128>) -> heapless::String<128> {
-    let mut out: heapless::String<128> = heapless::String::new();
+pub async fn cli(line: &heapless::String<128>) -> (heapless::String<512>, Option<Stream>) {
+    let mut out: heapless::String<512> = heapless::String::new();
+    let mut stream = None;
     if line.is_empty() {
-        return out;
+        return (out, stream);
     }
-    match cli_parser(line.as_str()) {
-        Ok((_, CliMsg::Hello)) => {
+    match parse_command(line.as_str()) {
+        Ok(CliMsg::Help(topic)) => {
+            stream = Some(Stream::Help { topic, next: 0 });
+        }
+        // Listed by line_input, which holds the history
+        Ok(CliMsg::History) => {}
+        Ok(CliMsg::Hello) => {
             out.push_str("Hello!").ok();
         }
-        Ok((_, CliMsg::GetTime)) => {
+        Ok(CliMsg::GetTime) => {
             let mut rtc_time_rx = crate::RTC_TIME.receiver().unwrap();
             let time = rtc_time_rx.get().await.time();
             write!(
@@ -185,60 +158,225 @@ pub async fn cli(line: &heapless::String<128>) -> heapless::String<128> {
             )
             .ok();
         }
-        Ok((_, CliMsg::GetDate)) => {
+        Ok(CliMsg::GetDate) => {
             let mut rtc_time_rx = crate::RTC_TIME.receiver().unwrap();
             let date = rtc_time_rx.get().await.date();
-            write!(
-                out,
-                "{:02}/{:02}/{:04}",
-                date.day(),
-                date.month(),
-                date.year()
-            )
-            .ok();
+            let format = crate::SETTINGS.lock(|s| s.borrow().date_format);
+            format.write(&mut out, date).ok();
         }
-        Ok((_, CliMsg::GetTemp)) => {
+        Ok(CliMsg::GetTemp) => {
             let mut rtc_temp_rx = crate::RTC_TEMP.receiver().unwrap();
-            let temp = rtc_temp_rx.get().await;
-            write!(out, "Temp: {:.1}°C", temp).ok();
+            match rtc_temp_rx.try_get() {
+                Some(temp) => write!(out, "Temp: {:.1}°C", temp).ok(),
+                None => write!(out, "Temp Not Available").ok(),
+            };
+        }
+        Ok(CliMsg::GetTempNow) => {
+            crate::TEMP_NOW.reset();
+            let msg_pub = crate::MSG_BUS.publisher().unwrap();
+            msg_pub.publish(crate::Msg::ConvertTemp).await;
+            // Don't wait for the next tick
+            crate::RTC_WAKE.signal(());
+            // A conversion takes up to 200ms
+            match with_timeout(Duration::from_secs(1), crate::TEMP_NOW.wait()).await {
+                Ok(Some(temp)) => write!(out, "Temp: {:.2}°C", temp).ok(),
+                _ => write!(out, "Temp Not Available").ok(),
+            };
         }
-        Ok((_, CliMsg::SetTime(t))) => {
+        Ok(CliMsg::SetTime(t)) => {
             let msg_pub = crate::MSG_BUS.publisher().unwrap();
             msg_pub.publish(crate::Msg::SetTime(t)).await;
             out.push_str("OK").ok();
         }
-        Ok((_, CliMsg::SetDate(d))) => {
+        Ok(CliMsg::SetDate(d)) => {
             let msg_pub = crate::MSG_BUS.publisher().unwrap();
             msg_pub.publish(crate::Msg::SetDate(d)).await;
             out.push_str("OK").ok();
         }
-        Ok((_, CliMsg::GetAlarm)) => {
-            let mut rtc_alarm_rx = crate::ALARM1_TIME.receiver().unwrap();
+        Ok(CliMsg::GetAlarm) => {
+            let mut next_alarm_rx = crate::NEXT_ALARM.receiver().unwrap();
+            match next_alarm_rx.try_get() {
+                Some(Some(next)) => write!(out, "Next Alarm: {}", next).ok(),
+                _ => write!(out, "Alarm Not Set").ok(),
+            };
+        }
+        Ok(CliMsg::SetAlarm(schedule)) => {
+            // Default alarm in the alarm table
+            update_alarms(&mut out, |alarms| {
+                alarms.set(alarm_name("alarm").unwrap(), schedule)
+            })
+            .await;
+        }
+        Ok(CliMsg::GetAlarm2) => {
+            let mut rtc_alarm_rx = crate::ALARM2_TIME.receiver().unwrap();
             match rtc_alarm_rx.try_get() {
-                Some(Some(t)) => write!(
-                    out,
-                    "Alarm1: {:02}:{:02}:{:02}",
-                    t.hour(),
-                    t.minute(),
-                    t.second()
-                )
-                .ok(),
-                _ => write!(out, "Alarm1 Not Set").ok(),
+                Some(Some(t)) => write!(out, "Alarm2: {:02}:{:02}", t.hour(), t.minute()).ok(),
+                _ => write!(out, "Alarm2 Not Set").ok(),
             };
         }
-        Ok((_, CliMsg::SetAlarm(t))) => {
+        Ok(CliMsg::SetAlarm2(t)) => {
             let msg_pub = crate::MSG_BUS.publisher().unwrap();
-            msg_pub.publish(crate::Msg::SetAlarm1(t)).await;
+            msg_pub.publish(crate::Msg::SetAlarm2(t)).await;
             out.push_str("OK").ok();
         }
-        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
-            out.push_str("Parse Error <<").ok();
-            out.push_str(e.input).ok();
-            out.push_str(">").ok();
+        Ok(CliMsg::ClearAlarm2) => {
+            let msg_pub = crate::MSG_BUS.publisher().unwrap();
+            msg_pub.publish(crate::Msg::ClearAlarm2).await;
+            out.push_str("OK").ok();
+        }
+        Ok(CliMsg::AlarmAdd(name, schedule)) => {
+            update_alarms(&mut out, |alarms| alarms.add(name, schedule)).await;
+        }
+        Ok(CliMsg::AlarmList) => {
+            crate::SETTINGS.lock(|s| {
+                let alarms = &s.borrow().alarms;
+                for (i, alarm) in alarms.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str("\r\n").ok();
+                    }
+                    write!(out, "{}", alarm).ok();
+                }
+                if alarms.is_empty() {
+                    out.push_str("No Alarms").ok();
+                }
+            });
+        }
+        Ok(CliMsg::AlarmDelete(name)) => {
+            update_alarms(&mut out, |alarms| alarms.delete(&name).map(|_| ())).await;
+        }
+        Ok(CliMsg::AlarmEnable(name)) => {
+            update_alarms(&mut out, |alarms| alarms.set_enabled(&name, true)).await;
+        }
+        Ok(CliMsg::AlarmDisable(name)) => {
+            update_alarms(&mut out, |alarms| alarms.set_enabled(&name, false)).await;
+        }
+        Ok(CliMsg::SetSnooze(m)) => {
+            update_settings(&mut out, |s| s.snooze = m, crate::Msg::SetSnooze(m)).await;
+        }
+        Ok(CliMsg::SetRingTime(m)) => {
+            update_settings(&mut out, |s| s.ring_time = m, crate::Msg::SetRingTime(m)).await;
+        }
+        Ok(CliMsg::SetTempInterval(m)) => {
+            update_settings(
+                &mut out,
+                |s| s.temp_interval = m,
+                crate::Msg::SetTempInterval(m),
+            )
+            .await;
+        }
+        Ok(CliMsg::SetDateFormat(f)) => {
+            update_settings(
+                &mut out,
+                |s| s.date_format = f,
+                crate::Msg::SetDateFormat(f),
+            )
+            .await;
+        }
+        Ok(CliMsg::SetColour(c)) => {
+            update_settings(&mut out, |s| s.colour = c, crate::Msg::SetColour(c)).await;
+        }
+        Ok(CliMsg::SetBrightness(b)) => {
+            update_settings(&mut out, |s| s.brightness = b, crate::Msg::SetBrightness(b)).await;
+        }
+        Ok(CliMsg::GetTimeZone) => {
+            let tz = crate::SETTINGS.lock(|s| s.borrow().tz.clone());
+            let mut rtc_time_rx = crate::RTC_TIME.receiver().unwrap();
+            let utc = tz.to_utc(rtc_time_rx.get().await);
+            write!(out, "{} ({})", tz, tz.name_at(utc)).ok();
+        }
+        Ok(CliMsg::SetTimeZone(tz)) => {
+            update_settings(&mut out, |s| s.tz = tz, crate::Msg::SetTimeZone).await;
+        }
+        Ok(CliMsg::Sync(epoch_ms)) => {
+            let req = SyncRequest {
+                epoch_ms: epoch_ms as i64,
+                received_ms: Instant::now().as_millis(),
+            };
+            crate::SYNC_RESULT.reset();
+            let msg_pub = crate::MSG_BUS.publisher().unwrap();
+            msg_pub.publish(crate::Msg::Sync(req)).await;
+            // Up to 1s to find the seconds edge and 1s to the next second
+            let result = with_timeout(Duration::from_secs(3), crate::SYNC_RESULT.wait()).await;
+            write!(out, "{}", result.unwrap_or(SyncResult::Failed)).ok();
+        }
+        Ok(CliMsg::DriftStatus) => {
+            let aging = crate::AGING.receiver().unwrap().try_get().flatten();
+            crate::SETTINGS.lock(|s| s.borrow().drift.write_status(&mut out, aging).ok());
+        }
+        Ok(CliMsg::DriftCalibrate) => {
+            let aging = crate::AGING.receiver().unwrap().try_get().flatten();
+            let suggested = crate::SETTINGS.lock(|s| s.borrow().drift.suggested_aging());
+            match (aging, suggested) {
+                (None, _) => out.push_str("Aging Not Supported").ok(),
+                (_, None) => out.push_str("Not Enough Sync Data").ok(),
+                (_, Some(aging)) => {
+                    let msg_pub = crate::MSG_BUS.publisher().unwrap();
+                    msg_pub.publish(crate::Msg::SetAging(aging)).await;
+                    write!(out, "Aging: {}", aging).ok()
+                }
+            };
+        }
+        Ok(CliMsg::SetAging(aging)) => match crate::AGING.receiver().unwrap().try_get().flatten() {
+            Some(_) => publish(&mut out, crate::Msg::SetAging(aging)).await,
+            None => {
+                out.push_str("Aging Not Supported").ok();
+            }
+        },
+        Ok(CliMsg::RtcStatus) => {
+            match crate::RTC_HEALTH.receiver().unwrap().try_get() {
+                Some(health) => write!(out, "{}", health).ok(),
+                None => out.push_str("RTC Not Started").ok(),
+            };
+        }
+        Ok(CliMsg::TempHistory) => {
+            // Hourly for the last day
+            match crate::TEMP_LOG.lock(|log| log.borrow().latest()) {
+                Some(latest) => {
+                    out.push_str("Hour     Min   Max   Avg").ok();
+                    stream = Some(Stream::TempHistory {
+                        after: Some(latest.start - TimeDelta::hours(24)),
+                    });
+                }
+                None => {
+                    out.push_str("No Temperature History").ok();
+                }
+            }
+        }
+        Ok(CliMsg::TempStats) => {
+            let tz = crate::SETTINGS.lock(|s| s.borrow().tz.clone());
+            crate::TEMP_LOG.lock(|log| {
+                let log = log.borrow();
+                if log.is_empty() {
+                    out.push_str("No Temperature History").ok();
+                    return;
+                }
+                out.push_str("Date          Min   Max   Avg").ok();
+                for (date, s) in log.daily(&tz) {
+                    write!(
+                        out,
+                        "\r\n{}  {:5.1} {:5.1} {:5.1}",
+                        date, s.min, s.max, s.avg
+                    )
+                    .ok();
+                }
+            });
+        }
+        Ok(CliMsg::TempDumpCsv) => {
+            out.push_str(CSV_HEADER).ok();
+            stream = Some(Stream::TempCsv { after: None });
+        }
+        Ok(CliMsg::GpsStatus) => {
+            match crate::GPS_STATUS.receiver().unwrap().try_get() {
+                Some(status) => write!(out, "{}", status).ok(),
+                None => out.push_str("GPS Not Enabled").ok(),
+            };
         }
-        Err(_) => {
-            out.push_str("Error").ok();
+        Ok(CliMsg::ConfigSave) => publish(&mut out, crate::Msg::SaveSettings).await,
+        Ok(CliMsg::ConfigLoad) => publish(&mut out, crate::Msg::LoadSettings).await,
+        Ok(CliMsg::ConfigReset) => publish(&mut out, crate::Msg::ResetSettings).await,
+        Err(e) => {
+            write!(out, "{}", e).ok();
         }
     }
-    out
+    (out, stream)
 }
@@ -1,5 +1,5 @@
 use chrono::NaiveTime;
-use chrono::{Datelike, NaiveDateTime, Timelike};
+use chrono::{NaiveDateTime, Timelike};
 use core::fmt::Write;
 use defmt::{debug, info};
 use display_interface_spi::SPIInterface;
@@ -16,56 +16,72 @@ use embedded_graphics::{
     pixelcolor::Rgb565,
     prelude::*,
     primitives::rectangle::Rectangle,
-    primitives::PrimitiveStyle,
+    primitives::{Line, PrimitiveStyle, Triangle},
     text::{Alignment, Text},
 };
 use embedded_hal_bus::spi::ExclusiveDevice;
 use ili9341::{DisplaySize240x320, Ili9341, Orientation};
-use profont::{PROFONT_18_POINT, PROFONT_24_POINT};
+use profont::{PROFONT_12_POINT, PROFONT_18_POINT, PROFONT_24_POINT};
+use stm32f401_embassy::alarm::{NextAlarm, Schedule};
+use stm32f401_embassy::gps::GpsStatus;
+use stm32f401_embassy::layout::*;
+use stm32f401_embassy::ringer::AlarmState;
+use stm32f401_embassy::settings::{Colour, DateFormat};
+use stm32f401_embassy::temp_graph::Graph;
 
 pub type DisplaySpi = embassy_stm32::peripherals::SPI2;
 pub type DisplaySpiSck = embassy_stm32::peripherals::PB13;
 pub type DisplaySpiMosi = embassy_stm32::peripherals::PB15;
 pub type DisplaySpiRxDma = embassy_stm32::peripherals::DMA1_CH4;
 
-// 7-segment display
-const DIGIT_WIDTH: u32 = 32;
-const DIGIT_HEIGHT: u32 = 64;
-const DIGIT_SPACING: u32 = 4;
-const SEGMENT_WIDTH: u32 = 8;
-const SEGMENT_COLOUR: Rgb565 = Rgb565::GREEN;
+const TITLE_COLOUR: Rgb565 = Rgb565::RED;
+const WARNING_COLOUR: Rgb565 = Rgb565::MAGENTA;
 const BACKGROUND_COLOUR: Rgb565 = Rgb565::WHITE;
-const START_Y: i32 = 60;
-const START_X: i32 = 2;
-const DATE_X: i32 = 20;
-const DATE_Y: i32 = START_Y + DIGIT_HEIGHT as i32 + 40;
 const DATE_COLOUR: Rgb565 = Rgb565::BLUE;
-const DATE_WIDTH: u32 = 200;
-const DATE_HEIGHT: u32 = 24;
-const TEMP_X: i32 = 20;
-const TEMP_Y: i32 = DATE_Y + 40;
 const TEMP_COLOUR: Rgb565 = Rgb565::BLUE;
-const TEMP_WIDTH: u32 = 200;
-const TEMP_HEIGHT: u32 = 24;
-const ALARM1_X: i32 = 20;
-const ALARM1_Y: i32 = TEMP_Y + 40;
 const ALARM1_COLOUR: Rgb565 = Rgb565::BLUE;
-const ALARM1_WIDTH: u32 = 200;
-const ALARM1_HEIGHT: u32 = 24;
-
-// START_X | DIGIT | SP | DIGIT | SP | SEP | SP | DIGIT | SP | DIGIT | SP | SEP | SP | DIGIT | SEP
-const SEPARATOR_OFFSETS: [i32; 2] = [
-    START_X + (2 * DIGIT_WIDTH + 2 * DIGIT_SPACING) as i32,
-    START_X + (4 * DIGIT_WIDTH + 5 * DIGIT_SPACING + SEGMENT_WIDTH) as i32,
-];
-const DIGIT_OFFSETS: [i32; 6] = [
-    START_X,
-    START_X + (DIGIT_WIDTH + DIGIT_SPACING) as i32,
-    START_X + (2 * DIGIT_WIDTH + 3 * DIGIT_SPACING + SEGMENT_WIDTH) as i32,
-    START_X + (3 * DIGIT_WIDTH + 4 * DIGIT_SPACING + SEGMENT_WIDTH) as i32,
-    START_X + (4 * DIGIT_WIDTH + 6 * DIGIT_SPACING + 2 * SEGMENT_WIDTH) as i32,
-    START_X + (5 * DIGIT_WIDTH + 7 * DIGIT_SPACING + 2 * SEGMENT_WIDTH) as i32,
-];
+const ALARM2_COLOUR: Rgb565 = Rgb565::BLUE;
+const GPS_FIX_COLOUR: Rgb565 = Rgb565::GREEN;
+const GPS_NO_FIX_COLOUR: Rgb565 = Rgb565::RED;
+const GRAPH_COLOUR: Rgb565 = Rgb565::BLUE;
+const GRAPH_AXIS_COLOUR: Rgb565 = Rgb565::BLACK;
+const GRAPH_GRID_COLOUR: Rgb565 = Rgb565::new(24, 48, 24);
+const GRAPH_LABEL_COLOUR: Rgb565 = Rgb565::BLACK;
+const GRAPH_MIN_COLOUR: Rgb565 = Rgb565::BLUE;
+const GRAPH_MAX_COLOUR: Rgb565 = Rgb565::RED;
+
+// Samples further apart than this (or two sample intervals) are not joined
+// (minutes)
+const GRAPH_GAP: i64 = 5;
+
+// Pages below the title, changed by a short button press
+#[derive(Clone, Copy, PartialEq)]
+enum Page {
+    Clock,
+    Graph,
+}
+
+// Clock digit colour setting
+fn segment_colour(colour: Colour) -> Rgb565 {
+    match colour {
+        Colour::Green => Rgb565::GREEN,
+        Colour::Red => Rgb565::RED,
+        Colour::Blue => Rgb565::BLUE,
+        Colour::Yellow => Rgb565::YELLOW,
+        Colour::Cyan => Rgb565::CYAN,
+        Colour::Magenta => Rgb565::MAGENTA,
+        Colour::Black => Rgb565::BLACK,
+    }
+}
+
+// (digit colour, date format, backlight on)
+fn display_settings() -> (Rgb565, DateFormat, bool) {
+    crate::SETTINGS.lock(|s| {
+        let s = s.borrow();
+        // PB12 has no timer channel so the backlight is on/off only
+        (segment_colour(s.colour), s.date_format, s.brightness > 0)
+    })
+}
 
 pub struct DisplayPins {
     pub sck: DisplaySpiSck,
@@ -110,23 +126,17 @@ pub async fn display(pins: DisplayPins, spi: DisplaySpi, rxdma: DisplaySpiRxDma)
     // let mut scroll = display.configure_vertical_scroll(30, 2).unwrap();
 
     info!("Starting Display");
-    lcd_backlight.set_high();
+    let (mut colour, mut date_format, backlight) = display_settings();
+    lcd_backlight.set_level(Level::from(backlight));
 
     display.clear(BACKGROUND_COLOUR).ok();
 
-    Text::with_alignment(
-        "DS3231 RTC",
-        Point::new(20, 29),
-        MonoTextStyle::new(&PROFONT_24_POINT, Rgb565::RED),
-        Alignment::Left,
-    )
-    .draw(&mut display)
-    .ok();
+    draw_title(&mut display, AlarmState::Idle, None);
 
     debug!("DIGIT OFFSETS >> {:?}", DIGIT_OFFSETS);
     debug!("SEPARATOR OFFSETS >> {:?}", SEPARATOR_OFFSETS);
 
-    draw_separators(&mut display);
+    draw_separators(&mut display, colour);
 
     // Store previous time so that we can clear only changed digits
     let mut prev: Option<NaiveDateTime> = None;
@@ -137,26 +147,46 @@ pub async fn display(pins: DisplayPins, spi: DisplaySpi, rxdma: DisplaySpiRxDma)
     // Receivers for watch values
     let mut rtc_time_rx = crate::RTC_TIME.receiver().unwrap();
     let mut rtc_temp_rx = crate::RTC_TEMP.receiver().unwrap();
-    let mut alarm1_time_rx = crate::ALARM1_TIME.receiver().unwrap();
+    let mut next_alarm_rx = crate::NEXT_ALARM.receiver().unwrap();
+    let mut alarm2_time_rx = crate::ALARM2_TIME.receiver().unwrap();
+    let mut gps_status_rx = crate::GPS_STATUS.receiver().unwrap();
+    let mut rtc_health_rx = crate::RTC_HEALTH.receiver().unwrap();
     // let mut alarm1_match_rx = crate::ALARM1_MATCH.receiver().unwrap();
 
-    let mut current_temp: f32 = 0.0;
-    let mut current_alarm1_time: Option<NaiveTime> = None;
+    let mut page = Page::Clock;
+    let mut redraw_page = false;
+    // Graph state (only while the graph is shown)
+    let mut graph: Option<GraphPage> = None;
+
+    let mut current_temp: Option<f32> = None;
+    let mut current_next_alarm: Option<NextAlarm> = None;
+    let mut current_alarm2_time: Option<NaiveTime> = None;
+    // GPS fix (None without a GPS)
+    let mut current_gps_fix: Option<bool> = None;
+    // Title shows the alarm state, or an RTC warning while idle
+    let mut current_alarm_state = AlarmState::Idle;
+    let mut current_rtc_warning: Option<&'static str> = None;
     // let mut current_alarm1_match: bool = false;
 
     // Get initial values
     let t = rtc_time_rx.get().await;
     info!("Clock: {:02}:{:02}:{:02}", t.hour(), t.minute(), t.second());
-    prev = draw_clock(&mut display, t, prev);
+    prev = draw_clock(&mut display, t, prev, colour, date_format);
     if let Some(temp) = rtc_temp_rx.try_get() {
         draw_temp(&mut display, temp);
-        current_temp = temp;
+        current_temp = Some(temp);
+    }
+    if let Some(next_alarm) = next_alarm_rx.try_get() {
+        draw_alarm(&mut display, 1, next_alarm.as_ref());
+        current_next_alarm = next_alarm;
+    } else {
+        draw_alarm(&mut display, 1, None::<NextAlarm>);
     }
-    if let Some(alarm_time) = alarm1_time_rx.try_get() {
-        draw_alarm(&mut display, alarm_time);
-        current_alarm1_time = alarm_time;
+    if let Some(alarm_time) = alarm2_time_rx.try_get() {
+        draw_alarm(&mut display, 2, alarm_time.map(Schedule::Daily));
+        current_alarm2_time = alarm_time;
     } else {
-        draw_alarm(&mut display, None);
+        draw_alarm(&mut display, 2, None::<Schedule>);
     }
 
     // Loop - update every second (await RTC_TIME update)
@@ -165,32 +195,180 @@ pub async fn display(pins: DisplayPins, spi: DisplaySpi, rxdma: DisplaySpiRxDma)
         while let Some(msg) = sub.try_next_message() {
             match msg {
                 WaitResult::Lagged(_) => {}
+                WaitResult::Message(crate::Msg::AlarmState(state)) => {
+                    draw_title(&mut display, state, current_rtc_warning);
+                    current_alarm_state = state;
+                    // Show the clock while ringing
+                    if matches!(state, AlarmState::Ringing { .. }) && page != Page::Clock {
+                        page = Page::Clock;
+                        redraw_page = true;
+                    }
+                }
+                WaitResult::Message(crate::Msg::NextPage) => {
+                    // The press snoozes instead while an alarm is active
+                    if matches!(
+                        current_alarm_state,
+                        AlarmState::Idle | AlarmState::Dismissed
+                    ) {
+                        page = match page {
+                            Page::Clock => Page::Graph,
+                            Page::Graph => Page::Clock,
+                        };
+                        redraw_page = true;
+                    }
+                }
+                WaitResult::Message(
+                    crate::Msg::SetColour(_)
+                    | crate::Msg::SetDateFormat(_)
+                    | crate::Msg::SetBrightness(_)
+                    | crate::Msg::SettingsLoaded,
+                ) => {
+                    let backlight;
+                    (colour, date_format, backlight) = display_settings();
+                    lcd_backlight.set_level(Level::from(backlight));
+                    if page == Page::Clock {
+                        draw_separators(&mut display, colour);
+                    }
+                    // Redraw all digits and date
+                    prev = None;
+                }
                 WaitResult::Message(m) => {
                     info!("Message: {:?}", m);
                 }
             }
         }
-        prev = draw_clock(&mut display, t, prev);
-        if let Some(alarm_time) = alarm1_time_rx.try_changed() {
+        if redraw_page {
+            redraw_page = false;
+            graph = None;
+            match page {
+                Page::Clock => {
+                    clear_page(&mut display);
+                    draw_separators(&mut display, colour);
+                    prev = None;
+                    if let Some(temp) = current_temp {
+                        draw_temp(&mut display, temp);
+                    }
+                    draw_alarm(&mut display, 1, current_next_alarm.as_ref());
+                    draw_alarm(&mut display, 2, current_alarm2_time.map(Schedule::Daily));
+                }
+                Page::Graph => graph = Some(draw_graph_page(&mut display, t)),
+            }
+        }
+        // Values are tracked on either page but only drawn on the clock page
+        let clock = page == Page::Clock;
+        if clock {
+            prev = draw_clock(&mut display, t, prev, colour, date_format);
+        }
+        if let Some(graph) = graph.as_mut() {
+            update_graph(&mut display, graph, t);
+        }
+        if let Some(next_alarm) = next_alarm_rx.try_changed() {
             // Update alarm
-            if alarm_time != current_alarm1_time {
-                draw_alarm(&mut display, alarm_time);
-                current_alarm1_time = alarm_time;
+            if next_alarm != current_next_alarm {
+                if clock {
+                    draw_alarm(&mut display, 1, next_alarm.as_ref());
+                }
+                current_next_alarm = next_alarm;
+            }
+        }
+        if let Some(alarm_time) = alarm2_time_rx.try_changed() {
+            if alarm_time != current_alarm2_time {
+                if clock {
+                    draw_alarm(&mut display, 2, alarm_time.map(Schedule::Daily));
+                }
+                current_alarm2_time = alarm_time;
+            }
+        }
+        if let Some(status) = gps_status_rx.try_changed() {
+            if Some(status.fix) != current_gps_fix {
+                draw_gps(&mut display, &status);
+                current_gps_fix = Some(status.fix);
+            }
+        }
+        if let Some(health) = rtc_health_rx.try_changed() {
+            if health.warning() != current_rtc_warning {
+                draw_title(&mut display, current_alarm_state, health.warning());
+                current_rtc_warning = health.warning();
             }
         }
         if t.second() == 0 {
             // Update temp
             if let Some(temp) = rtc_temp_rx.try_changed() {
-                if temp != current_temp {
-                    draw_temp(&mut display, temp);
-                    current_temp = temp;
+                if Some(temp) != current_temp {
+                    if clock {
+                        draw_temp(&mut display, temp);
+                    }
+                    current_temp = Some(temp);
                 }
             }
         }
     }
 }
 
-fn draw_separators<D>(display: &mut D)
+// Title doubles as the alarm status (and RTC warning when no alarm is active)
+fn draw_title<D>(display: &mut D, state: AlarmState, warning: Option<&str>)
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    let (title, colour) = match (state, warning) {
+        (AlarmState::Ringing { .. }, _) => ("** ALARM **", TITLE_COLOUR),
+        (AlarmState::Snoozed { .. }, _) => ("Snoozed", TITLE_COLOUR),
+        (AlarmState::Idle | AlarmState::Dismissed, Some(warning)) => (warning, WARNING_COLOUR),
+        (AlarmState::Idle | AlarmState::Dismissed, None) => ("DS3231 RTC", TITLE_COLOUR),
+    };
+
+    // Clear title
+    let background_style = PrimitiveStyle::with_fill(BACKGROUND_COLOUR);
+    Rectangle::new(
+        Point::new(TITLE_X, TITLE_Y - TITLE_HEIGHT as i32),
+        Size::new(TITLE_WIDTH, TITLE_HEIGHT + 4), // Handle descender
+    )
+    .into_styled(background_style)
+    .draw(display)
+    .ok();
+
+    Text::with_alignment(
+        title,
+        Point::new(TITLE_X, TITLE_Y),
+        MonoTextStyle::new(&PROFONT_24_POINT, colour),
+        Alignment::Left,
+    )
+    .draw(display)
+    .ok();
+}
+
+// "GPS" in green with a fix, red without
+fn draw_gps<D>(display: &mut D, status: &GpsStatus)
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    let colour = if status.fix {
+        GPS_FIX_COLOUR
+    } else {
+        GPS_NO_FIX_COLOUR
+    };
+
+    // Clear indicator
+    let background_style = PrimitiveStyle::with_fill(BACKGROUND_COLOUR);
+    Rectangle::new(
+        Point::new(GPS_X, GPS_Y - GPS_HEIGHT as i32),
+        Size::new(GPS_WIDTH, GPS_HEIGHT + 4), // Handle descender
+    )
+    .into_styled(background_style)
+    .draw(display)
+    .ok();
+
+    Text::with_alignment(
+        "GPS",
+        Point::new(GPS_X, GPS_Y),
+        MonoTextStyle::new(&PROFONT_18_POINT, colour),
+        Alignment::Left,
+    )
+    .draw(display)
+    .ok();
+}
+
+fn draw_separators<D>(display: &mut D, colour: Rgb565)
 where
     D: DrawTarget<Color = Rgb565>,
 {
@@ -198,7 +376,7 @@ where
         .digit_size(Size::new(DIGIT_WIDTH, DIGIT_HEIGHT))
         .digit_spacing(DIGIT_SPACING)
         .segment_width(SEGMENT_WIDTH)
-        .segment_color(SEGMENT_COLOUR)
+        .segment_color(colour)
         .build();
 
     for x_offset in SEPARATOR_OFFSETS {
@@ -212,39 +390,47 @@ where
     }
 }
 
-fn digits(t: NaiveDateTime) -> [u8; 6] {
-    let (h, m, s) = (
-        t.time().hour() as u8,
-        t.time().minute() as u8,
-        t.time().second() as u8,
-    );
-    [h / 10, h % 10, m / 10, m % 10, s / 10, s % 10]
-}
-
-fn draw_alarm<D>(display: &mut D, alarm_time: Option<NaiveTime>)
+// Alarm 1 row shows the next alarm due from the alarm table
+fn draw_alarm<D, T>(display: &mut D, alarm: u8, value: Option<T>)
 where
     D: DrawTarget<Color = Rgb565>,
+    T: core::fmt::Display,
 {
-    let mut s: heapless::String<24> = heapless::String::new();
+    let mut s: heapless::String<40> = heapless::String::new();
 
-    let _ = match alarm_time {
-        Some(t) => write!(
-            s,
-            "Alarm 1: {:02}:{:02}:{:02}",
-            t.hour(),
-            t.minute(),
-            t.second()
-        ),
-        None => write!(s, "Alarm 1: Not Set"),
+    let label = match alarm {
+        1 => "Next",
+        _ => "Alarm 2",
+    };
+    let _ = match value {
+        Some(value) => write!(s, "{}: {}", label, value),
+        None => write!(s, "{}: Not Set", label),
     };
 
     info!("Draw Alarm: {}", s.as_str());
 
+    let (x, y, width, height, colour) = match alarm {
+        1 => (
+            ALARM1_X,
+            ALARM1_Y,
+            ALARM1_WIDTH,
+            ALARM1_HEIGHT,
+            ALARM1_COLOUR,
+        ),
+        _ => (
+            ALARM2_X,
+            ALARM2_Y,
+            ALARM2_WIDTH,
+            ALARM2_HEIGHT,
+            ALARM2_COLOUR,
+        ),
+    };
+
     // Clear alarm
     let background_style = PrimitiveStyle::with_fill(BACKGROUND_COLOUR);
     Rectangle::new(
-        Point::new(ALARM1_X, ALARM1_Y - ALARM1_HEIGHT as i32),
-        Size::new(ALARM1_WIDTH, ALARM1_HEIGHT + 4), // Handle descender
+        Point::new(x, y - height as i32),
+        Size::new(width, height + 4), // Handle descender
     )
     .into_styled(background_style)
     .draw(display)
@@ -252,8 +438,8 @@ where
 
     Text::with_alignment(
         s.as_str(),
-        Point::new(ALARM1_X, ALARM1_Y),
-        MonoTextStyle::new(&PROFONT_18_POINT, ALARM1_COLOUR),
+        Point::new(x, y),
+        MonoTextStyle::new(&PROFONT_18_POINT, colour),
         Alignment::Left,
     )
     .draw(display)
@@ -288,10 +474,252 @@ where
     .ok();
 }
 
+// Clear everything below the title row
+fn clear_page<D>(display: &mut D)
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    let top = TITLE_Y + 4;
+    Rectangle::new(
+        Point::new(0, top),
+        Size::new(SCREEN_WIDTH, SCREEN_HEIGHT - top as u32),
+    )
+    .into_styled(PrimitiveStyle::with_fill(BACKGROUND_COLOUR))
+    .draw(display)
+    .ok();
+}
+
+// Temperature graph page state - samples are plotted as they are logged
+struct GraphPage {
+    graph: Graph,
+    // Last sample plotted (UTC) and its point
+    last: Option<(NaiveDateTime, Point)>,
+    // Range of the samples plotted
+    min: f32,
+    max: f32,
+    // Longest gap joined by a line (minutes)
+    gap: i64,
+}
+
+impl GraphPage {
+    fn new(graph: Graph, interval: u8) -> Self {
+        GraphPage {
+            graph,
+            last: None,
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+            gap: GRAPH_GAP.max(2 * interval as i64),
+        }
+    }
+
+    // Join a sample to the previous one (unless there is a gap)
+    fn plot<D>(&mut self, display: &mut D, t: NaiveDateTime, temp: f32)
+    where
+        D: DrawTarget<Color = Rgb565>,
+    {
+        let Some(x) = self.graph.x(t) else {
+            return;
+        };
+        let p = Point::new(GRAPH_X + x, GRAPH_Y + self.graph.y(temp));
+        match self.last {
+            Some((last_t, last_p)) if (t - last_t).num_minutes() <= self.gap => {
+                Line::new(last_p, p)
+                    .into_styled(PrimitiveStyle::with_stroke(GRAPH_COLOUR, 1))
+                    .draw(display)
+                    .ok();
+            }
+            _ => {
+                Pixel(p, GRAPH_COLOUR).draw(display).ok();
+            }
+        }
+        self.last = Some((t, p));
+        self.min = self.min.min(temp);
+        self.max = self.max.max(temp);
+    }
+}
+
+// Draw the whole graph from the temperature log (`now` is local time, used
+// for the window until there are samples)
+fn draw_graph_page<D>(display: &mut D, now: NaiveDateTime) -> GraphPage
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    let (tz, interval) = crate::SETTINGS.lock(|s| {
+        let s = s.borrow();
+        (s.tz.clone(), s.temp_interval)
+    });
+    let mut samples: heapless::Vec<(NaiveDateTime, f32), { GRAPH_WIDTH as usize }> =
+        heapless::Vec::new();
+    let graph = crate::TEMP_LOG.lock(|log| {
+        let log = log.borrow();
+        let latest = log.latest().map_or(tz.to_utc(now), |s| s.start);
+        let graph = Graph::fit(latest, log.records().map(|s| (s.start, s.avg)));
+        for s in log.records().filter(|s| graph.x(s.start).is_some()) {
+            samples.push((s.start, s.avg)).ok();
+        }
+        graph
+    });
+
+    clear_page(display);
+
+    let (width, height) = (GRAPH_WIDTH as i32, GRAPH_HEIGHT as i32);
+    let grid_style = PrimitiveStyle::with_stroke(GRAPH_GRID_COLOUR, 1);
+    let label_style = MonoTextStyle::new(&PROFONT_12_POINT, GRAPH_LABEL_COLOUR);
+    let mut s: heapless::String<8> = heapless::String::new();
+
+    // Temperature grid, labelled on the left
+    for temp in graph.ticks() {
+        let y = GRAPH_Y + graph.y(temp as f32);
+        Line::new(Point::new(GRAPH_X, y), Point::new(GRAPH_X + width - 1, y))
+            .into_styled(grid_style)
+            .draw(display)
+            .ok();
+        s.clear();
+        write!(s, "{}", temp).ok();
+        Text::with_alignment(
+            s.as_str(),
+            Point::new(GRAPH_X - 4, y + 4),
+            label_style,
+            Alignment::Right,
+        )
+        .draw(display)
+        .ok();
+    }
+
+    // Hour lines, labelled below in local time
+    for (x, hour) in graph.hours() {
+        let x = GRAPH_X + x;
+        if x > GRAPH_X && x < GRAPH_X + width {
+            Line::new(Point::new(x, GRAPH_Y), Point::new(x, GRAPH_Y + height - 1))
+                .into_styled(grid_style)
+                .draw(display)
+                .ok();
+        }
+        let local = tz.to_local(hour);
+        s.clear();
+        write!(s, "{:02}:{:02}", local.hour(), local.minute()).ok();
+        Text::with_alignment(
+            s.as_str(),
+            Point::new(x, GRAPH_LABEL_Y),
+            label_style,
+            Alignment::Center,
+        )
+        .draw(display)
+        .ok();
+    }
+
+    // Axes
+    Rectangle::new(
+        Point::new(GRAPH_X - 1, GRAPH_Y - 1),
+        Size::new(GRAPH_WIDTH + 2, GRAPH_HEIGHT + 2),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(GRAPH_AXIS_COLOUR, 1))
+    .draw(display)
+    .ok();
+
+    let mut page = GraphPage::new(graph, interval);
+    for (t, temp) in samples {
+        page.plot(display, t, temp);
+    }
+    if page.last.is_none() {
+        Text::with_alignment(
+            "No temperature data",
+            Point::new(GRAPH_X + width / 2, GRAPH_Y + height / 2),
+            label_style,
+            Alignment::Center,
+        )
+        .draw(display)
+        .ok();
+    }
+    draw_graph_markers(display, &page);
+    page
+}
+
+// Min/max markers to the right of the graph, and their values below it
+fn draw_graph_markers<D>(display: &mut D, page: &GraphPage)
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    let background_style = PrimitiveStyle::with_fill(BACKGROUND_COLOUR);
+    let left = GRAPH_X + GRAPH_WIDTH as i32 + 1;
+    let right = left + GRAPH_MARKER_WIDTH as i32 - 1;
+    let half = GRAPH_MARKER_WIDTH as i32 / 2;
+
+    // Clear markers (they overhang the top and bottom of the graph)
+    Rectangle::new(
+        Point::new(left, GRAPH_Y - half),
+        Size::new(GRAPH_MARKER_WIDTH, GRAPH_HEIGHT + 2 * half as u32),
+    )
+    .into_styled(background_style)
+    .draw(display)
+    .ok();
+
+    // Clear values
+    Rectangle::new(
+        Point::new(GRAPH_MINMAX_X, GRAPH_MINMAX_Y - GRAPH_MINMAX_HEIGHT as i32),
+        Size::new(GRAPH_MINMAX_WIDTH, GRAPH_MINMAX_HEIGHT + 4), // Handle descender
+    )
+    .into_styled(background_style)
+    .draw(display)
+    .ok();
+
+    if page.last.is_none() {
+        return;
+    }
+
+    // Triangles pointing at the graph
+    for (temp, colour) in [(page.min, GRAPH_MIN_COLOUR), (page.max, GRAPH_MAX_COLOUR)] {
+        let y = GRAPH_Y + page.graph.y(temp);
+        Triangle::new(
+            Point::new(left, y),
+            Point::new(right, y - half),
+            Point::new(right, y + half),
+        )
+        .into_styled(PrimitiveStyle::with_fill(colour))
+        .draw(display)
+        .ok();
+    }
+
+    let mut s: heapless::String<32> = heapless::String::new();
+    write!(s, "Min {:.1}° Max {:.1}°", page.min, page.max).ok();
+    Text::with_alignment(
+        s.as_str(),
+        Point::new(GRAPH_MINMAX_X, GRAPH_MINMAX_Y),
+        MonoTextStyle::new(&PROFONT_18_POINT, TEMP_COLOUR),
+        Alignment::Left,
+    )
+    .draw(display)
+    .ok();
+}
+
+// Plot the latest sample if it fits, otherwise redraw (the window has moved
+// on or the sample is out of range)
+fn update_graph<D>(display: &mut D, page: &mut GraphPage, now: NaiveDateTime)
+where
+    D: DrawTarget<Color = Rgb565>,
+{
+    let Some(latest) = crate::TEMP_LOG.lock(|log| log.borrow().latest()) else {
+        return;
+    };
+    match page.last {
+        Some((t, _)) if t == latest.start => {}
+        Some((t, _)) if t < latest.start && page.graph.contains(latest.start, latest.avg) => {
+            let range = (page.min, page.max);
+            page.plot(display, latest.start, latest.avg);
+            if (page.min, page.max) != range {
+                draw_graph_markers(display, page);
+            }
+        }
+        _ => *page = draw_graph_page(display, now),
+    }
+}
+
 fn draw_clock<D>(
     display: &mut D,
     t: NaiveDateTime,
     t_prev: Option<NaiveDateTime>,
+    colour: Rgb565,
+    date_format: DateFormat,
 ) -> Option<NaiveDateTime>
 where
     D: DrawTarget<Color = Rgb565>,
@@ -301,7 +729,7 @@ where
         .digit_size(Size::new(DIGIT_WIDTH, DIGIT_HEIGHT))
         .digit_spacing(DIGIT_SPACING)
         .segment_width(SEGMENT_WIDTH)
-        .segment_color(SEGMENT_COLOUR)
+        .segment_color(colour)
         .build();
 
     let prev_digits = match t_prev {
@@ -334,17 +762,9 @@ where
         }
     }) == None
     {
-        let date = t.date();
         let mut s: heapless::String<24> = heapless::String::new();
 
-        write!(
-            s,
-            "{:02}/{:02}/{:04}",
-            date.day(),
-            date.month(),
-            date.year()
-        )
-        .ok();
+        date_format.write(&mut s, t.date()).ok();
 
         // Clear date
         Rectangle::new(
@@ -0,0 +1,97 @@
+use chrono::{NaiveDateTime, TimeDelta};
+use defmt::{info, warn};
+use embassy_stm32::{
+    bind_interrupts,
+    exti::{AnyChannel, ExtiInput},
+    gpio::{AnyPin, Pull},
+    usart::{self, BufferedUartRx},
+};
+use embassy_time::{with_timeout, Duration};
+use embedded_io_async::Read;
+use static_cell::StaticCell;
+use stm32f401_embassy::gps::{local_time, GpsStatus};
+use stm32f401_embassy::nmea::{self, LineBuffer};
+
+// GPS receiver TX -> PA3 (USART2 RX), PPS -> PA8 (optional)
+pub type GpsUart = embassy_stm32::peripherals::USART2;
+pub type GpsRxPin = embassy_stm32::peripherals::PA3;
+
+bind_interrupts!(struct Irqs {
+    USART2 => usart::BufferedInterruptHandler<GpsUart>;
+});
+
+const BAUD_RATE: u32 = 9600;
+// Over a second of sentences at 9600 baud (read stalls while waiting for PPS)
+const RX_BUFFER_SIZE: usize = 1024;
+
+// Set the RTC (local time) - with PPS on the next edge, so `local` must be the
+// time at that edge. Returns false if the edge was missed.
+async fn set_clock(
+    rtc: NaiveDateTime,
+    local: NaiveDateTime,
+    pps: Option<&mut ExtiInput<'static>>,
+) -> bool {
+    if let Some(pps) = pps {
+        let edge = with_timeout(Duration::from_millis(1100), pps.wait_for_rising_edge());
+        if edge.await.is_err() {
+            return false;
+        }
+    }
+    crate::rtc_task::set_clock(rtc, local).await;
+    true
+}
+
+#[embassy_executor::task]
+pub async fn gps(uart: GpsUart, rx: GpsRxPin, pps: Option<(AnyPin, AnyChannel)>) {
+    static RX_BUFFER: StaticCell<[u8; RX_BUFFER_SIZE]> = StaticCell::new();
+    let mut config = usart::Config::default();
+    config.baudrate = BAUD_RATE;
+    let mut rx =
+        BufferedUartRx::new(uart, Irqs, rx, RX_BUFFER.init([0; RX_BUFFER_SIZE]), config).unwrap();
+    let mut pps = pps.map(|(pin, exti)| ExtiInput::new(pin, exti, Pull::None));
+
+    let status_tx = crate::GPS_STATUS.sender();
+    let mut rtc_time_rx = crate::RTC_TIME.receiver().unwrap();
+    let mut status = GpsStatus::new();
+    let mut line = LineBuffer::new();
+    let mut buf = [0u8; 64];
+
+    info!("Starting GPS (PPS {})", pps.is_some());
+    status_tx.send(status.clone());
+    loop {
+        let n = match rx.read(&mut buf).await {
+            Ok(n) => n,
+            Err(e) => {
+                warn!("GPS UART error: {:?}", e);
+                continue;
+            }
+        };
+        for &b in &buf[..n] {
+            let Some(sentence) = line.push(b).and_then(nmea::parse) else {
+                continue;
+            };
+            let utc = status.update(&sentence);
+            if let (Some(utc), Some(rtc)) = (utc, rtc_time_rx.try_get()) {
+                // With PPS the RTC is set on the next edge (the following second)
+                let utc = match pps {
+                    Some(_) => utc + TimeDelta::seconds(1),
+                    None => utc,
+                };
+                let tz = crate::SETTINGS.lock(|s| s.borrow().tz.clone());
+                let local = local_time(&tz, utc)
+                    .filter(|local| status.needs_sync(rtc, *local, utc, pps.is_some()));
+                if let Some(local) = local {
+                    let synced = set_clock(rtc, local, pps.as_mut()).await;
+                    if pps.is_some() {
+                        status.pps = synced;
+                    }
+                    if synced {
+                        info!("RTC set from GPS");
+                        status.last_sync = Some(utc);
+                    }
+                }
+            }
+            status_tx.send(status.clone());
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use defmt::warn;
+use embassy_stm32::gpio::{Level, OutputOpenDrain, Speed};
+use embassy_stm32::i2c::{self, I2c};
+use embassy_stm32::mode::Async;
+use embassy_stm32::time::Hertz;
+use embassy_stm32::{bind_interrupts, peripherals, Peripheral};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{with_timeout, Duration};
+use embedded_hal::i2c::{ErrorKind, ErrorType, Operation};
+use static_cell::StaticCell;
+
+// I2C1 is shared by the DS3231 and the AT24C32 EEPROM on the RTC module
+pub type I2cPeripheral = peripherals::I2C1;
+pub type I2cSclPin = peripherals::PB8;
+pub type I2cSdaPin = peripherals::PB9;
+pub type I2cTxDma = peripherals::DMA1_CH6;
+pub type I2cRxDma = peripherals::DMA1_CH0;
+
+bind_interrupts!(struct Irqs {
+    I2C1_EV => i2c::EventInterruptHandler<I2cPeripheral>;
+    I2C1_ER => i2c::ErrorInterruptHandler<I2cPeripheral>;
+});
+
+const FREQUENCY: Hertz = Hertz(400_000);
+// A DS3231 read takes well under a millisecond at 400kHz
+const TIMEOUT: Duration = Duration::from_millis(20);
+// Half an SCL period while recovering the bus (at most 100kHz). Counted in
+// cycles of the 60MHz SYSCLK set up in main - the timer's 30us ticks are too
+// coarse, and a wait for the next tick can be arbitrarily short.
+const RECOVERY_DELAY_CYCLES: u32 = 60_000_000 / 200_000;
+
+fn recovery_delay() {
+    cortex_m::asm::delay(RECOVERY_DELAY_CYCLES);
+}
+
+// The peripheral, pins and DMA channels, owned by the bus
+struct I2cParts {
+    i2c: I2cPeripheral,
+    scl: I2cSclPin,
+    sda: I2cSdaPin,
+    tx_dma: I2cTxDma,
+    rx_dma: I2cRxDma,
+}
+
+impl I2cParts {
+    // Safety: the parts are aliased by the returned I2c, so they must not be
+    // used (or passed to `new_i2c` again) until it has been dropped
+    unsafe fn new_i2c(&self) -> I2c<'static, Async> {
+        I2c::new(
+            self.i2c.clone_unchecked(),
+            self.scl.clone_unchecked(),
+            self.sda.clone_unchecked(),
+            Irqs,
+            self.tx_dma.clone_unchecked(),
+            self.rx_dma.clone_unchecked(),
+            FREQUENCY,
+            Default::default(),
+        )
+    }
+}
+
+pub struct Bus {
+    // None only while the bus is being recovered
+    i2c: Option<I2c<'static, Async>>,
+    // Kept so that nothing else can take them. Only `recover` touches them,
+    // once `i2c` has been dropped.
+    parts: I2cParts,
+}
+
+pub type I2cBus = Mutex<CriticalSectionRawMutex, Bus>;
+
+static I2C_BUS: StaticCell<I2cBus> = StaticCell::new();
+
+// Call once - use `device` to get a handle for each driver
+pub fn init(
+    i2c: I2cPeripheral,
+    scl: I2cSclPin,
+    sda: I2cSdaPin,
+    tx_dma: I2cTxDma,
+    rx_dma: I2cRxDma,
+) -> &'static I2cBus {
+    let parts = I2cParts {
+        i2c,
+        scl,
+        sda,
+        tx_dma,
+        rx_dma,
+    };
+    // Safety: `parts` are only used by this I2c until `recover` drops it
+    let i2c = unsafe { parts.new_i2c() };
+    I2C_BUS.init(Mutex::new(Bus {
+        i2c: Some(i2c),
+        parts,
+    }))
+}
+
+pub fn device(bus: &'static I2cBus) -> I2cDevice {
+    I2cDevice { bus }
+}
+
+// Free a slave holding SDA low (e.g. reset part way through a read) by
+// clocking SCL until it lets go, then send a STOP and re-create the peripheral
+fn recover(bus: &mut Bus) {
+    // Release the pins
+    bus.i2c = None;
+    {
+        let mut scl = OutputOpenDrain::new(&mut bus.parts.scl, Level::High, Speed::Low);
+        let mut sda = OutputOpenDrain::new(&mut bus.parts.sda, Level::High, Speed::Low);
+        for _ in 0..9 {
+            if sda.is_high() {
+                break;
+            }
+            scl.set_low();
+            recovery_delay();
+            scl.set_high();
+            recovery_delay();
+        }
+        // STOP: SDA rises while SCL is high
+        scl.set_low();
+        recovery_delay();
+        sda.set_low();
+        recovery_delay();
+        scl.set_high();
+        recovery_delay();
+        sda.set_high();
+        recovery_delay();
+        if sda.is_low() {
+            warn!("I2C SDA still held low");
+        }
+    }
+    // Safety: the old I2c was dropped above and the pins are free again
+    bus.i2c = Some(unsafe { bus.parts.new_i2c() });
+}
+
+#[derive(Debug)]
+pub enum I2cError {
+    I2c(i2c::Error),
+    Timeout,
+    // Only single reads and writes, and a write then a read, are supported
+    Unsupported,
+}
+
+impl embedded_hal::i2c::Error for I2cError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            I2cError::I2c(e) => e.kind(),
+            I2cError::Timeout | I2cError::Unsupported => ErrorKind::Other,
+        }
+    }
+}
+
+// Async handle to the shared bus. Transfers time out, and the bus is
+// recovered after a timeout or bus error.
+pub struct I2cDevice {
+    bus: &'static I2cBus,
+}
+
+impl ErrorType for I2cDevice {
+    type Error = I2cError;
+}
+
+impl embedded_hal_async::i2c::I2c for I2cDevice {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut bus = self.bus.lock().await;
+        let i2c = bus.i2c.as_mut().unwrap();
+        let transfer = async {
+            match operations {
+                [Operation::Write(write)] => i2c.write(address, write).await,
+                [Operation::Read(read)] => i2c.read(address, read).await,
+                [Operation::Write(write), Operation::Read(read)] => {
+                    i2c.write_read(address, write, read).await
+                }
+                _ => return Err(I2cError::Unsupported),
+            }
+            .map_err(I2cError::I2c)
+        };
+        let e = match with_timeout(TIMEOUT, transfer).await {
+            Ok(Ok(())) => return Ok(()),
+            Ok(Err(e)) => e,
+            Err(_) => I2cError::Timeout,
+        };
+        if matches!(
+            e,
+            I2cError::Timeout | I2cError::I2c(i2c::Error::Bus | i2c::Error::Arbitration)
+        ) {
+            warn!("I2C {}, recovering bus", defmt::Debug2Format(&e));
+            recover(&mut bus);
+        }
+        Err(e)
+    }
+}
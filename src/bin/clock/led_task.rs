@@ -1,20 +1,39 @@
 use defmt::*;
 use embassy_stm32::gpio::{AnyPin, Level, Output, Speed};
+use embassy_sync::pubsub::WaitResult;
 use embassy_time::Timer;
-use portable_atomic::Ordering;
+use stm32f401_embassy::ringer::{ALARM1_FIRED, ALARM2_FIRED};
 
 #[embassy_executor::task]
 pub async fn blink(led: AnyPin) {
     let mut led = Output::new(led, Level::High, Speed::Low);
     led.set_high();
 
+    let mut sub = crate::MSG_BUS.subscriber().unwrap();
+    // Alarms ringing (from the ringer state on the message bus)
+    let mut alarm: u8 = 0;
+
     loop {
-        if crate::ALARM.load(Ordering::Relaxed) {
-            info!("FLASH -> true");
-            led.set_low();
-            Timer::after_millis(100).await;
-            led.set_high();
-            Timer::after_millis(100).await;
+        while let Some(msg) = sub.try_next_message() {
+            if let WaitResult::Message(crate::Msg::AlarmState(state)) = msg {
+                alarm = state.ringing();
+            }
+        }
+        if alarm != 0 {
+            info!("FLASH -> {}", alarm);
+            // Alarm 1: single flash, Alarm 2: double flash (both: triple)
+            let flashes = match alarm {
+                ALARM1_FIRED => 1,
+                ALARM2_FIRED => 2,
+                _ => 3,
+            };
+            for _ in 0..flashes {
+                led.set_low();
+                Timer::after_millis(100).await;
+                led.set_high();
+                Timer::after_millis(100).await;
+            }
+            Timer::after_millis(400).await;
         } else {
             Timer::after_millis(100).await;
         }
@@ -2,59 +2,102 @@
 #![no_main]
 
 use chrono::NaiveDateTime;
-use chrono::{NaiveDate, NaiveTime};
+use chrono::NaiveTime;
+use core::cell::RefCell;
 use defmt::*;
 use display_task::DisplayPins;
 use embassy_executor::Spawner;
-use embassy_stm32::{bind_interrupts, exti::Channel, gpio::Pin, time::Hertz, usb};
+use embassy_stm32::{bind_interrupts, exti::Channel as _, gpio::Pin, time::Hertz, usb};
 use embassy_sync::{
-    blocking_mutex::raw::CriticalSectionRawMutex, pubsub::PubSubChannel, watch::Watch,
+    blocking_mutex::{raw::CriticalSectionRawMutex, Mutex},
+    channel::Channel,
+    pubsub::PubSubChannel,
+    signal::Signal,
+    watch::Watch,
 };
 use embedded_graphics::draw_target::DrawTarget;
-use portable_atomic::AtomicBool;
+use stm32f401_embassy::alarm::NextAlarm;
+use stm32f401_embassy::gps::GpsStatus;
+use stm32f401_embassy::msg::Msg;
+use stm32f401_embassy::ringer::RingEvent;
+use stm32f401_embassy::rtc_health::RtcHealth;
+use stm32f401_embassy::settings::{Settings, SettingsStore};
+use stm32f401_embassy::sync::SyncResult;
+use stm32f401_embassy::temp_log::TempLog;
 use {defmt_rtt as _, panic_probe as _};
 
 mod alarm_task;
 mod button_task;
 mod cli;
 mod display_task;
+#[cfg(feature = "gps")]
+mod gps_task;
+#[cfg(feature = "rtc-ds3231")]
+mod i2c_bus;
 mod led_task;
 mod line_input;
+#[cfg(any(feature = "dcf77", feature = "msf"))]
+mod radio_clock_task;
+mod ringer_task;
 mod rtc_task;
+mod settings_task;
+#[cfg(feature = "rtc-internal")]
+mod stm32_rtc;
 mod usb_task;
 
+#[cfg(not(any(feature = "rtc-ds3231", feature = "rtc-internal", feature = "rtc-soft")))]
+compile_error!("Select an RTC backend feature: rtc-ds3231, rtc-internal or rtc-soft");
+#[cfg(any(
+    all(feature = "rtc-ds3231", feature = "rtc-internal"),
+    all(feature = "rtc-ds3231", feature = "rtc-soft"),
+    all(feature = "rtc-internal", feature = "rtc-soft"),
+))]
+compile_error!("Only one RTC backend feature can be enabled (use --no-default-features)");
+#[cfg(all(feature = "settings-eeprom", not(feature = "rtc-ds3231")))]
+compile_error!("settings-eeprom needs the AT24C32 on the DS3231 module (rtc-ds3231)");
+#[cfg(all(feature = "dcf77", feature = "msf"))]
+compile_error!("Only one radio clock feature can be enabled: dcf77 or msf");
+
 bind_interrupts!(struct Irqs {
     OTG_FS => usb::InterruptHandler<usb_task::UsbOtgPeripheral>;
 });
 
-#[derive(Clone, Debug)]
-enum Msg {
-    SetTime(NaiveTime),
-    SetDate(NaiveDate),
-    // SetBacklight(f32),
-    SetAlarm1(NaiveTime),
-}
-
-impl defmt::Format for Msg {
-    fn format(&self, fmt: Formatter) {
-        match self {
-            Msg::SetTime(_) => defmt::write!(fmt, "<SetTime>"),
-            Msg::SetDate(_) => defmt::write!(fmt, "<SetDate>"),
-            // Msg::SetBacklight(_) => defmt::write!(fmt, "<SetBacklight>"),
-            Msg::SetAlarm1(_) => defmt::write!(fmt, "<SetAlarm1>"),
-        }
-    }
-}
-
 // Global values
+// Local time (the RTC itself runs in UTC - see rtc_task)
 static RTC_TIME: Watch<CriticalSectionRawMutex, NaiveDateTime, 4> = Watch::new();
 static RTC_TEMP: Watch<CriticalSectionRawMutex, f32, 4> = Watch::new();
-static MSG_BUS: PubSubChannel<CriticalSectionRawMutex, Msg, 4, 4, 4> = PubSubChannel::new();
-static ALARM: AtomicBool = AtomicBool::new(false);
-static ALARM1_TIME: Watch<CriticalSectionRawMutex, Option<NaiveTime>, 4> = Watch::new();
+// Publishers held at once: ringer_task (permanently), the CLI, and set_clock
+// from gps_task and radio_clock_task - plus spares
+static MSG_BUS: PubSubChannel<CriticalSectionRawMutex, Msg, 4, 6, 6> = PubSubChannel::new();
+// Alarm fired / button pressed - handled by ringer_task
+static RING_EVENTS: Channel<CriticalSectionRawMutex, RingEvent, 4> = Channel::new();
+// RTC INT/SQW falling edge - wake rtc_task on the 1Hz square wave tick (or to
+// check which alarm fired if the backend has no square wave)
+static ALARM_INT: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+// Wake rtc_task to handle a message now (time set on a GPS PPS edge, or a
+// temperature reading for the CLI)
+static RTC_WAKE: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+// User settings including the alarm table (the next alarm due is programmed
+// into Alarm 1 by rtc_task). Saved by settings_task.
+static SETTINGS: Mutex<CriticalSectionRawMutex, RefCell<Settings>> =
+    Mutex::new(RefCell::new(Settings::new()));
+static NEXT_ALARM: Watch<CriticalSectionRawMutex, Option<NextAlarm>, 4> = Watch::new();
 static ALARM1_MATCH: Watch<CriticalSectionRawMutex, bool, 4> = Watch::new();
-// static ALARM2_TIME: Watch<CriticalSectionRawMutex, Option<NaiveTime>, 4> = Watch::new();
-// static ALARM2_MATCH: Watch<CriticalSectionRawMutex, bool, 4> = Watch::new();
+static ALARM2_TIME: Watch<CriticalSectionRawMutex, Option<NaiveTime>, 4> = Watch::new();
+static ALARM2_MATCH: Watch<CriticalSectionRawMutex, bool, 4> = Watch::new();
+// RTC aging offset (None if not supported by the backend)
+static AGING: Watch<CriticalSectionRawMutex, Option<i8>, 4> = Watch::new();
+// Reply to Msg::Sync (from rtc_task to the CLI)
+static SYNC_RESULT: Signal<CriticalSectionRawMutex, SyncResult> = Signal::new();
+// Reply to Msg::ConvertTemp (None without a temperature sensor)
+static TEMP_NOW: Signal<CriticalSectionRawMutex, Option<f32>> = Signal::new();
+// Sampled by rtc_task (every Settings::temp_interval minutes)
+static TEMP_LOG: Mutex<CriticalSectionRawMutex, RefCell<TempLog>> =
+    Mutex::new(RefCell::new(TempLog::new()));
+// Sent by rtc_task every tick
+static RTC_HEALTH: Watch<CriticalSectionRawMutex, RtcHealth, 4> = Watch::new();
+// Sent by gps_task (never set without the gps feature)
+static GPS_STATUS: Watch<CriticalSectionRawMutex, GpsStatus, 4> = Watch::new();
 
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
@@ -79,6 +122,10 @@ async fn main(spawner: Spawner) {
         config.rcc.apb1_pre = APBPrescaler::DIV2; // APB1 = SYSCLK/2  (30MHz)
         config.rcc.apb2_pre = APBPrescaler::DIV2; // APB2 = SYSCLK/2  (30MHz)
         config.rcc.mux.clk48sel = mux::Clk48sel::PLL1_Q;
+        #[cfg(feature = "rtc-internal")]
+        {
+            config.rcc.ls = LsConfig::default_lse();
+        }
     }
 
     let p = embassy_stm32::init(config);
@@ -93,10 +140,48 @@ async fn main(spawner: Spawner) {
         backlight: p.PB12.degrade(),
     };
 
+    #[cfg(feature = "rtc-ds3231")]
+    let i2c_bus = i2c_bus::init(p.I2C1, p.PB8, p.PB9, p.DMA1_CH6, p.DMA1_CH0);
+
+    // Load settings before anything uses them
+    #[cfg(not(feature = "settings-eeprom"))]
+    let mut settings_store =
+        settings_task::backend(embassy_stm32::flash::Flash::new_blocking(p.FLASH));
+    #[cfg(feature = "settings-eeprom")]
+    let mut settings_store = settings_task::backend(i2c_bus);
+    match settings_store.load().await {
+        Ok(Some(settings)) => {
+            info!("Settings loaded");
+            SETTINGS.lock(|s| s.replace(settings));
+        }
+        Ok(None) => info!("No saved settings - using defaults"),
+        Err(e) => error!("Error loading settings: {:?}", Debug2Format(&e)),
+    }
+
     // Spawn tasks
-    spawner.must_spawn(rtc_task::rtc(p.I2C1, p.PB8, p.PB9));
+    spawner.must_spawn(settings_task::settings(settings_store));
+    #[cfg(feature = "rtc-ds3231")]
+    spawner.must_spawn(rtc_task::rtc(i2c_bus::device(i2c_bus)));
+    #[cfg(feature = "rtc-internal")]
+    spawner.must_spawn(rtc_task::rtc(p.RTC));
+    #[cfg(feature = "rtc-soft")]
+    spawner.must_spawn(rtc_task::rtc());
+    spawner.must_spawn(ringer_task::ringer());
     spawner.must_spawn(button_task::button(p.PA0.degrade(), p.EXTI0.degrade()));
     spawner.must_spawn(alarm_task::alarm(p.PA1.degrade(), p.EXTI1.degrade()));
+    #[cfg(feature = "gps")]
+    {
+        #[cfg(feature = "gps-pps")]
+        let pps = Some((p.PA8.degrade(), p.EXTI8.degrade()));
+        #[cfg(not(feature = "gps-pps"))]
+        let pps = None;
+        spawner.must_spawn(gps_task::gps(p.USART2, p.PA3, pps));
+    }
+    #[cfg(any(feature = "dcf77", feature = "msf"))]
+    spawner.must_spawn(radio_clock_task::radio_clock(
+        p.PB10.degrade(),
+        p.EXTI10.degrade(),
+    ));
     spawner.must_spawn(led_task::blink(p.PC13.degrade()));
     spawner.must_spawn(display_task::display(display_pins, p.SPI2, p.DMA1_CH4));
     spawner.must_spawn(usb_task::usb_device(spawner, p.USB_OTG_FS, p.PA12, p.PA11));
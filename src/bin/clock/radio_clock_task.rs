@@ -0,0 +1,55 @@
+use chrono::TimeDelta;
+use defmt::info;
+use embassy_stm32::{
+    exti::{AnyChannel, ExtiInput},
+    gpio::{AnyPin, Pull},
+};
+use embassy_time::{Duration, Instant, Timer};
+use stm32f401_embassy::gps::local_time;
+use stm32f401_embassy::radio_clock::{Decoder, Protocol};
+
+#[cfg(feature = "dcf77")]
+const PROTOCOL: Protocol = Protocol::Dcf77;
+#[cfg(feature = "msf")]
+const PROTOCOL: Protocol = Protocol::Msf;
+
+// Receiver output level while the carrier is off (most modules pull the
+// output low for each pulse)
+const CARRIER_OFF_HIGH: bool = false;
+// Frames arrive every minute - only re-align the RTC this often (unless it is
+// out by more than a second)
+const RESYNC_INTERVAL: Duration = Duration::from_secs(3600);
+
+#[embassy_executor::task]
+pub async fn radio_clock(pin: AnyPin, exti: AnyChannel) {
+    let mut input = ExtiInput::new(pin, exti, Pull::None);
+    let mut decoder = Decoder::new(PROTOCOL);
+    let mut rtc_time_rx = crate::RTC_TIME.receiver().unwrap();
+    let mut last_sync: Option<Instant> = None;
+
+    info!("Starting radio clock ({})", defmt::Debug2Format(&PROTOCOL));
+    loop {
+        input.wait_for_any_edge().await;
+        let at_ms = Instant::now().as_millis();
+        let Some(frame) = decoder.edge(input.is_high() == CARRIER_OFF_HIGH, at_ms) else {
+            continue;
+        };
+        let Some(rtc) = rtc_time_rx.try_get() else {
+            continue;
+        };
+        // Set on the next second (the frame is decoded during second 0)
+        let utc = frame.utc + TimeDelta::seconds(1);
+        let tz = crate::SETTINGS.lock(|s| s.borrow().tz.clone());
+        let Some(local) = local_time(&tz, utc) else {
+            continue;
+        };
+        // RTC_TIME is up to a second behind
+        let wrong = (rtc - tz.to_local(frame.utc)).num_seconds().abs() > 1;
+        if wrong || last_sync.map_or(true, |t| t.elapsed() >= RESYNC_INTERVAL) {
+            Timer::at(Instant::from_millis(frame.at_ms + 1000)).await;
+            crate::rtc_task::set_clock(rtc, local).await;
+            info!("RTC set from radio clock");
+            last_sync = Some(Instant::now());
+        }
+    }
+}
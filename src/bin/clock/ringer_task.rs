@@ -0,0 +1,51 @@
+use defmt::*;
+use embassy_futures::select::{select, Either};
+use embassy_sync::pubsub::WaitResult;
+use embassy_time::{Instant, Timer};
+use stm32f401_embassy::ringer::{RingConfig, Ringer};
+
+fn ring_config() -> RingConfig {
+    crate::SETTINGS.lock(|s| {
+        let s = s.borrow();
+        RingConfig::from_minutes(s.snooze, s.ring_time)
+    })
+}
+
+// Alarm state machine - events from rtc_task (fire/cancel) and button_task
+// (snooze/dismiss). State changes are published on the message bus.
+#[embassy_executor::task]
+pub async fn ringer() {
+    let mut ringer = Ringer::new(ring_config());
+    let msg_pub = crate::MSG_BUS.publisher().unwrap();
+    let mut sub = crate::MSG_BUS.subscriber().unwrap();
+    loop {
+        let prev = ringer.state();
+        match select(crate::RING_EVENTS.receive(), Timer::after_millis(100)).await {
+            Either::First(event) => {
+                ringer.handle(event, Instant::now().as_millis());
+            }
+            Either::Second(_) => {
+                ringer.tick(Instant::now().as_millis());
+            }
+        }
+        while let Some(msg) = sub.try_next_message() {
+            match msg {
+                WaitResult::Message(crate::Msg::SetSnooze(m)) => {
+                    ringer.config.snooze_ms = m as u64 * 60_000;
+                }
+                WaitResult::Message(crate::Msg::SetRingTime(m)) => {
+                    ringer.config.max_ring_ms = m as u64 * 60_000;
+                }
+                WaitResult::Message(crate::Msg::SettingsLoaded) => {
+                    ringer.config = ring_config();
+                }
+                _ => {}
+            }
+        }
+        let state = ringer.state();
+        if state != prev {
+            info!("Alarm state: {}", state);
+            msg_pub.publish(crate::Msg::AlarmState(state)).await;
+        }
+    }
+}
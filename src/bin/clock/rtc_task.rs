@@ -1,134 +1,484 @@
-use core::fmt::Write;
-use defmt::{error, info};
-use ds323x::ic::DS3231;
-use ds323x::interface::I2cInterface;
-use ds323x::{DateTimeAccess, Ds323x, Error, NaiveDateTime, Timelike};
-use embassy_stm32::i2c::I2c;
-use embassy_stm32::mode::Blocking;
-use embassy_stm32::time::Hertz;
+use chrono::{NaiveDateTime, NaiveTime, TimeDelta, Timelike};
+use core::fmt::{Debug, Write};
+use defmt::{error, info, warn};
+use embassy_futures::select::{select3, Either3};
 use embassy_sync::pubsub::WaitResult;
-use embassy_time::Timer;
+use embassy_time::{Instant, Timer};
 use heapless::String;
+use stm32f401_embassy::alarm::{alarm_name, NextAlarm, Schedule};
+use stm32f401_embassy::drift::DriftLog;
+use stm32f401_embassy::ringer::{RingEvent, ALARM1_FIRED, ALARM2_FIRED};
+use stm32f401_embassy::rtc::RtcBackend;
+use stm32f401_embassy::rtc_health::RtcHealth;
+use stm32f401_embassy::sync::{SyncRequest, SyncResult};
+use stm32f401_embassy::tz::TimeZone;
 
-pub type I2cDevice = embassy_stm32::peripherals::I2C1;
-pub type I2cSclPin = embassy_stm32::peripherals::PB8;
-pub type I2cSdaPin = embassy_stm32::peripherals::PB9;
+#[cfg(feature = "rtc-ds3231")]
+use stm32f401_embassy::ds3231::Ds3231;
 
-const _DS3231_ADDRESS: u8 = 0x68;
-const _DS3231_CONTROL: u8 = 0x0E;
-const _DS3231_STATUS: u8 = 0x0F;
+// DS3231 on the shared I2C1 bus (see i2c_bus)
+#[cfg(feature = "rtc-ds3231")]
+#[embassy_executor::task]
+pub async fn rtc(i2c: crate::i2c_bus::I2cDevice) {
+    run(Ds3231::new(i2c)).await
+}
 
-type RtcInstance<'a> = Ds323x<I2cInterface<I2c<'a, Blocking>>, DS3231>;
+#[cfg(feature = "rtc-internal")]
+#[embassy_executor::task]
+pub async fn rtc(rtc: crate::stm32_rtc::RtcPeripheral) {
+    run(crate::stm32_rtc::Stm32Rtc::new(rtc)).await
+}
 
+#[cfg(feature = "rtc-soft")]
 #[embassy_executor::task]
-pub async fn rtc(i2cdev: I2cDevice, scl: I2cSclPin, sda: I2cSdaPin) {
-    let mut i2c = I2c::new_blocking(i2cdev, scl, sda, Hertz(400_000), Default::default());
-    let rtc_time_tx = crate::RTC_TIME.sender();
-    let rtc_temp_tx = crate::RTC_TEMP.sender();
-    let alarm1_time_tx = crate::ALARM1_TIME.sender();
-    let alarm1_match_tx = crate::ALARM1_MATCH.sender();
-    let mut sub = crate::MSG_BUS.subscriber().unwrap();
+pub async fn rtc() {
+    run(stm32f401_embassy::rtc::SoftRtc::new(|| {
+        embassy_time::Instant::now().as_millis()
+    }))
+    .await
+}
 
-    // Use direct i2c to get alarm register
-    let mut buf: [u8; 4] = [0; 4];
+// Set the RTC from a time source task (GPS, radio clock) - `rtc` is the current
+// RTC_TIME and `local` the time now
+pub async fn set_clock(rtc: NaiveDateTime, local: NaiveDateTime) {
+    let msg_pub = crate::MSG_BUS.publisher().unwrap();
+    if rtc.date() != local.date() {
+        msg_pub.publish(crate::Msg::SetDate(local.date())).await;
+    }
+    msg_pub.publish(crate::Msg::SetTime(local.time())).await;
+    // Don't wait for the next tick
+    crate::RTC_WAKE.signal(());
+}
 
-    match i2c.blocking_write_read(0x68, &[0x07], &mut buf) {
-        Ok(_) => info!("I2C Read: {:?}", buf),
-        Err(e) => error!("i2c error: {:?}", e),
+fn log_error<E: Debug>(msg: &str, e: E) {
+    let mut s: String<32> = String::new();
+    write!(s, "{:?}", e).ok();
+    error!("{}: {}", msg, s.as_str());
+}
+
+// Program the next alarm due from the alarm table (local time) into Alarm 1
+async fn arm_alarm1<B: RtcBackend>(
+    rtc: &mut B,
+    tz: &TimeZone,
+    now: NaiveDateTime,
+) -> Result<Option<NextAlarm>, B::Error> {
+    let next = crate::SETTINGS.lock(|s| s.borrow().alarms.next(tz.to_local(now)));
+    match &next {
+        Some(next) => rtc.set_alarm1(next.alarm_match(tz)).await?,
+        None => rtc.clear_alarm1().await?,
     }
+    Ok(next)
+}
 
-    let mut rtc = Ds323x::new_ds3231(i2c);
+// Returns the alarm armed
+async fn rearm<B: RtcBackend>(rtc: &mut B, tz: &TimeZone) -> Option<NextAlarm> {
+    let next = match rtc.datetime().await {
+        Ok(now) => arm_alarm1(rtc, tz, now).await,
+        Err(e) => Err(e),
+    };
+    match next {
+        Ok(next) => {
+            crate::NEXT_ALARM.sender().send(next.clone());
+            next
+        }
+        Err(ref e) => {
+            log_error("Error setting alarm", e);
+            None
+        }
+    }
+}
 
-    // Configure RTC
-    let rtc_setup = [
-        |rtc: &mut RtcInstance| rtc.enable(),
-        |rtc: &mut RtcInstance| rtc.clear_alarm1_matched_flag(),
-        |rtc: &mut RtcInstance| rtc.clear_alarm2_matched_flag(),
-        |rtc: &mut RtcInstance| rtc.enable_alarm1_interrupts(),
-        |rtc: &mut RtcInstance| rtc.enable_alarm2_interrupts(),
-        |rtc: &mut RtcInstance| rtc.use_int_sqw_output_as_interrupt(),
-    ];
-
-    // Use wrapper to call setup functions
-    for f in rtc_setup {
-        while let Err(e) = f(&mut rtc) {
-            match e {
-                Error::Comm(e) => {
-                    error!("RTC Comm Error (retrying): {:?}", e);
-                    Timer::after_micros(100).await;
-                }
-                _ => panic!("Error configuring RTC: {:?}", e),
+// Alarm 2 is a daily match in UTC - keep it at the same local time when the
+// UTC offset changes (DST or a new time zone). Returns the local alarm time.
+async fn shift_alarm2<B: RtcBackend>(
+    rtc: &mut B,
+    from: i32,
+    to: i32,
+) -> Result<Option<NaiveTime>, B::Error> {
+    let local = rtc
+        .alarm2()
+        .await?
+        .map(|t| t + TimeDelta::seconds(from as i64));
+    if let (Some(t), true) = (local, from != to) {
+        rtc.set_alarm2(t - TimeDelta::seconds(to as i64)).await?;
+    }
+    Ok(local)
+}
+
+// Force a temperature conversion and read the result, rather than the
+// reading from the last automatic conversion (up to 64s old)
+async fn fresh_temperature<B: RtcBackend>(rtc: &mut B) -> Result<Option<f32>, B::Error> {
+    if rtc.start_temperature_conversion().await? {
+        // Typically 125ms, at most 200ms
+        let mut polls = 0;
+        while rtc.temperature_converting().await? {
+            if polls == 20 {
+                warn!("Temperature conversion timed out");
+                break;
             }
+            Timer::after_millis(20).await;
+            polls += 1;
         }
     }
+    rtc.temperature().await
+}
+
+// Update the drift log and save it (settings_task) if `f` changed it
+fn update_drift(f: impl FnOnce(&mut DriftLog) -> bool) {
+    if crate::SETTINGS.lock(|s| f(&mut s.borrow_mut().drift)) {
+        crate::MSG_BUS
+            .immediate_publisher()
+            .publish_immediate(crate::Msg::SaveSettings);
+    }
+}
+
+// Record a sync and re-calibrate the aging offset once enough syncs have
+// been recorded
+async fn synced<B: RtcBackend>(rtc: &mut B, error_ms: Option<i64>) -> Result<(), B::Error> {
+    let now = rtc.datetime().await?;
+    let aging = rtc.aging().await?;
+    let mut calibrated = None;
+    update_drift(|drift| {
+        drift.synced(now, error_ms, aging.unwrap_or(0));
+        calibrated = aging.and_then(|aging| drift.calibrate(aging));
+        if let Some(aging) = calibrated {
+            drift.aging_changed(aging);
+        }
+        true
+    });
+    if let Some(aging) = calibrated {
+        info!("Aging offset calibrated: {}", aging);
+        rtc.set_aging(aging).await?;
+        crate::AGING.sender().send(Some(aging));
+    }
+    Ok(())
+}
+
+// Measure the RTC error at the next seconds edge, then set the RTC (UTC) on
+// the next whole second of host time
+async fn sync<B: RtcBackend>(rtc: &mut B, req: &SyncRequest) -> Result<SyncResult, B::Error> {
+    let start = rtc.datetime().await?;
+    let mut error = None;
+    for _ in 0..1100 {
+        Timer::after_millis(1).await;
+        let now = rtc.datetime().await?;
+        if now != start {
+            error = Some(req.rtc_error_ms(now, Instant::now().as_millis()));
+            break;
+        }
+    }
+    let Some((at, when)) = req.next_second(Instant::now().as_millis()) else {
+        return Ok(SyncResult::Failed);
+    };
+    Timer::at(Instant::from_millis(when)).await;
+    rtc.set_datetime(&at).await?;
+    rtc.clear_oscillator_stopped().await?;
+    Ok(SyncResult::Synced(error))
+}
+
+async fn run<B: RtcBackend>(mut rtc: B) {
+    let rtc_time_tx = crate::RTC_TIME.sender();
+    let rtc_temp_tx = crate::RTC_TEMP.sender();
+    let alarm1_match_tx = crate::ALARM1_MATCH.sender();
+    let alarm2_time_tx = crate::ALARM2_TIME.sender();
+    let alarm2_match_tx = crate::ALARM2_MATCH.sender();
+    let health_tx = crate::RTC_HEALTH.sender();
+    let mut sub = crate::MSG_BUS.subscriber().unwrap();
+    let mut tz = crate::SETTINGS.lock(|s| s.borrow().tz.clone());
+    let mut temp_interval = crate::SETTINGS.lock(|s| s.borrow().temp_interval);
+    let mut health = RtcHealth::new();
+
+    // Configure RTC
+    while let Err(e) = rtc.init().await {
+        log_error("RTC Init Error (retrying)", e);
+        health.write_error();
+        health_tx.send(health.clone());
+        Timer::after_millis(100).await;
+    }
 
-    info!(
-        "RTC: stopped={} running={} temperature={}",
-        rtc.has_been_stopped().ok(),
-        rtc.running().ok(),
-        rtc.temperature().ok(),
-    );
+    let stopped = rtc.oscillator_stopped().await.ok();
+    let temp = rtc.temperature().await.ok().flatten();
+    info!("RTC: stopped={} temperature={}", stopped, temp);
+    match stopped {
+        Some(stopped) => health.powered_up(stopped),
+        None => health.read_error(),
+    }
+    if health.battery_suspect {
+        warn!("RTC time lost at power up - check the backup battery");
+    }
 
-    // Set initial temp
-    if let Ok(temp) = rtc.temperature() {
+    // Set initial temp/alarm
+    if let Some(temp) = temp {
         rtc_temp_tx.send(temp);
     }
+    // Alarm 1 holds the next alarm due from the table (re-armed after each fire).
+    // Adopt a schedule left in the hardware alarm if the table is empty.
+    if let Some(schedule) = rtc
+        .alarm1()
+        .await
+        .ok()
+        .flatten()
+        .and_then(Schedule::from_alarm_match)
+    {
+        crate::SETTINGS.lock(|s| {
+            let alarms = &mut s.borrow_mut().alarms;
+            if alarms.is_empty() {
+                alarms.add(alarm_name("alarm").unwrap(), schedule).ok();
+            }
+        });
+    }
+    let mut armed = rearm(&mut rtc, &tz).await;
+    crate::AGING.sender().send(rtc.aging().await.ok().flatten());
+    // Tick on the 1Hz square wave (INT pin) where supported - alarm flags are
+    // polled on each tick. The DS3231 has a single INT/SQW pin, so this gives
+    // up the alarm interrupt on purpose: ticks land on the seconds rollover,
+    // letting the time be counted on from the last read, and alarms match on
+    // whole seconds so a poll per tick fires them just as promptly.
+    let sqw = match rtc.enable_square_wave().await {
+        Ok(sqw) => sqw,
+        Err(ref e) => {
+            log_error("Error enabling square wave", e);
+            false
+        }
+    };
+    info!("RTC ticks: {}", if sqw { "1Hz SQW" } else { "timer" });
+    crate::ALARM_INT.reset();
+    // UTC offset alarm 2 was programmed with
+    let mut offset = rtc
+        .datetime()
+        .await
+        .map_or(tz.offset, |now| tz.offset_at(now));
+    if let Ok(t) = shift_alarm2(&mut rtc, offset, offset).await {
+        alarm2_time_tx.send(t);
+    }
+    // RTC time (UTC) - counted on SQW ticks, read again every minute or when
+    // the time is set
+    let mut time: Option<NaiveDateTime> = None;
     loop {
         // Check message bus
         while let Some(msg) = sub.try_next_message() {
             match msg {
                 WaitResult::Lagged(_) => {}
+                // Time and date are set in local time
                 WaitResult::Message(crate::Msg::SetTime(t)) => {
-                    match rtc.datetime().and_then(|now| {
-                        let d = now.date();
-                        let dt = NaiveDateTime::new(d, t);
-                        rtc.set_datetime(&dt)
-                            .and_then(|_| rtc.clear_has_been_stopped_flag())
-                    }) {
-                        Ok(_) => {}
-                        Err(_) => error!("Error setting clock"),
+                    time = None;
+                    let set = async {
+                        let now = rtc.datetime().await?;
+                        let dt = tz.to_local(now).date().and_time(t);
+                        rtc.set_datetime(&tz.to_utc(dt)).await?;
+                        rtc.clear_oscillator_stopped().await
+                    };
+                    match set.await {
+                        Ok(_) => {
+                            health.time_set();
+                            // Setting the RTC (by hand, or hourly from GPS or
+                            // a radio clock) discards the error built up since
+                            // the last host sync, so that interval can't be
+                            // measured. Only the first set after a sync saves.
+                            update_drift(|drift| drift.restart());
+                            armed = rearm(&mut rtc, &tz).await;
+                        }
+                        Err(_) => {
+                            error!("Error setting clock");
+                            health.write_error();
+                        }
                     }
                 }
                 WaitResult::Message(crate::Msg::SetDate(d)) => {
-                    match rtc.datetime().and_then(|now| {
-                        let t = now.time();
-                        let dt = NaiveDateTime::new(d, t);
-                        rtc.set_datetime(&dt)
-                    }) {
-                        Ok(_) => {}
-                        Err(_) => error!("Error setting clock"),
+                    time = None;
+                    let set = async {
+                        let now = rtc.datetime().await?;
+                        let dt = d.and_time(tz.to_local(now).time());
+                        rtc.set_datetime(&tz.to_utc(dt)).await
+                    };
+                    match set.await {
+                        Ok(_) => {
+                            update_drift(|drift| drift.restart());
+                            armed = rearm(&mut rtc, &tz).await;
+                        }
+                        Err(_) => {
+                            error!("Error setting clock");
+                            health.write_error();
+                        }
+                    }
+                }
+                WaitResult::Message(crate::Msg::Sync(req)) => {
+                    time = None;
+                    let result = match sync(&mut rtc, &req).await {
+                        Ok(result) => result,
+                        Err(ref e) => {
+                            log_error("Error syncing clock", e);
+                            health.write_error();
+                            SyncResult::Failed
+                        }
+                    };
+                    if let SyncResult::Synced(error) = result {
+                        info!("Clock synced (error {}ms)", error);
+                        health.time_set();
+                        if let Err(ref e) = synced(&mut rtc, error).await {
+                            log_error("Error calibrating aging offset", e);
+                        }
+                        armed = rearm(&mut rtc, &tz).await;
+                    }
+                    crate::SYNC_RESULT.signal(result);
+                }
+                WaitResult::Message(crate::Msg::SetAging(aging)) => {
+                    match rtc.set_aging(aging).await {
+                        Ok(_) => {
+                            update_drift(|drift| drift.restart());
+                            crate::AGING.sender().send(rtc.aging().await.ok().flatten());
+                        }
+                        Err(ref e) => log_error("Error setting aging offset", e),
                     }
                 }
-                WaitResult::Message(crate::Msg::SetAlarm1(t)) => match rtc
-                    .clear_alarm1_matched_flag()
-                    .and_then(|_| rtc.set_alarm1_hms(t))
-                {
+                WaitResult::Message(crate::Msg::AlarmsChanged) => {
+                    armed = rearm(&mut rtc, &tz).await;
+                    alarm1_match_tx.send(false);
+                }
+                WaitResult::Message(crate::Msg::SetTimeZone | crate::Msg::SettingsLoaded) => {
+                    (tz, temp_interval) = crate::SETTINGS.lock(|s| {
+                        let s = s.borrow();
+                        (s.tz.clone(), s.temp_interval)
+                    });
+                    // Alarm 2 is re-programmed when the tick below sees the new offset
+                    armed = rearm(&mut rtc, &tz).await;
+                    alarm1_match_tx.send(false);
+                }
+                WaitResult::Message(crate::Msg::SetTempInterval(m)) => temp_interval = m,
+                WaitResult::Message(crate::Msg::ConvertTemp) => {
+                    let temp = match fresh_temperature(&mut rtc).await {
+                        Ok(temp) => temp,
+                        Err(ref e) => {
+                            log_error("Error reading temperature", e);
+                            health.read_error();
+                            None
+                        }
+                    };
+                    if let Some(temp) = temp {
+                        rtc_temp_tx.send(temp);
+                    }
+                    crate::TEMP_NOW.signal(temp);
+                }
+                WaitResult::Message(crate::Msg::SetAlarm2(t)) => {
+                    match rtc.set_alarm2(t - TimeDelta::seconds(offset as i64)).await {
+                        Ok(_) => {
+                            let t = shift_alarm2(&mut rtc, offset, offset).await;
+                            alarm2_time_tx.send(t.ok().flatten());
+                            alarm2_match_tx.send(false);
+                        }
+                        Err(_) => error!("Error setting alarm2"),
+                    }
+                }
+                WaitResult::Message(crate::Msg::ClearAlarm2) => match rtc.clear_alarm2().await {
                     Ok(_) => {
-                        alarm1_time_tx.send(Some(t));
-                        alarm1_match_tx.send(false);
+                        alarm2_time_tx.send(None);
+                        alarm2_match_tx.send(false);
+                        crate::RING_EVENTS
+                            .try_send(RingEvent::Cancel(ALARM2_FIRED))
+                            .ok();
                     }
-                    Err(_) => error!("Error setting alarm"),
+                    Err(_) => error!("Error clearing alarm2"),
                 },
-                // WaitResult::Message(_) => {} // Ignore other messages
+                WaitResult::Message(_) => {} // Ignore other messages
             }
         }
         // Update global time
-        match rtc.datetime() {
+        let now = match time {
+            Some(t) => Ok(t),
+            None => rtc.datetime().await,
+        };
+        // Ticks counted from the last read don't count as reads
+        if time.is_none() {
+            match &now {
+                Ok(t) => health.read_ok(*t),
+                Err(_) => health.read_error(),
+            }
+        }
+        match now {
             Ok(time) => {
-                rtc_time_tx.send(time);
-                // Update temperature every minute
+                rtc_time_tx.send(tz.to_local(time));
+                let new_offset = tz.offset_at(time);
+                if new_offset != offset {
+                    info!("UTC offset now {}s ({})", new_offset, tz.name_at(time));
+                    match shift_alarm2(&mut rtc, offset, new_offset).await {
+                        Ok(t) => {
+                            offset = new_offset;
+                            alarm2_time_tx.send(t);
+                        }
+                        Err(ref e) => log_error("Error setting alarm2", e),
+                    }
+                }
+                // Check the oscillator every minute, and sample the temperature
+                // every temp_interval minutes (from local midnight)
                 if time.second() == 0 {
-                    if let Ok(temp) = rtc.temperature() {
-                        rtc_temp_tx.send(temp);
+                    match rtc.oscillator_stopped().await {
+                        Ok(stopped) => {
+                            if health.oscillator(stopped) {
+                                warn!("RTC oscillator stopped - time invalid");
+                            }
+                        }
+                        Err(_) => health.read_error(),
+                    }
+                    let local = tz.to_local(time);
+                    let minute = local.hour() * 60 + local.minute();
+                    if minute % temp_interval.max(1) as u32 == 0 {
+                        if let Ok(Some(temp)) = fresh_temperature(&mut rtc).await {
+                            rtc_temp_tx.send(temp);
+                            crate::TEMP_LOG.lock(|log| log.borrow_mut().add(time, temp));
+                        }
                     }
                 }
             }
-            Err(e) => {
-                let mut s: String<32> = String::new();
-                write!(s, "{:?}", e).ok();
-                error!("rtc.gettime: {}", s.as_str());
+            Err(ref e) => log_error("rtc.gettime", e),
+        }
+        // Poll alarm flags (needed with SQW ticks or without an alarm interrupt)
+        let (alarm1, alarm2) = rtc.alarms_matched().await.unwrap_or((false, false));
+        if let (true, Ok(now)) = (alarm1, &now) {
+            rtc.clear_alarm1_matched().await.ok();
+            // Date matches repeat monthly - only fire for the occurrence armed.
+            // (Checking the table instead would miss alarms in a skipped DST hour.)
+            let due = armed.as_ref().filter(|next| {
+                let at = tz.to_utc(next.at);
+                at <= *now && *now - at < TimeDelta::minutes(1)
+            });
+            if let Some(next) = due {
+                info!("Alarm matched: {}", next.name.as_str());
+                crate::RING_EVENTS
+                    .try_send(RingEvent::Fire(ALARM1_FIRED))
+                    .ok();
+                alarm1_match_tx.send(true);
+            }
+            let retired = crate::SETTINGS.lock(|s| s.borrow_mut().alarms.retire(tz.to_local(*now)));
+            if retired {
+                // Saved like any other alarm table change
+                crate::MSG_BUS
+                    .immediate_publisher()
+                    .publish_immediate(crate::Msg::SaveSettings);
             }
+            armed = rearm(&mut rtc, &tz).await;
         }
-        Timer::after_millis(1000).await;
+        if alarm2 {
+            info!("Alarm2 matched");
+            crate::RING_EVENTS
+                .try_send(RingEvent::Fire(ALARM2_FIRED))
+                .ok();
+            alarm2_match_tx.send(true);
+            rtc.clear_alarm2_matched().await.ok();
+        }
+        health_tx.send(health.clone());
+        // Wait for next tick (SQW edge or timer), RTC INT or wake
+        let timeout = if sqw { 1100 } else { 1000 };
+        let tick = select3(
+            Timer::after_millis(timeout),
+            crate::ALARM_INT.wait(),
+            crate::RTC_WAKE.wait(),
+        )
+        .await;
+        time = match (tick, now) {
+            (Either3::Second(_), Ok(t)) if sqw && t.second() != 59 => {
+                Some(t + TimeDelta::seconds(1))
+            }
+            _ => None,
+        };
     }
 }
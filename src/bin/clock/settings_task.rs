@@ -0,0 +1,96 @@
+use defmt::*;
+use embassy_sync::pubsub::WaitResult;
+use stm32f401_embassy::settings::{Settings, SettingsStore};
+
+#[cfg(feature = "settings-eeprom")]
+use {
+    crate::i2c_bus::{self, I2cBus, I2cDevice},
+    embassy_time::Delay,
+    stm32f401_embassy::at24c32::At24c32,
+    stm32f401_embassy::settings::EepromStore,
+};
+#[cfg(not(feature = "settings-eeprom"))]
+use {
+    embassy_stm32::flash::{Blocking, Flash},
+    stm32f401_embassy::settings::FlashJournal,
+};
+
+// Flash sectors 1 and 2 (16K each) as (offset, size) - the code starts after
+// them (_stext in memory.x)
+#[cfg(not(feature = "settings-eeprom"))]
+pub const SETTINGS_REGIONS: [(u32, u32); 2] = [(0x4000, 0x4000), (0x8000, 0x4000)];
+
+// Start of the AT24C32 on the RTC module
+#[cfg(feature = "settings-eeprom")]
+pub const SETTINGS_OFFSET: u32 = 0;
+
+#[cfg(not(feature = "settings-eeprom"))]
+pub type SettingsBackend = FlashJournal<Flash<'static, Blocking>>;
+
+#[cfg(not(feature = "settings-eeprom"))]
+pub fn backend(flash: Flash<'static, Blocking>) -> SettingsBackend {
+    FlashJournal::new(flash, SETTINGS_REGIONS)
+}
+
+// The EEPROM shares the RTC's I2C bus through its own device handle, so each
+// transfer (and each ACK poll during a page write) takes the bus lock in turn
+// and gets the same timeout and bus recovery as the RTC
+#[cfg(feature = "settings-eeprom")]
+pub type SettingsBackend = EepromStore<I2cDevice, Delay>;
+
+#[cfg(feature = "settings-eeprom")]
+pub fn backend(bus: &'static I2cBus) -> SettingsBackend {
+    EepromStore::new(At24c32::new(i2c_bus::device(bus), Delay), SETTINGS_OFFSET)
+}
+
+// Save the settings whenever a set-command changes them. The CLI updates
+// SETTINGS before publishing the message.
+//
+// Note that saving to flash blocks the executor while a 16K sector is erased
+// (up to 0.5s, only when a journal region fills up - every 32 saves).
+#[embassy_executor::task]
+pub async fn settings(mut store: SettingsBackend) {
+    // Waiting on a full queue here could deadlock - this task is one of the
+    // subscribers it would be waiting for
+    let msg_pub = crate::MSG_BUS.immediate_publisher();
+    let mut sub = crate::MSG_BUS.subscriber().unwrap();
+    loop {
+        let msg = match sub.next_message().await {
+            WaitResult::Lagged(_) => continue,
+            WaitResult::Message(msg) => msg,
+        };
+        match msg {
+            crate::Msg::AlarmsChanged
+            | crate::Msg::SetSnooze(_)
+            | crate::Msg::SetRingTime(_)
+            | crate::Msg::SetTempInterval(_)
+            | crate::Msg::SetDateFormat(_)
+            | crate::Msg::SetColour(_)
+            | crate::Msg::SetBrightness(_)
+            | crate::Msg::SetTimeZone
+            | crate::Msg::SaveSettings => {
+                let settings = crate::SETTINGS.lock(|s| s.borrow().clone());
+                match store.save(&settings).await {
+                    Ok(_) => info!("Settings saved"),
+                    Err(e) => error!("Error saving settings: {:?}", Debug2Format(&e)),
+                }
+            }
+            crate::Msg::LoadSettings => match store.load().await {
+                Ok(Some(settings)) => {
+                    crate::SETTINGS.lock(|s| s.replace(settings));
+                    msg_pub.publish_immediate(crate::Msg::SettingsLoaded);
+                }
+                Ok(None) => warn!("No saved settings"),
+                Err(e) => error!("Error loading settings: {:?}", Debug2Format(&e)),
+            },
+            crate::Msg::ResetSettings => {
+                if let Err(e) = store.reset().await {
+                    error!("Error erasing settings: {:?}", Debug2Format(&e));
+                }
+                crate::SETTINGS.lock(|s| s.replace(Settings::new()));
+                msg_pub.publish_immediate(crate::Msg::SettingsLoaded);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::{NaiveDateTime, NaiveTime, Timelike};
+use embassy_stm32::rtc::{Rtc, RtcConfig, RtcError};
+use stm32f401_embassy::alarm::AlarmMatch;
+use stm32f401_embassy::rtc::{RtcBackend, SoftAlarm};
+
+pub type RtcPeripheral = embassy_stm32::peripherals::RTC;
+
+// STM32F401 internal RTC (clocked from LSE - see `config.rcc.ls` in main).
+// Alarms are matched in software and there is no temperature sensor.
+pub struct Stm32Rtc {
+    rtc: Rtc,
+    alarm1: SoftAlarm,
+    alarm2: SoftAlarm,
+}
+
+impl Stm32Rtc {
+    pub fn new(rtc: RtcPeripheral) -> Self {
+        Stm32Rtc {
+            rtc: Rtc::new(rtc, RtcConfig::default()),
+            alarm1: SoftAlarm::new(),
+            alarm2: SoftAlarm::new(),
+        }
+    }
+}
+
+impl RtcBackend for Stm32Rtc {
+    type Error = RtcError;
+
+    async fn init(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn datetime(&mut self) -> Result<NaiveDateTime, Self::Error> {
+        let now: NaiveDateTime = self.rtc.now()?.into();
+        self.alarm1.check(now);
+        self.alarm2.check(now);
+        Ok(now)
+    }
+
+    async fn set_datetime(&mut self, dt: &NaiveDateTime) -> Result<(), Self::Error> {
+        self.rtc.set_datetime((*dt).into())
+    }
+
+    async fn alarm1(&mut self) -> Result<Option<AlarmMatch>, Self::Error> {
+        Ok(self.alarm1.alarm())
+    }
+
+    async fn set_alarm1(&mut self, m: AlarmMatch) -> Result<(), Self::Error> {
+        self.alarm1.set(m);
+        Ok(())
+    }
+
+    async fn clear_alarm1(&mut self) -> Result<(), Self::Error> {
+        self.alarm1.clear();
+        Ok(())
+    }
+
+    async fn alarm1_matched(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.alarm1.matched())
+    }
+
+    async fn clear_alarm1_matched(&mut self) -> Result<(), Self::Error> {
+        self.alarm1.clear_matched();
+        Ok(())
+    }
+
+    async fn alarm2(&mut self) -> Result<Option<NaiveTime>, Self::Error> {
+        Ok(self.alarm2.time())
+    }
+
+    async fn set_alarm2(&mut self, t: NaiveTime) -> Result<(), Self::Error> {
+        self.alarm2.set(AlarmMatch::Time(t.with_second(0).unwrap()));
+        Ok(())
+    }
+
+    async fn clear_alarm2(&mut self) -> Result<(), Self::Error> {
+        self.alarm2.clear();
+        Ok(())
+    }
+
+    async fn alarm2_matched(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.alarm2.matched())
+    }
+
+    async fn clear_alarm2_matched(&mut self) -> Result<(), Self::Error> {
+        self.alarm2.clear_matched();
+        Ok(())
+    }
+
+    async fn temperature(&mut self) -> Result<Option<f32>, Self::Error> {
+        Ok(None)
+    }
+
+    async fn start_temperature_conversion(&mut self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    async fn temperature_converting(&mut self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    // Smooth calibration (CALR) is not used
+    async fn aging(&mut self) -> Result<Option<i8>, Self::Error> {
+        Ok(None)
+    }
+
+    async fn set_aging(&mut self, _offset: i8) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn enable_square_wave(&mut self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    // Calendar not initialised (INITS clear) after backup domain reset
+    async fn oscillator_stopped(&mut self) -> Result<bool, Self::Error> {
+        Ok(!embassy_stm32::pac::RTC.isr().read().inits())
+    }
+
+    // INITS is set by writing the calendar
+    async fn clear_oscillator_stopped(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,991 @@
+use crate::alarm::{alarm_name, day_bit, AlarmName, Schedule, ALL_DAYS, WEEKDAYS, WEEKENDS};
+use crate::rtc::YEARS;
+use crate::settings::{Colour, DateFormat};
+use crate::tz::TimeZone;
+use chrono::{Datelike, NaiveDate, NaiveTime, Weekday};
+use core::fmt;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_till1};
+use nom::character::complete::{alpha1, char, multispace0, multispace1, one_of};
+use nom::combinator::{eof, map, map_opt, opt, peek, rest, value};
+use nom::error::Error;
+use nom::multi::fold_many0;
+use nom::sequence::{pair, preceded, tuple};
+use nom::IResult;
+
+mod commands;
+pub use commands::COMMANDS;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CliMsg {
+    Hello,
+    GetTime,
+    GetDate,
+    GetTemp,
+    // Fresh reading (forced conversion) rather than the last sample
+    GetTempNow,
+    GetAlarm,
+    SetTime(NaiveTime),
+    SetDate(NaiveDate),
+    SetAlarm(Schedule),
+    GetAlarm2,
+    SetAlarm2(NaiveTime),
+    ClearAlarm2,
+    AlarmAdd(AlarmName, Schedule),
+    AlarmList,
+    AlarmDelete(AlarmName),
+    AlarmEnable(AlarmName),
+    AlarmDisable(AlarmName),
+    SetSnooze(u8),
+    SetRingTime(u8),
+    // Minutes between temperature samples
+    SetTempInterval(u8),
+    SetDateFormat(DateFormat),
+    SetColour(Colour),
+    SetBrightness(u8),
+    ConfigSave,
+    ConfigLoad,
+    ConfigReset,
+    GetTimeZone,
+    SetTimeZone(TimeZone),
+    // Host UTC (Unix epoch ms)
+    Sync(u64),
+    DriftStatus,
+    DriftCalibrate,
+    SetAging(i8),
+    GpsStatus,
+    RtcStatus,
+    TempHistory,
+    TempStats,
+    TempDumpCsv,
+    // Commands starting with these words (all if empty)
+    Help(heapless::String<TOPIC_LEN>),
+    // Lines entered, numbered for `!n` (the history is the line editor's)
+    History,
+}
+
+fn digit_parser(input: &str) -> IResult<&str, u32, Error<&str>> {
+    map_opt(
+        pair(one_of("0123456789"), one_of("0123456789")),
+        |(d1, d2)| {
+            d1.to_digit(10)
+                .and_then(|n1| d2.to_digit(10).map(|n2| n1 * 10 + n2))
+        },
+    )(input)
+}
+
+// HH:MM:SS or HH:MM
+fn time_parser(input: &str) -> IResult<&str, NaiveTime, Error<&str>> {
+    map_opt(take_till1(char::is_whitespace), |time: &str| {
+        NaiveTime::parse_from_str(time, "%H:%M:%S")
+            .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+            .ok()
+    })(input)
+}
+
+// DD/MM/YYYY or YYYY-MM-DD
+fn date_parser(input: &str) -> IResult<&str, NaiveDate, Error<&str>> {
+    map_opt(take_till1(char::is_whitespace), |date: &str| {
+        NaiveDate::parse_from_str(date, "%d/%m/%Y")
+            .or_else(|_| NaiveDate::parse_from_str(date, "%Y-%m-%d"))
+            .ok()
+    })(input)
+}
+
+fn weekday_parser(input: &str) -> IResult<&str, Weekday, Error<&str>> {
+    map_opt(alpha1, |day: &str| day.parse::<Weekday>().ok())(input)
+}
+
+// weekdays | weekends | daily | <day>[,<day>...]
+fn days_parser(input: &str) -> IResult<&str, u8, Error<&str>> {
+    alt((
+        value(WEEKDAYS, tag("weekdays")),
+        value(WEEKENDS, tag("weekends")),
+        value(ALL_DAYS, tag("daily")),
+        map(
+            pair(
+                weekday_parser,
+                fold_many0(
+                    preceded(char(','), weekday_parser),
+                    || 0,
+                    |mask, day| mask | day_bit(day),
+                ),
+            ),
+            |(day, mask)| mask | day_bit(day),
+        ),
+    ))(input)
+}
+
+fn at_parser(input: &str) -> IResult<&str, (), Error<&str>> {
+    value((), opt(pair(tag("at"), multispace1)))(input)
+}
+
+// Alarm schedule:
+//   HH:MM[:SS]
+//   weekdays|weekends|daily|<day>[,<day>...] [at] HH:MM[:SS]
+//   on DD/MM/YYYY [at] HH:MM[:SS]
+//   hourly|every hour [at] :MM
+pub fn schedule_parser(input: &str) -> IResult<&str, Schedule, Error<&str>> {
+    alt((
+        map_opt(
+            tuple((
+                alt((tag("hourly"), tag("every hour"))),
+                multispace1,
+                at_parser,
+                char(':'),
+                digit_parser,
+            )),
+            |(_, _, _, _, m)| (m < 60).then_some(Schedule::Hourly(m as u8)),
+        ),
+        map(
+            tuple((
+                tag("on"),
+                multispace1,
+                date_parser,
+                multispace1,
+                at_parser,
+                time_parser,
+            )),
+            |(_, _, d, _, _, t)| Schedule::Once(d.and_time(t)),
+        ),
+        map(
+            tuple((days_parser, multispace1, at_parser, time_parser)),
+            |(mask, _, _, t)| match mask {
+                ALL_DAYS => Schedule::Daily(t),
+                mask => Schedule::Days(mask, t),
+            },
+        ),
+        map(time_parser, Schedule::Daily),
+    ))(input)
+}
+
+// Schedule words (for completion)
+const SCHEDULE_WORDS: [&str; 5] = ["weekdays", "weekends", "daily", "on", "hourly"];
+
+// Argument types: how each is parsed, shown in usage and completed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arg {
+    // HH:MM:SS
+    Time,
+    // HH:MM
+    AlarmTime,
+    // DD/MM/YYYY
+    Date,
+    // The rest of the line (see schedule_parser)
+    Schedule,
+    Name,
+    // 1-60
+    Minutes,
+    // 0-100
+    Percent,
+    // DS3231 aging offset
+    Aging,
+    // Unix epoch ms
+    EpochMs,
+    // POSIX TZ rule
+    TimeZone,
+    DateFormat,
+    Colour,
+    // Command words for `help` (the rest of the line)
+    Topic,
+}
+
+impl Arg {
+    // As shown in usage
+    pub fn name(&self) -> &'static str {
+        match self {
+            Arg::Time => "HH:MM:SS",
+            Arg::AlarmTime => "HH:MM",
+            Arg::Date => "DD/MM/YYYY",
+            Arg::Schedule => "<schedule>",
+            Arg::Name => "<name>",
+            Arg::Minutes => "<minutes 1-60>",
+            Arg::Percent => "<percent 0-100>",
+            Arg::Aging => "<offset -128..127>",
+            Arg::EpochMs => "<unix-ms>",
+            Arg::TimeZone => "<POSIX TZ rule>",
+            Arg::DateFormat => "dmy|ymd|mdy",
+            Arg::Colour => "<colour>",
+            Arg::Topic => "<command>",
+        }
+    }
+
+    // Explanation for `help <command>`
+    pub fn detail(&self) -> Option<&'static str> {
+        match self {
+            Arg::Schedule => Some(
+                "HH:MM[:SS], weekdays|weekends|daily|<day>[,<day>...] [at] HH:MM[:SS], \
+                 on DD/MM/YYYY [at] HH:MM[:SS] or hourly [at] :MM",
+            ),
+            Arg::Name => Some("letters, digits, _ and -"),
+            Arg::TimeZone => Some("e.g. UTC0, CET-1CEST,M3.5.0,M10.5.0/3"),
+            _ => None,
+        }
+    }
+
+    // Fixed values by index, for completion
+    pub fn choice(&self, i: usize) -> Option<&'static str> {
+        match self {
+            Arg::DateFormat => DateFormat::ALL.get(i).map(DateFormat::name),
+            Arg::Colour => Colour::ALL.get(i).map(Colour::name),
+            Arg::Schedule => SCHEDULE_WORDS.get(i).copied(),
+            // First words of the commands
+            Arg::Topic => COMMANDS
+                .iter()
+                .enumerate()
+                .map(|(i, c)| (i, c.words[0]))
+                .filter(|(i, w)| !COMMANDS[..*i].iter().any(|c| c.words[0] == *w))
+                .nth(i)
+                .map(|(_, w)| w),
+            _ => None,
+        }
+    }
+
+    fn parse<'a>(&self, input: &'a str) -> IResult<&'a str, Value, Error<&'a str>> {
+        match self {
+            Arg::Schedule => map(schedule_parser, Value::Schedule)(input),
+            Arg::Topic => map_opt(rest, |topic: &str| {
+                let mut words: heapless::String<TOPIC_LEN> = heapless::String::new();
+                for word in topic.split_whitespace() {
+                    if !words.is_empty() {
+                        words.push(' ').ok()?;
+                    }
+                    words.push_str(word).ok()?;
+                }
+                let known = help_topic(&words).next().is_some();
+                known.then_some(Value::Topic(words))
+            })(input),
+            arg => map_opt(take_till1(char::is_whitespace), |s| arg.value(s))(input),
+        }
+    }
+
+    // Value of a single word argument
+    fn value(&self, s: &str) -> Option<Value> {
+        match self {
+            Arg::Time => NaiveTime::parse_from_str(s, "%H:%M:%S")
+                .ok()
+                .map(Value::Time),
+            Arg::AlarmTime => NaiveTime::parse_from_str(s, "%H:%M").ok().map(Value::Time),
+            Arg::Date => NaiveDate::parse_from_str(s, "%d/%m/%Y")
+                .ok()
+                .filter(|d| YEARS.contains(&d.year()))
+                .map(Value::Date),
+            Arg::Schedule | Arg::Topic => None,
+            Arg::Name => alarm_name(s).map(Value::Name),
+            Arg::Minutes => s
+                .parse()
+                .ok()
+                .filter(|m| (1..=60).contains(m))
+                .map(Value::U8),
+            Arg::Percent => s.parse().ok().filter(|p| *p <= 100).map(Value::U8),
+            Arg::Aging => s.parse().ok().map(Value::I8),
+            Arg::EpochMs => s.parse().ok().map(Value::U64),
+            Arg::TimeZone => TimeZone::parse(s).map(Value::TimeZone),
+            Arg::DateFormat => DateFormat::from_name(s).map(Value::DateFormat),
+            Arg::Colour => Colour::from_name(s).map(Value::Colour),
+        }
+    }
+}
+
+// Parsed argument
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Time(NaiveTime),
+    Date(NaiveDate),
+    Schedule(Schedule),
+    Name(AlarmName),
+    U8(u8),
+    I8(i8),
+    U64(u64),
+    TimeZone(TimeZone),
+    DateFormat(DateFormat),
+    Colour(Colour),
+    Topic(heapless::String<TOPIC_LEN>),
+}
+
+impl Value {
+    fn time(&self) -> Option<NaiveTime> {
+        match self {
+            Value::Time(t) => Some(*t),
+            _ => None,
+        }
+    }
+
+    fn date(&self) -> Option<NaiveDate> {
+        match self {
+            Value::Date(d) => Some(*d),
+            _ => None,
+        }
+    }
+
+    fn schedule(&self) -> Option<Schedule> {
+        match self {
+            Value::Schedule(s) => Some(*s),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> Option<AlarmName> {
+        match self {
+            Value::Name(n) => Some(n.clone()),
+            _ => None,
+        }
+    }
+
+    fn u8(&self) -> Option<u8> {
+        match self {
+            Value::U8(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn i8(&self) -> Option<i8> {
+        match self {
+            Value::I8(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn u64(&self) -> Option<u64> {
+        match self {
+            Value::U64(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn tz(&self) -> Option<TimeZone> {
+        match self {
+            Value::TimeZone(tz) => Some(tz.clone()),
+            _ => None,
+        }
+    }
+
+    fn date_format(&self) -> Option<DateFormat> {
+        match self {
+            Value::DateFormat(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    fn colour(&self) -> Option<Colour> {
+        match self {
+            Value::Colour(c) => Some(*c),
+            _ => None,
+        }
+    }
+
+    fn topic(&self) -> Option<heapless::String<TOPIC_LEN>> {
+        match self {
+            Value::Topic(t) => Some(t.clone()),
+            _ => None,
+        }
+    }
+}
+
+// Most arguments any command takes
+const MAX_ARGS: usize = 2;
+
+// Room for a help topic (command words)
+pub const TOPIC_LEN: usize = 32;
+
+// A command in the registry
+pub struct Command {
+    pub words: &'static [&'static str],
+    pub args: &'static [Arg],
+    // One line description (commands without one aren't listed)
+    pub help: &'static str,
+    // Message for the parsed arguments (a value for each of `args`)
+    handler: fn(&[Value]) -> Option<CliMsg>,
+}
+
+impl Command {
+    // Number of the command words `line` starts with (whole words), and the
+    // rest of the line
+    fn matched<'a>(&self, line: &'a str) -> (usize, &'a str) {
+        let mut input = line;
+        for (n, word) in self.words.iter().enumerate() {
+            match tuple((
+                multispace0::<_, Error<&str>>,
+                tag(*word),
+                peek(alt((multispace1, eof))),
+            ))(input)
+            {
+                Ok((rest, _)) => input = rest,
+                Err(_) => return (n, input),
+            }
+        }
+        (self.words.len(), input)
+    }
+
+    // Arguments after the command words, or where they went wrong
+    fn parse_args<'a>(&'static self, input: &'a str) -> Result<CliMsg, (&'a str, Expected)> {
+        let mut values: heapless::Vec<Value, MAX_ARGS> = heapless::Vec::new();
+        let mut input = input;
+        for arg in self.args {
+            let rest = input.trim_start();
+            let (after, value) = arg
+                .parse(rest)
+                .map_err(|_| (rest, Expected::Arg(self, *arg)))?;
+            values.push(value).ok();
+            input = after;
+        }
+        let rest = input.trim_start();
+        if !rest.is_empty() {
+            return Err((rest, Expected::End(self)));
+        }
+        (self.handler)(&values).ok_or((rest, Expected::End(self)))
+    }
+
+    // Usage and description, with the arguments explained if `detail`
+    pub fn write_help<W: fmt::Write>(&self, w: &mut W, detail: bool) -> fmt::Result {
+        write!(w, "{:<32}{}", self, self.help)?;
+        for arg in self.args.iter().filter(|_| detail) {
+            if let Some(text) = arg.detail() {
+                write!(w, "\r\n  {}: {}", arg.name(), text)?;
+            } else if arg.choice(0).is_some() {
+                write!(w, "\r\n  {}:", arg.name())?;
+                for choice in (0..).map_while(|i| arg.choice(i)) {
+                    write!(w, " {}", choice)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for Command {
+    fn eq(&self, other: &Self) -> bool {
+        self.words == other.words
+    }
+}
+
+impl fmt::Debug for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Command({})", self)
+    }
+}
+
+// Usage: the words then the arguments (padded as asked)
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut usage: heapless::String<64> = heapless::String::new();
+        let args = self.args.iter().map(Arg::name);
+        for (i, word) in self.words.iter().copied().chain(args).enumerate() {
+            if i > 0 {
+                usage.push(' ').ok();
+            }
+            usage.push_str(word).ok();
+        }
+        f.pad(&usage)
+    }
+}
+
+// What a line was missing
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expected {
+    // A command word after `prefix`, with the nearest one if close
+    Word {
+        prefix: &'static [&'static str],
+        nearest: Option<&'static str>,
+    },
+    Arg(&'static Command, Arg),
+    End(&'static Command),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError<'a> {
+    pub line: &'a str,
+    // Byte offset of the token that couldn't be parsed
+    pub at: usize,
+    pub expected: Expected,
+}
+
+// The line with a caret under the failing token, what was expected there,
+// then the usage or a suggestion
+impl fmt::Display for ParseError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let col = self.line[..self.at].chars().count();
+        write!(f, "  {}\r\n  {:col$}^ ", self.line, "")?;
+        match &self.expected {
+            Expected::Word { prefix, nearest } => {
+                match self.line[self.at..].trim().is_empty() {
+                    true => f.write_str("incomplete command")?,
+                    false => f.write_str("unknown command")?,
+                }
+                match nearest {
+                    Some(word) => {
+                        f.write_str("\r\nDid you mean \"")?;
+                        for w in prefix.iter() {
+                            write!(f, "{} ", w)?;
+                        }
+                        write!(f, "{}\"?", word)
+                    }
+                    None => {
+                        f.write_str("\r\nTry \"help")?;
+                        for w in prefix.iter() {
+                            write!(f, " {}", w)?;
+                        }
+                        f.write_str("\"")
+                    }
+                }
+            }
+            Expected::Arg(command, arg) => {
+                write!(f, "expected {}\r\nUsage: {}", arg.name(), command)
+            }
+            Expected::End(command) => write!(f, "expected end of line\r\nUsage: {}", command),
+        }
+    }
+}
+
+// Edit distance (counting a swap of adjacent characters as one edit), or
+// None for long words
+fn distance(a: &str, b: &str) -> Option<usize> {
+    const MAX: usize = 16;
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() > MAX || b.len() > MAX {
+        return None;
+    }
+    // Rows i-2, i-1 and i of the distance table
+    let mut rows = [[0; MAX + 1]; 3];
+    for (j, d) in rows[1].iter_mut().enumerate() {
+        *d = j;
+    }
+    for i in 1..=a.len() {
+        let [older, prev, row] = &mut rows;
+        row[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (prev[j] + 1).min(row[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(older[j - 2] + 1);
+            }
+        }
+        rows.rotate_left(1);
+    }
+    Some(rows[1][b.len()])
+}
+
+// Closest command word following `prefix` to the one typed, if close enough
+// to be a typo
+fn nearest(prefix: &[&str], typed: &str) -> Option<&'static str> {
+    let allowed = if typed.len() <= 4 { 1 } else { 2 };
+    COMMANDS
+        .iter()
+        .filter(|c| c.words.len() > prefix.len() && c.words.starts_with(prefix))
+        .filter_map(|c| {
+            let word = c.words[prefix.len()];
+            Some((distance(typed, word)?, word))
+        })
+        .filter(|(d, _)| *d <= allowed)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, word)| word)
+}
+
+pub fn parse_command(line: &str) -> Result<CliMsg, ParseError<'_>> {
+    let at = |rest: &str| line.len() - rest.len();
+    // Report the command matching the most words, then the one that got
+    // furthest, preferring a suggestion
+    let mut error: Option<((usize, usize, bool), ParseError)> = None;
+    for command in COMMANDS {
+        let (n, input) = command.matched(line);
+        let (rest, expected) = if n == command.words.len() {
+            match command.parse_args(input) {
+                Ok(msg) => return Ok(msg),
+                Err(e) => e,
+            }
+        } else {
+            let rest = input.trim_start();
+            let prefix = &command.words[..n];
+            let typed = rest.split_whitespace().next().unwrap_or("");
+            let nearest = nearest(prefix, typed).filter(|_| !typed.is_empty());
+            (rest, Expected::Word { prefix, nearest })
+        };
+        let suggestion = matches!(
+            expected,
+            Expected::Word {
+                nearest: Some(_),
+                ..
+            }
+        );
+        let offset = at(rest);
+        let rank = (n, offset, suggestion);
+        if error.as_ref().is_none_or(|(best, _)| rank > *best) {
+            let e = ParseError {
+                line,
+                at: offset,
+                expected,
+            };
+            error = Some((rank, e));
+        }
+    }
+    Err(error.map(|(_, e)| e).unwrap_or(ParseError {
+        line,
+        at: 0,
+        expected: Expected::Word {
+            prefix: &[],
+            nearest: None,
+        },
+    }))
+}
+
+// Commands listed by `help <topic>`: those starting with the topic words
+pub fn help_topic(topic: &str) -> impl Iterator<Item = &'static Command> + '_ {
+    COMMANDS.iter().filter(move |c| {
+        !c.help.is_empty()
+            && topic.split_whitespace().count() <= c.words.len()
+            && c.words
+                .iter()
+                .zip(topic.split_whitespace())
+                .all(|(w, t)| *w == t)
+    })
+}
+
+// Words (command words or argument choices) that could complete the last,
+// partly typed, word of `line`, for tab completion. Each is given once.
+pub fn completions(line: &str) -> impl Iterator<Item = &'static str> + '_ {
+    let (typed, partial) = line.split_at(line.trim_end_matches(|c: char| !c.is_whitespace()).len());
+    let n = typed.split_whitespace().count();
+    let candidates = move |command: &'static Command| {
+        let matches = command
+            .words
+            .iter()
+            .zip(typed.split_whitespace())
+            .all(|(w, t)| *w == t);
+        (0..)
+            .map_while(move |j| {
+                if !matches {
+                    return None;
+                }
+                match n.checked_sub(command.words.len()) {
+                    // A command word
+                    None => (j == 0).then(|| command.words[n]),
+                    // A value for an argument
+                    Some(i) => command.args.get(i)?.choice(j),
+                }
+            })
+            .filter(move |word| word.starts_with(partial))
+    };
+    COMMANDS.iter().enumerate().flat_map(move |(i, command)| {
+        candidates(command).filter(move |word| {
+            !COMMANDS[..i]
+                .iter()
+                .any(|c| candidates(c).any(|w| w == *word))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Option<CliMsg> {
+        parse_command(s).ok()
+    }
+
+    #[test]
+    fn get_commands() {
+        assert_eq!(parse("hello"), Some(CliMsg::Hello));
+        assert_eq!(parse("get time"), Some(CliMsg::GetTime));
+        assert_eq!(parse("  get   date "), Some(CliMsg::GetDate));
+        assert_eq!(parse("get temp"), Some(CliMsg::GetTemp));
+        assert_eq!(parse("get temp now"), Some(CliMsg::GetTempNow));
+        assert_eq!(parse("get alarm"), Some(CliMsg::GetAlarm));
+        assert_eq!(parse("get alarm2"), Some(CliMsg::GetAlarm2));
+        assert_eq!(parse("clear alarm2"), Some(CliMsg::ClearAlarm2));
+    }
+
+    #[test]
+    fn set_commands() {
+        assert_eq!(
+            parse("set time 12:34:56"),
+            Some(CliMsg::SetTime(
+                NaiveTime::from_hms_opt(12, 34, 56).unwrap()
+            ))
+        );
+        assert_eq!(
+            parse("set date 24/12/2026 "),
+            Some(CliMsg::SetDate(
+                NaiveDate::from_ymd_opt(2026, 12, 24).unwrap()
+            ))
+        );
+        assert_eq!(
+            parse("set alarm 07:00:00"),
+            Some(CliMsg::SetAlarm(Schedule::Daily(
+                NaiveTime::from_hms_opt(7, 0, 0).unwrap()
+            )))
+        );
+        assert_eq!(
+            parse("set alarm2 21:30"),
+            Some(CliMsg::SetAlarm2(
+                NaiveTime::from_hms_opt(21, 30, 0).unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn alarm_schedules() {
+        let t = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let schedule = |s: &str| match parse(s) {
+            Some(CliMsg::SetAlarm(schedule)) => Some(schedule),
+            _ => None,
+        };
+        assert_eq!(
+            schedule("set alarm weekdays at 07:00"),
+            Some(Schedule::Days(WEEKDAYS, t(7, 0)))
+        );
+        assert_eq!(
+            schedule("set alarm Saturday 09:30"),
+            Some(Schedule::Days(day_bit(Weekday::Sat), t(9, 30)))
+        );
+        assert_eq!(
+            schedule("set alarm mon,wed,fri 06:45:30"),
+            Some(Schedule::Days(
+                0b10101,
+                NaiveTime::from_hms_opt(6, 45, 30).unwrap()
+            ))
+        );
+        assert_eq!(
+            schedule("set alarm daily 08:00"),
+            Some(Schedule::Daily(t(8, 0)))
+        );
+        let xmas = NaiveDate::from_ymd_opt(2026, 12, 24).unwrap();
+        assert_eq!(
+            schedule("set alarm on 2026-12-24 at 18:00"),
+            Some(Schedule::Once(xmas.and_time(t(18, 0))))
+        );
+        assert_eq!(
+            schedule("set alarm on 24/12/2026 18:00"),
+            Some(Schedule::Once(xmas.and_time(t(18, 0))))
+        );
+        assert_eq!(
+            schedule("set alarm every hour at :15"),
+            Some(Schedule::Hourly(15))
+        );
+        assert_eq!(schedule("set alarm hourly :05"), Some(Schedule::Hourly(5)));
+        assert_eq!(schedule("set alarm hourly :75"), None);
+        assert_eq!(schedule("set alarm funday 07:00"), None);
+        assert_eq!(schedule("set alarm weekdays"), None);
+        assert_eq!(schedule("set alarm 07:00 extra"), None);
+    }
+
+    #[test]
+    fn alarm_table_commands() {
+        let name = |s| alarm_name(s).unwrap();
+        assert_eq!(
+            parse("alarm add wake weekdays at 07:00"),
+            Some(CliMsg::AlarmAdd(
+                name("wake"),
+                Schedule::Days(WEEKDAYS, NaiveTime::from_hms_opt(7, 0, 0).unwrap())
+            ))
+        );
+        assert_eq!(parse("alarm list"), Some(CliMsg::AlarmList));
+        assert_eq!(
+            parse("alarm delete wake"),
+            Some(CliMsg::AlarmDelete(name("wake")))
+        );
+        assert_eq!(
+            parse(" alarm enable wake "),
+            Some(CliMsg::AlarmEnable(name("wake")))
+        );
+        assert_eq!(
+            parse("alarm disable wake"),
+            Some(CliMsg::AlarmDisable(name("wake")))
+        );
+        assert_eq!(parse("alarm add wake"), None);
+        assert_eq!(parse("alarm add much_too_long_name 07:00"), None);
+        assert_eq!(parse("alarm delete wake now"), None);
+    }
+
+    #[test]
+    fn ring_settings() {
+        assert_eq!(parse("set snooze 9"), Some(CliMsg::SetSnooze(9)));
+        assert_eq!(parse("set ringtime 5 "), Some(CliMsg::SetRingTime(5)));
+        assert_eq!(parse("set snooze 0"), None);
+        assert_eq!(parse("set ringtime 90"), None);
+        assert_eq!(parse("set snooze 5m"), None);
+        assert_eq!(
+            parse("set tempinterval 15"),
+            Some(CliMsg::SetTempInterval(15))
+        );
+    }
+
+    #[test]
+    fn settings_commands() {
+        assert_eq!(
+            parse("set dateformat ymd"),
+            Some(CliMsg::SetDateFormat(DateFormat::Ymd))
+        );
+        assert_eq!(parse("set color red"), Some(CliMsg::SetColour(Colour::Red)));
+        assert_eq!(parse("set brightness 40"), Some(CliMsg::SetBrightness(40)));
+        assert_eq!(parse("set brightness 101"), None);
+        assert_eq!(parse("set dateformat iso"), None);
+        assert_eq!(parse("config save"), Some(CliMsg::ConfigSave));
+        assert_eq!(parse("config load"), Some(CliMsg::ConfigLoad));
+        assert_eq!(parse("config reset "), Some(CliMsg::ConfigReset));
+        assert_eq!(parse("config erase"), None);
+    }
+
+    #[test]
+    fn tz_commands() {
+        let uk = TimeZone::parse("GMT0BST,M3.5.0/1,M10.5.0").unwrap();
+        assert_eq!(
+            parse("set tz GMT0BST,M3.5.0/1,M10.5.0 "),
+            Some(CliMsg::SetTimeZone(uk))
+        );
+        assert_eq!(parse("get tz"), Some(CliMsg::GetTimeZone));
+        assert_eq!(parse("set tz GMT0BST"), None);
+        assert_eq!(parse("set tz GMT0 BST"), None);
+    }
+
+    #[test]
+    fn sync_command() {
+        assert_eq!(
+            parse("sync 1772366400250"),
+            Some(CliMsg::Sync(1_772_366_400_250))
+        );
+        assert_eq!(parse("sync -1"), None);
+        assert_eq!(parse("sync 12:00"), None);
+    }
+
+    #[test]
+    fn drift_commands() {
+        assert_eq!(parse("drift status"), Some(CliMsg::DriftStatus));
+        assert_eq!(parse("drift calibrate"), Some(CliMsg::DriftCalibrate));
+        assert_eq!(parse("set aging -12"), Some(CliMsg::SetAging(-12)));
+        assert_eq!(parse("set aging 127"), Some(CliMsg::SetAging(127)));
+        assert_eq!(parse("set aging 128"), None);
+        assert_eq!(parse("drift reset"), None);
+    }
+
+    #[test]
+    fn temp_commands() {
+        assert_eq!(parse("temp history"), Some(CliMsg::TempHistory));
+        assert_eq!(parse("temp stats"), Some(CliMsg::TempStats));
+        assert_eq!(parse("temp dump  csv "), Some(CliMsg::TempDumpCsv));
+        assert_eq!(parse("temp dump"), None);
+    }
+
+    #[test]
+    fn rtc_command() {
+        assert_eq!(parse("rtc status"), Some(CliMsg::RtcStatus));
+        assert_eq!(parse("rtc"), None);
+    }
+
+    #[test]
+    fn history_command() {
+        assert_eq!(parse("history"), Some(CliMsg::History));
+        assert_eq!(parse("history 3"), None);
+    }
+
+    #[test]
+    fn gps_command() {
+        assert_eq!(parse("gps status"), Some(CliMsg::GpsStatus));
+        assert_eq!(parse(" gps  status "), Some(CliMsg::GpsStatus));
+        assert_eq!(parse("gps"), None);
+    }
+
+    #[test]
+    fn command_completion() {
+        let complete = |line| completions(line).collect::<Vec<_>>();
+        assert_eq!(complete("set ti"), ["time"]);
+        assert_eq!(complete("set al"), ["alarm", "alarm2"]);
+        assert_eq!(complete(" get  temp "), ["now"]);
+        assert_eq!(complete("co"), ["config"]);
+        assert_eq!(complete("c"), ["clear", "config"]);
+        assert_eq!(complete("alarm add "), Vec::<&str>::new());
+        assert_eq!(complete("frob"), Vec::<&str>::new());
+        // Every first word once
+        assert_eq!(complete("").len(), 13);
+        assert_eq!(complete("hi"), ["history"]);
+        // Argument values
+        assert_eq!(complete("set colour b"), ["blue", "black"]);
+        assert_eq!(complete("set dateformat ").len(), 3);
+        assert_eq!(complete("alarm add wake week"), ["weekdays", "weekends"]);
+        assert_eq!(complete("set alarm daily "), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn diagnostics() {
+        let error = |s| parse_command(s).unwrap_err().to_string();
+        assert_eq!(
+            error("set time 25:00"),
+            "  set time 25:00\r\n           ^ expected HH:MM:SS\r\nUsage: set time HH:MM:SS"
+        );
+        assert_eq!(
+            error("set alarm 07:00 extra"),
+            "  set alarm 07:00 extra\r\n                  ^ expected end of line\r\n\
+             Usage: set alarm <schedule>"
+        );
+        assert_eq!(
+            error("alarm add wake"),
+            "  alarm add wake\r\n                ^ expected <schedule>\r\n\
+             Usage: alarm add <name> <schedule>"
+        );
+        // Typos
+        assert_eq!(
+            error("get tmie"),
+            "  get tmie\r\n      ^ unknown command\r\nDid you mean \"get time\"?"
+        );
+        assert_eq!(
+            error(" tmep stats"),
+            "   tmep stats\r\n   ^ unknown command\r\nDid you mean \"temp\"?"
+        );
+        assert_eq!(
+            error("get temp nwo"),
+            "  get temp nwo\r\n           ^ unknown command\r\nDid you mean \"get temp now\"?"
+        );
+        assert_eq!(
+            error("frobnicate"),
+            "  frobnicate\r\n  ^ unknown command\r\nTry \"help\""
+        );
+        assert_eq!(
+            error("drift"),
+            "  drift\r\n       ^ incomplete command\r\nTry \"help drift\""
+        );
+        assert_eq!(distance("alarm", "alarm2"), Some(1));
+        assert_eq!(distance("tmie", "time"), Some(1));
+        assert_eq!(distance("", "abc"), Some(3));
+    }
+
+    #[test]
+    fn help() {
+        assert_eq!(parse("help"), Some(CliMsg::Help("".try_into().unwrap())));
+        assert_eq!(
+            parse("help  set   time"),
+            Some(CliMsg::Help("set time".try_into().unwrap()))
+        );
+        assert_eq!(parse("help frobnicate"), None);
+        let listed = |topic| help_topic(topic).map(|c| c.to_string()).collect::<Vec<_>>();
+        assert_eq!(listed("drift"), ["drift status", "drift calibrate"]);
+        assert_eq!(listed("set colour"), ["set colour <colour>"]);
+        // Hidden
+        assert_eq!(listed("set color"), Vec::<String>::new());
+        assert_eq!(listed("").len(), COMMANDS.len() - 1);
+
+        let mut s = String::new();
+        let command = help_topic("set colour").next().unwrap();
+        command.write_help(&mut s, true).unwrap();
+        assert_eq!(
+            s,
+            "set colour <colour>             Set the display colour\r\n  \
+             <colour>: green red blue yellow cyan magenta black"
+        );
+        assert_eq!(completions("help se").collect::<Vec<_>>(), ["set"]);
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert_eq!(parse("set time 25:00:00"), None);
+        assert_eq!(parse("set date 31/02/2026"), None);
+        // Outside the RTC's years
+        assert_eq!(parse("set date 31/12/1999"), None);
+        assert_eq!(parse("set date 01/01/2200"), None);
+        assert_eq!(parse("set time"), None);
+        assert_eq!(parse("set alarm2 21:30:00"), None);
+        assert_eq!(parse("gettime"), None);
+        assert_eq!(parse("frobnicate"), None);
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("get time now"), None);
+        assert_eq!(parse("set alarm2x 07:00"), None);
+    }
+}
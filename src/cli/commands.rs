@@ -0,0 +1,256 @@
+// Command registry
+//
+// Each entry gives the command words, the arguments that follow, a line of
+// help and the message for the parsed arguments. Parsing, error messages,
+// `help` and tab completion all come from this table, in this order.
+use super::{Arg, CliMsg, Command};
+
+pub const COMMANDS: &[Command] = &[
+    Command {
+        words: &["help"],
+        args: &[],
+        help: "List the commands",
+        handler: |_| Some(CliMsg::Help(heapless::String::new())),
+    },
+    Command {
+        words: &["help"],
+        args: &[Arg::Topic],
+        help: "Describe the commands starting with these words",
+        handler: |v| v.first()?.topic().map(CliMsg::Help),
+    },
+    Command {
+        words: &["hello"],
+        args: &[],
+        help: "Check the console is working",
+        handler: |_| Some(CliMsg::Hello),
+    },
+    Command {
+        words: &["history"],
+        args: &[],
+        help: "List the lines entered, numbered for !n",
+        handler: |_| Some(CliMsg::History),
+    },
+    Command {
+        words: &["get", "time"],
+        args: &[],
+        help: "Show the local time",
+        handler: |_| Some(CliMsg::GetTime),
+    },
+    Command {
+        words: &["set", "time"],
+        args: &[Arg::Time],
+        help: "Set the local time",
+        handler: |v| v.first()?.time().map(CliMsg::SetTime),
+    },
+    Command {
+        words: &["get", "date"],
+        args: &[],
+        help: "Show the local date",
+        handler: |_| Some(CliMsg::GetDate),
+    },
+    Command {
+        words: &["set", "date"],
+        args: &[Arg::Date],
+        help: "Set the local date",
+        handler: |v| v.first()?.date().map(CliMsg::SetDate),
+    },
+    Command {
+        words: &["get", "tz"],
+        args: &[],
+        help: "Show the time zone rule",
+        handler: |_| Some(CliMsg::GetTimeZone),
+    },
+    Command {
+        words: &["set", "tz"],
+        args: &[Arg::TimeZone],
+        help: "Set the time zone, e.g. GMT0BST,M3.5.0/1,M10.5.0",
+        handler: |v| v.first()?.tz().map(CliMsg::SetTimeZone),
+    },
+    Command {
+        words: &["sync"],
+        args: &[Arg::EpochMs],
+        help: "Set the time from the host (sent by tools/clock-sync)",
+        handler: |v| v.first()?.u64().map(CliMsg::Sync),
+    },
+    Command {
+        words: &["get", "alarm"],
+        args: &[],
+        help: "Show the alarms",
+        handler: |_| Some(CliMsg::GetAlarm),
+    },
+    Command {
+        words: &["set", "alarm"],
+        args: &[Arg::Schedule],
+        help: "Set the main alarm, e.g. weekdays at 07:00",
+        handler: |v| v.first()?.schedule().map(CliMsg::SetAlarm),
+    },
+    Command {
+        words: &["get", "alarm2"],
+        args: &[],
+        help: "Show the RTC alarm 2",
+        handler: |_| Some(CliMsg::GetAlarm2),
+    },
+    Command {
+        words: &["set", "alarm2"],
+        args: &[Arg::AlarmTime],
+        help: "Set the RTC alarm 2 (daily)",
+        handler: |v| v.first()?.time().map(CliMsg::SetAlarm2),
+    },
+    Command {
+        words: &["clear", "alarm2"],
+        args: &[],
+        help: "Turn off the RTC alarm 2",
+        handler: |_| Some(CliMsg::ClearAlarm2),
+    },
+    Command {
+        words: &["alarm", "add"],
+        args: &[Arg::Name, Arg::Schedule],
+        help: "Add a named alarm, e.g. wake weekdays at 07:00",
+        handler: |v| Some(CliMsg::AlarmAdd(v.first()?.name()?, v.get(1)?.schedule()?)),
+    },
+    Command {
+        words: &["alarm", "list"],
+        args: &[],
+        help: "List the named alarms",
+        handler: |_| Some(CliMsg::AlarmList),
+    },
+    Command {
+        words: &["alarm", "delete"],
+        args: &[Arg::Name],
+        help: "Delete a named alarm",
+        handler: |v| v.first()?.name().map(CliMsg::AlarmDelete),
+    },
+    Command {
+        words: &["alarm", "enable"],
+        args: &[Arg::Name],
+        help: "Enable a named alarm",
+        handler: |v| v.first()?.name().map(CliMsg::AlarmEnable),
+    },
+    Command {
+        words: &["alarm", "disable"],
+        args: &[Arg::Name],
+        help: "Disable a named alarm",
+        handler: |v| v.first()?.name().map(CliMsg::AlarmDisable),
+    },
+    Command {
+        words: &["set", "snooze"],
+        args: &[Arg::Minutes],
+        help: "Set the snooze time",
+        handler: |v| v.first()?.u8().map(CliMsg::SetSnooze),
+    },
+    Command {
+        words: &["set", "ringtime"],
+        args: &[Arg::Minutes],
+        help: "Set how long the alarm rings",
+        handler: |v| v.first()?.u8().map(CliMsg::SetRingTime),
+    },
+    Command {
+        words: &["set", "dateformat"],
+        args: &[Arg::DateFormat],
+        help: "Set the date format",
+        handler: |v| v.first()?.date_format().map(CliMsg::SetDateFormat),
+    },
+    Command {
+        words: &["set", "colour"],
+        args: &[Arg::Colour],
+        help: "Set the display colour",
+        handler: |v| v.first()?.colour().map(CliMsg::SetColour),
+    },
+    // US spelling
+    Command {
+        words: &["set", "color"],
+        args: &[Arg::Colour],
+        help: "",
+        handler: |v| v.first()?.colour().map(CliMsg::SetColour),
+    },
+    Command {
+        words: &["set", "brightness"],
+        args: &[Arg::Percent],
+        help: "Set the display brightness",
+        handler: |v| v.first()?.u8().map(CliMsg::SetBrightness),
+    },
+    Command {
+        words: &["config", "save"],
+        args: &[],
+        help: "Save the settings",
+        handler: |_| Some(CliMsg::ConfigSave),
+    },
+    Command {
+        words: &["config", "load"],
+        args: &[],
+        help: "Load the saved settings",
+        handler: |_| Some(CliMsg::ConfigLoad),
+    },
+    Command {
+        words: &["config", "reset"],
+        args: &[],
+        help: "Reset the settings to the defaults",
+        handler: |_| Some(CliMsg::ConfigReset),
+    },
+    Command {
+        words: &["drift", "status"],
+        args: &[],
+        help: "Show the measured RTC drift",
+        handler: |_| Some(CliMsg::DriftStatus),
+    },
+    Command {
+        words: &["drift", "calibrate"],
+        args: &[],
+        help: "Set the aging offset from the measured drift",
+        handler: |_| Some(CliMsg::DriftCalibrate),
+    },
+    Command {
+        words: &["set", "aging"],
+        args: &[Arg::Aging],
+        help: "Set the DS3231 aging offset",
+        handler: |v| v.first()?.i8().map(CliMsg::SetAging),
+    },
+    Command {
+        words: &["get", "temp"],
+        args: &[],
+        help: "Show the last temperature sample",
+        handler: |_| Some(CliMsg::GetTemp),
+    },
+    Command {
+        words: &["get", "temp", "now"],
+        args: &[],
+        help: "Measure the temperature now",
+        handler: |_| Some(CliMsg::GetTempNow),
+    },
+    Command {
+        words: &["set", "tempinterval"],
+        args: &[Arg::Minutes],
+        help: "Set the time between temperature samples",
+        handler: |v| v.first()?.u8().map(CliMsg::SetTempInterval),
+    },
+    Command {
+        words: &["temp", "history"],
+        args: &[],
+        help: "Show hourly temperatures for the last day",
+        handler: |_| Some(CliMsg::TempHistory),
+    },
+    Command {
+        words: &["temp", "stats"],
+        args: &[],
+        help: "Show daily temperature statistics",
+        handler: |_| Some(CliMsg::TempStats),
+    },
+    Command {
+        words: &["temp", "dump", "csv"],
+        args: &[],
+        help: "Dump the temperature log as CSV",
+        handler: |_| Some(CliMsg::TempDumpCsv),
+    },
+    Command {
+        words: &["rtc", "status"],
+        args: &[],
+        help: "Show the RTC health",
+        handler: |_| Some(CliMsg::RtcStatus),
+    },
+    Command {
+        words: &["gps", "status"],
+        args: &[],
+        help: "Show the GPS fix",
+        handler: |_| Some(CliMsg::GpsStatus),
+    },
+];
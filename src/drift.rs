@@ -0,0 +1,253 @@
+// RTC drift estimation from host syncs (see `sync`)
+//
+// Each sync measures the error accumulated since the previous sync. Errors are
+// normalised to aging offset 0 so that measurements taken with different aging
+// offsets can be combined (weighted by interval):
+//
+//   drift(aging) = drift(0) - aging * AGING_PPB_PER_LSB
+use crate::ds3231::AGING_PPB_PER_LSB;
+use chrono::NaiveDateTime;
+use core::fmt::{self, Write};
+use heapless::Vec;
+
+// Measurements kept (oldest dropped)
+pub const MAX_MEASUREMENTS: usize = 6;
+// Shorter intervals are dominated by the sync error (a few ms)
+pub const MIN_INTERVAL_S: u32 = 6 * 3600;
+// Total interval needed before the aging offset is changed automatically
+pub const MIN_CALIBRATION_S: u32 = 48 * 3600;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Measurement {
+    pub interval_s: u32,
+    // Positive = RTC fast
+    pub error_ms: i32,
+    // Aging offset during the interval
+    pub aging: i8,
+}
+
+impl Measurement {
+    pub fn drift_ppb(&self) -> i64 {
+        self.error_ms as i64 * 1_000_000 / self.interval_s as i64
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DriftLog {
+    measurements: Vec<Measurement, MAX_MEASUREMENTS>,
+    // Time (UTC) and aging offset of the last sync, None once the RTC has been
+    // set by hand or the aging offset changed since
+    last_sync: Option<(NaiveDateTime, i8)>,
+}
+
+impl DriftLog {
+    pub const fn new() -> Self {
+        DriftLog {
+            measurements: Vec::new(),
+            last_sync: None,
+        }
+    }
+
+    pub fn measurements(&self) -> impl Iterator<Item = &Measurement> {
+        self.measurements.iter()
+    }
+
+    pub fn last_sync(&self) -> Option<(NaiveDateTime, i8)> {
+        self.last_sync
+    }
+
+    // Add a measurement (as restored from storage)
+    pub fn push(&mut self, m: Measurement) {
+        if self.measurements.is_full() {
+            self.measurements.remove(0);
+        }
+        self.measurements.push(m).ok();
+    }
+
+    pub fn set_last_sync(&mut self, last_sync: Option<(NaiveDateTime, i8)>) {
+        self.last_sync = last_sync;
+    }
+
+    // RTC synced at `at` (UTC) with `error_ms` measured just before
+    pub fn synced(&mut self, at: NaiveDateTime, error_ms: Option<i64>, aging: i8) {
+        if let (Some((prev, prev_aging)), Some(error_ms)) = (self.last_sync, error_ms) {
+            let interval_s = (at - prev).num_seconds();
+            if prev_aging == aging && interval_s >= MIN_INTERVAL_S as i64 {
+                self.push(Measurement {
+                    interval_s: interval_s.min(u32::MAX as i64) as u32,
+                    error_ms: error_ms.clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+                    aging,
+                });
+            }
+        }
+        self.last_sync = Some((at, aging));
+    }
+
+    // RTC set by other means or aging offset changed - the next sync only
+    // starts a new interval. Returns false if there was no interval to drop.
+    pub fn restart(&mut self) -> bool {
+        self.last_sync.take().is_some()
+    }
+
+    // Aging offset changed just after a sync (the interval is still valid)
+    pub fn aging_changed(&mut self, aging: i8) {
+        if let Some((_, last_aging)) = self.last_sync.as_mut() {
+            *last_aging = aging;
+        }
+    }
+
+    pub fn total_interval_s(&self) -> u64 {
+        self.measurements.iter().map(|m| m.interval_s as u64).sum()
+    }
+
+    // Drift (ppb, positive = fast) with aging offset 0
+    pub fn base_drift_ppb(&self) -> Option<i64> {
+        let total = self.total_interval_s() as i64;
+        (total > 0).then(|| {
+            let sum: i64 = self
+                .measurements
+                .iter()
+                .map(|m| {
+                    m.error_ms as i64 * 1_000_000
+                        + m.aging as i64 * AGING_PPB_PER_LSB * m.interval_s as i64
+                })
+                .sum();
+            sum / total
+        })
+    }
+
+    pub fn drift_ppb(&self, aging: i8) -> Option<i64> {
+        self.base_drift_ppb()
+            .map(|ppb| ppb - aging as i64 * AGING_PPB_PER_LSB)
+    }
+
+    // Aging offset cancelling the measured drift
+    pub fn suggested_aging(&self) -> Option<i8> {
+        self.base_drift_ppb().map(|ppb| {
+            let lsb = (ppb + ppb.signum() * AGING_PPB_PER_LSB / 2) / AGING_PPB_PER_LSB;
+            lsb.clamp(i8::MIN as i64, i8::MAX as i64) as i8
+        })
+    }
+
+    // New aging offset to apply automatically after a sync (once enough data
+    // has been collected)
+    pub fn calibrate(&self, aging: i8) -> Option<i8> {
+        if self.total_interval_s() < MIN_CALIBRATION_S as u64 {
+            return None;
+        }
+        self.suggested_aging()
+            .filter(|suggested| *suggested != aging)
+    }
+
+    // `drift status` report
+    pub fn write_status<W: Write>(&self, w: &mut W, aging: Option<i8>) -> fmt::Result {
+        match aging {
+            Some(aging) => write!(w, "Aging: {}", aging)?,
+            None => write!(w, "Aging: Not Supported")?,
+        }
+        match self.drift_ppb(aging.unwrap_or(0)) {
+            Some(ppb) => write!(
+                w,
+                "\r\nDrift: {:+.2}ppm ({} syncs over {:.1}h)",
+                ppb as f32 / 1000.0,
+                self.measurements.len(),
+                self.total_interval_s() as f32 / 3600.0
+            )?,
+            None => write!(
+                w,
+                "\r\nDrift: Unknown (sync at least {}h apart)",
+                MIN_INTERVAL_S / 3600
+            )?,
+        }
+        if let (Some(suggested), Some(_)) = (self.suggested_aging(), aging) {
+            write!(w, "\r\nSuggested aging: {}", suggested)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeDelta};
+
+    fn day(d: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 3, d)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn estimate_and_calibrate() {
+        let mut log = DriftLog::new();
+        assert_eq!(log.drift_ppb(0), None);
+        // First sync only starts an interval
+        log.synced(day(1), Some(1500), 0);
+        assert_eq!(log.drift_ppb(0), None);
+        // 5ppm fast - 432ms a day
+        log.synced(day(2), Some(432), 0);
+        assert_eq!(log.drift_ppb(0), Some(5_000));
+        assert_eq!(log.suggested_aging(), Some(50));
+        // Not enough data to change the aging offset yet
+        assert_eq!(log.calibrate(0), None);
+        // Too short an interval is ignored
+        log.synced(day(2) + TimeDelta::hours(1), Some(5), 0);
+        assert_eq!(log.measurements().count(), 1);
+        log.synced(day(4) + TimeDelta::hours(1), Some(864), 0);
+        assert_eq!(log.drift_ppb(0), Some(5_000));
+        assert_eq!(log.calibrate(0), Some(50));
+        assert_eq!(log.calibrate(50), None);
+        // Out of range drift is clamped
+        let mut fast = DriftLog::new();
+        fast.synced(day(1), None, 0);
+        fast.synced(day(2), Some(1728), 0);
+        assert_eq!(fast.suggested_aging(), Some(127));
+
+        // Measurements with a different aging offset are combined
+        let mut log = DriftLog::new();
+        log.synced(day(1), None, 0);
+        log.synced(day(2), Some(864), 0);
+        assert_eq!(log.drift_ppb(0), Some(10_000));
+        log.aging_changed(50);
+        // 5ppm fast with aging 50
+        log.synced(day(3), Some(432), 50);
+        assert_eq!(log.drift_ppb(0), Some(10_000));
+        assert_eq!(log.drift_ppb(50), Some(5_000));
+        assert_eq!(log.calibrate(50), Some(100));
+        assert_eq!(log.calibrate(100), None);
+    }
+
+    #[test]
+    fn restart_and_rollover() {
+        let mut log = DriftLog::new();
+        assert!(!log.restart());
+        log.synced(day(1), Some(0), 0);
+        assert!(log.restart());
+        assert!(!log.restart());
+        log.synced(day(2), Some(864), 0);
+        assert_eq!(log.measurements().count(), 0);
+        // Aging changed without a sync
+        log.synced(day(3), Some(-864), 10);
+        assert_eq!(log.measurements().count(), 0);
+        for d in 4..=12 {
+            log.synced(day(d), Some(-864), 10);
+        }
+        assert_eq!(log.measurements().count(), MAX_MEASUREMENTS);
+        assert_eq!(log.drift_ppb(10), Some(-10_000));
+        assert_eq!(log.suggested_aging(), Some(-90));
+
+        let mut s: heapless::String<128> = heapless::String::new();
+        log.write_status(&mut s, Some(10)).unwrap();
+        assert_eq!(
+            s,
+            "Aging: 10\r\nDrift: -10.00ppm (6 syncs over 144.0h)\r\nSuggested aging: -90"
+        );
+        s.clear();
+        DriftLog::new().write_status(&mut s, None).unwrap();
+        assert_eq!(
+            s,
+            "Aging: Not Supported\r\nDrift: Unknown (sync at least 6h apart)"
+        );
+    }
+}
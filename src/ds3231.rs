@@ -0,0 +1,144 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+mod driver;
+pub mod emulator;
+
+pub use driver::{Ds3231, Error};
+
+// DS3231 register map
+pub const ADDRESS: u8 = 0x68;
+pub const SECONDS: u8 = 0x00;
+pub const MINUTES: u8 = 0x01;
+pub const HOURS: u8 = 0x02;
+pub const DAY: u8 = 0x03;
+pub const DATE: u8 = 0x04;
+pub const MONTH: u8 = 0x05;
+pub const YEAR: u8 = 0x06;
+pub const ALARM1_SECONDS: u8 = 0x07;
+pub const ALARM1_MINUTES: u8 = 0x08;
+pub const ALARM1_HOURS: u8 = 0x09;
+pub const ALARM1_DAY_DATE: u8 = 0x0A;
+pub const ALARM2_MINUTES: u8 = 0x0B;
+pub const ALARM2_HOURS: u8 = 0x0C;
+pub const ALARM2_DAY_DATE: u8 = 0x0D;
+pub const CONTROL: u8 = 0x0E;
+pub const STATUS: u8 = 0x0F;
+pub const AGING: u8 = 0x10;
+pub const TEMP_MSB: u8 = 0x11;
+pub const TEMP_LSB: u8 = 0x12;
+pub const REGISTER_COUNT: usize = 0x13;
+
+// Hours register
+pub const HOURS_12H: u8 = 0b0100_0000;
+pub const HOURS_PM: u8 = 0b0010_0000;
+
+// Month register
+pub const MONTH_CENTURY: u8 = 0b1000_0000;
+
+// Alarm registers
+pub const ALARM_MASK: u8 = 0b1000_0000;
+pub const ALARM_DAY: u8 = 0b0100_0000;
+
+// Control register
+pub const CONTROL_EOSC: u8 = 0b1000_0000;
+pub const CONTROL_BBSQW: u8 = 0b0100_0000;
+pub const CONTROL_CONV: u8 = 0b0010_0000;
+pub const CONTROL_RS2: u8 = 0b0001_0000;
+pub const CONTROL_RS1: u8 = 0b0000_1000;
+pub const CONTROL_INTCN: u8 = 0b0000_0100;
+pub const CONTROL_A2IE: u8 = 0b0000_0010;
+pub const CONTROL_A1IE: u8 = 0b0000_0001;
+
+// Status register
+pub const STATUS_OSF: u8 = 0b1000_0000;
+pub const STATUS_EN32KHZ: u8 = 0b0000_1000;
+pub const STATUS_BSY: u8 = 0b0000_0100;
+pub const STATUS_A2F: u8 = 0b0000_0010;
+pub const STATUS_A1F: u8 = 0b0000_0001;
+
+// Power-on register values
+pub const CONTROL_DEFAULT: u8 = CONTROL_RS2 | CONTROL_RS1 | CONTROL_INTCN;
+pub const STATUS_DEFAULT: u8 = STATUS_OSF | STATUS_EN32KHZ;
+
+// Approximate aging offset sensitivity (0.1ppm per LSB at 25°C). Positive
+// values slow the oscillator.
+pub const AGING_PPB_PER_LSB: i64 = 100;
+
+pub fn bcd_to_bin(bcd: u8) -> u8 {
+    (bcd >> 4) * 10 + (bcd & 0x0f)
+}
+
+pub fn bin_to_bcd(bin: u8) -> u8 {
+    ((bin / 10) << 4) | (bin % 10)
+}
+
+// Decode hours register (12h or 24h mode) to 0-23
+pub fn decode_hours(reg: u8) -> u8 {
+    if reg & HOURS_12H != 0 {
+        let h = bcd_to_bin(reg & 0x1f) % 12;
+        if reg & HOURS_PM != 0 {
+            h + 12
+        } else {
+            h
+        }
+    } else {
+        bcd_to_bin(reg & 0x3f)
+    }
+}
+
+// Encode 0-23 hours, keeping the 12h/24h mode of `mode`
+pub fn encode_hours(hours: u8, mode: u8) -> u8 {
+    if mode & HOURS_12H != 0 {
+        let h12 = match hours % 12 {
+            0 => 12,
+            h => h,
+        };
+        let pm = if hours >= 12 { HOURS_PM } else { 0 };
+        HOURS_12H | pm | bin_to_bcd(h12)
+    } else {
+        bin_to_bcd(hours)
+    }
+}
+
+// Decode time registers (SECONDS..=YEAR)
+pub fn decode_datetime(regs: &[u8]) -> Option<NaiveDateTime> {
+    let time = NaiveTime::from_hms_opt(
+        decode_hours(regs[2]) as u32,
+        bcd_to_bin(regs[1] & 0x7f) as u32,
+        bcd_to_bin(regs[0] & 0x7f) as u32,
+    )?;
+    let century = if regs[5] & MONTH_CENTURY != 0 { 100 } else { 0 };
+    let date = NaiveDate::from_ymd_opt(
+        2000 + century + bcd_to_bin(regs[6]) as i32,
+        bcd_to_bin(regs[5] & 0x1f) as u32,
+        bcd_to_bin(regs[4] & 0x3f) as u32,
+    )?;
+    Some(NaiveDateTime::new(date, time))
+}
+
+// Encode time registers (SECONDS..=YEAR) in 24h mode. Day of week is 1 (Monday) - 7.
+// The year must be in `rtc::YEARS`.
+pub fn encode_datetime(dt: &NaiveDateTime) -> [u8; 7] {
+    let year = dt.year() - 2000;
+    let century = if year >= 100 { MONTH_CENTURY } else { 0 };
+    [
+        bin_to_bcd(dt.second() as u8),
+        bin_to_bcd(dt.minute() as u8),
+        bin_to_bcd(dt.hour() as u8),
+        dt.weekday().number_from_monday() as u8,
+        bin_to_bcd(dt.day() as u8),
+        century | bin_to_bcd(dt.month() as u8),
+        bin_to_bcd((year % 100) as u8),
+    ]
+}
+
+// Temperature registers are a 10-bit two's complement value in 0.25°C steps
+pub fn decode_temperature(msb: u8, lsb: u8) -> f32 {
+    let quarters = ((msb as i8 as i16) << 2) | (lsb >> 6) as i16;
+    quarters as f32 / 4.0
+}
+
+pub fn encode_temperature(temp: f32) -> (u8, u8) {
+    let quarters = (temp * 4.0) as i16;
+    ((quarters >> 2) as u8, ((quarters & 0x03) as u8) << 6)
+}
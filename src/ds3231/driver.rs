@@ -0,0 +1,562 @@
+use super::*;
+use crate::alarm::AlarmMatch;
+use crate::rtc::{RtcBackend, YEARS};
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Timelike};
+use embedded_hal_async::i2c::I2c;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error<E> {
+    Comm(E),
+    // Registers do not hold a valid date/time
+    InvalidData,
+    // Date/time the registers can't hold (outside `rtc::YEARS`)
+    InvalidInput,
+}
+
+// Minimal DS3231 driver over the embedded-hal-async I2c trait
+pub struct Ds3231<I2C> {
+    i2c: I2C,
+}
+
+impl<I2C, E> Ds3231<I2C>
+where
+    I2C: I2c<Error = E>,
+{
+    pub fn new(i2c: I2C) -> Self {
+        Ds3231 { i2c }
+    }
+
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    pub async fn read_registers(&mut self, reg: u8, buf: &mut [u8]) -> Result<(), Error<E>> {
+        self.i2c
+            .write_read(ADDRESS, &[reg], buf)
+            .await
+            .map_err(Error::Comm)
+    }
+
+    pub async fn read_register(&mut self, reg: u8) -> Result<u8, Error<E>> {
+        let mut buf = [0; 1];
+        self.read_registers(reg, &mut buf).await?;
+        Ok(buf[0])
+    }
+
+    pub async fn write_register(&mut self, reg: u8, value: u8) -> Result<(), Error<E>> {
+        self.i2c
+            .write(ADDRESS, &[reg, value])
+            .await
+            .map_err(Error::Comm)
+    }
+
+    // Read-modify-write: clear `clear` bits then set `set` bits
+    pub async fn update_register(&mut self, reg: u8, clear: u8, set: u8) -> Result<(), Error<E>> {
+        let value = self.read_register(reg).await?;
+        self.write_register(reg, (value & !clear) | set).await
+    }
+
+    pub async fn datetime(&mut self) -> Result<NaiveDateTime, Error<E>> {
+        let mut buf = [0; 7];
+        self.read_registers(SECONDS, &mut buf).await?;
+        decode_datetime(&buf).ok_or(Error::InvalidData)
+    }
+
+    pub async fn set_datetime(&mut self, dt: &NaiveDateTime) -> Result<(), Error<E>> {
+        if !YEARS.contains(&dt.year()) {
+            return Err(Error::InvalidInput);
+        }
+        let mut buf = [0; 8];
+        buf[0] = SECONDS;
+        buf[1..].copy_from_slice(&encode_datetime(dt));
+        self.i2c.write(ADDRESS, &buf).await.map_err(Error::Comm)
+    }
+
+    // Start the oscillator (clear EOSC)
+    pub async fn enable(&mut self) -> Result<(), Error<E>> {
+        self.update_register(CONTROL, CONTROL_EOSC, 0).await
+    }
+
+    pub async fn use_int_sqw_output_as_interrupt(&mut self) -> Result<(), Error<E>> {
+        self.update_register(CONTROL, 0, CONTROL_INTCN).await
+    }
+
+    // 1Hz square wave on INT/SQW (alarm flags are still set)
+    pub async fn use_int_sqw_output_as_square_wave(&mut self) -> Result<(), Error<E>> {
+        self.update_register(CONTROL, CONTROL_INTCN | CONTROL_RS2 | CONTROL_RS1, 0)
+            .await
+    }
+
+    pub async fn enable_alarm1_interrupts(&mut self) -> Result<(), Error<E>> {
+        self.update_register(CONTROL, 0, CONTROL_A1IE).await
+    }
+
+    pub async fn disable_alarm1_interrupts(&mut self) -> Result<(), Error<E>> {
+        self.update_register(CONTROL, CONTROL_A1IE, 0).await
+    }
+
+    pub async fn enable_alarm2_interrupts(&mut self) -> Result<(), Error<E>> {
+        self.update_register(CONTROL, 0, CONTROL_A2IE).await
+    }
+
+    pub async fn disable_alarm2_interrupts(&mut self) -> Result<(), Error<E>> {
+        self.update_register(CONTROL, CONTROL_A2IE, 0).await
+    }
+
+    pub async fn set_alarm1_match(&mut self, m: AlarmMatch) -> Result<(), Error<E>> {
+        let hms = |t: &NaiveTime| {
+            [
+                bin_to_bcd(t.second() as u8),
+                bin_to_bcd(t.minute() as u8),
+                bin_to_bcd(t.hour() as u8),
+            ]
+        };
+        let (s, m, h, day_date) = match m {
+            AlarmMatch::MinuteSecond(m, s) => {
+                (bin_to_bcd(s), bin_to_bcd(m), ALARM_MASK, ALARM_MASK)
+            }
+            AlarmMatch::Time(t) => {
+                let [s, m, h] = hms(&t);
+                (s, m, h, ALARM_MASK)
+            }
+            AlarmMatch::DayTime(d, t) => {
+                let [s, m, h] = hms(&t);
+                (s, m, h, ALARM_DAY | d)
+            }
+            AlarmMatch::DateTime(d, t) => {
+                let [s, m, h] = hms(&t);
+                (s, m, h, bin_to_bcd(d))
+            }
+        };
+        self.i2c
+            .write(ADDRESS, &[ALARM1_SECONDS, s, m, h, day_date])
+            .await
+            .map_err(Error::Comm)
+    }
+
+    // Alarm 1 match mode (None if not a supported mode)
+    pub async fn alarm1_match(&mut self) -> Result<Option<AlarmMatch>, Error<E>> {
+        let mut buf = [0; 4];
+        self.read_registers(ALARM1_SECONDS, &mut buf).await?;
+        let masks = buf.map(|r| r & ALARM_MASK != 0);
+        let time = || {
+            NaiveTime::from_hms_opt(
+                decode_hours(buf[2] & 0x7f) as u32,
+                bcd_to_bin(buf[1] & 0x7f) as u32,
+                bcd_to_bin(buf[0] & 0x7f) as u32,
+            )
+        };
+        Ok(match masks {
+            [false, false, true, true] => Some(AlarmMatch::MinuteSecond(
+                bcd_to_bin(buf[1] & 0x7f),
+                bcd_to_bin(buf[0] & 0x7f),
+            )),
+            [false, false, false, true] => time().map(AlarmMatch::Time),
+            [false, false, false, false] if buf[3] & ALARM_DAY != 0 => {
+                time().map(|t| AlarmMatch::DayTime(buf[3] & 0x0f, t))
+            }
+            [false, false, false, false] => {
+                time().map(|t| AlarmMatch::DateTime(bcd_to_bin(buf[3] & 0x3f), t))
+            }
+            _ => None,
+        })
+    }
+
+    // Daily alarm when hours and minutes match
+    pub async fn set_alarm2_hm(&mut self, t: NaiveTime) -> Result<(), Error<E>> {
+        self.i2c
+            .write(
+                ADDRESS,
+                &[
+                    ALARM2_MINUTES,
+                    bin_to_bcd(t.minute() as u8),
+                    bin_to_bcd(t.hour() as u8),
+                    ALARM_MASK,
+                ],
+            )
+            .await
+            .map_err(Error::Comm)
+    }
+
+    // Alarm 2 time if programmed as a daily (hours, minutes) match
+    pub async fn alarm2_hm(&mut self) -> Result<Option<NaiveTime>, Error<E>> {
+        let mut buf = [0; 3];
+        self.read_registers(ALARM2_MINUTES, &mut buf).await?;
+        if buf[..2].iter().any(|r| r & ALARM_MASK != 0) || buf[2] & ALARM_MASK == 0 {
+            return Ok(None);
+        }
+        Ok(NaiveTime::from_hms_opt(
+            decode_hours(buf[1]) as u32,
+            bcd_to_bin(buf[0]) as u32,
+            0,
+        ))
+    }
+
+    pub async fn has_alarm1_matched(&mut self) -> Result<bool, Error<E>> {
+        Ok(self.read_register(STATUS).await? & STATUS_A1F != 0)
+    }
+
+    pub async fn clear_alarm1_matched_flag(&mut self) -> Result<(), Error<E>> {
+        self.update_register(STATUS, STATUS_A1F, 0).await
+    }
+
+    pub async fn has_alarm2_matched(&mut self) -> Result<bool, Error<E>> {
+        Ok(self.read_register(STATUS).await? & STATUS_A2F != 0)
+    }
+
+    pub async fn clear_alarm2_matched_flag(&mut self) -> Result<(), Error<E>> {
+        self.update_register(STATUS, STATUS_A2F, 0).await
+    }
+
+    pub async fn has_been_stopped(&mut self) -> Result<bool, Error<E>> {
+        Ok(self.read_register(STATUS).await? & STATUS_OSF != 0)
+    }
+
+    pub async fn clear_has_been_stopped_flag(&mut self) -> Result<(), Error<E>> {
+        self.update_register(STATUS, STATUS_OSF, 0).await
+    }
+
+    pub async fn aging_offset(&mut self) -> Result<i8, Error<E>> {
+        Ok(self.read_register(AGING).await? as i8)
+    }
+
+    // The new offset takes effect at the next temperature conversion, so one
+    // is started now
+    pub async fn set_aging_offset(&mut self, offset: i8) -> Result<(), Error<E>> {
+        self.write_register(AGING, offset as u8).await?;
+        self.update_register(CONTROL, 0, CONTROL_CONV).await
+    }
+
+    // Force a temperature conversion (and TCXO update) rather than waiting up
+    // to 64s for the next automatic one. A conversion already running gives a
+    // result just as fresh, so a new one is only started when BSY is clear.
+    pub async fn start_conversion(&mut self) -> Result<(), Error<E>> {
+        if self.read_register(STATUS).await? & STATUS_BSY == 0 {
+            self.update_register(CONTROL, 0, CONTROL_CONV).await?;
+        }
+        Ok(())
+    }
+
+    // CONV stays set until a forced conversion completes, BSY while any
+    // conversion is running
+    pub async fn is_converting(&mut self) -> Result<bool, Error<E>> {
+        let control = self.read_register(CONTROL).await?;
+        let status = self.read_register(STATUS).await?;
+        Ok(control & CONTROL_CONV != 0 || status & STATUS_BSY != 0)
+    }
+
+    pub async fn temperature(&mut self) -> Result<f32, Error<E>> {
+        let mut buf = [0; 2];
+        self.read_registers(TEMP_MSB, &mut buf).await?;
+        Ok(decode_temperature(buf[0], buf[1]))
+    }
+}
+
+impl<I2C, E> RtcBackend for Ds3231<I2C>
+where
+    I2C: I2c<Error = E>,
+    E: core::fmt::Debug,
+{
+    type Error = Error<E>;
+
+    async fn init(&mut self) -> Result<(), Self::Error> {
+        self.enable().await?;
+        self.clear_alarm1_matched_flag().await?;
+        self.clear_alarm2_matched_flag().await?;
+        self.use_int_sqw_output_as_interrupt().await
+    }
+
+    async fn datetime(&mut self) -> Result<NaiveDateTime, Self::Error> {
+        Ds3231::datetime(self).await
+    }
+
+    async fn set_datetime(&mut self, dt: &NaiveDateTime) -> Result<(), Self::Error> {
+        Ds3231::set_datetime(self, dt).await
+    }
+
+    // Alarms are only considered set while their interrupt is enabled
+    async fn alarm1(&mut self) -> Result<Option<AlarmMatch>, Self::Error> {
+        if self.read_register(CONTROL).await? & CONTROL_A1IE == 0 {
+            return Ok(None);
+        }
+        self.alarm1_match().await
+    }
+
+    async fn set_alarm1(&mut self, m: AlarmMatch) -> Result<(), Self::Error> {
+        self.clear_alarm1_matched_flag().await?;
+        self.set_alarm1_match(m).await?;
+        self.enable_alarm1_interrupts().await
+    }
+
+    async fn clear_alarm1(&mut self) -> Result<(), Self::Error> {
+        self.disable_alarm1_interrupts().await?;
+        self.clear_alarm1_matched_flag().await
+    }
+
+    async fn alarm1_matched(&mut self) -> Result<bool, Self::Error> {
+        self.has_alarm1_matched().await
+    }
+
+    async fn clear_alarm1_matched(&mut self) -> Result<(), Self::Error> {
+        self.clear_alarm1_matched_flag().await
+    }
+
+    async fn alarm2(&mut self) -> Result<Option<NaiveTime>, Self::Error> {
+        if self.read_register(CONTROL).await? & CONTROL_A2IE == 0 {
+            return Ok(None);
+        }
+        self.alarm2_hm().await
+    }
+
+    async fn set_alarm2(&mut self, t: NaiveTime) -> Result<(), Self::Error> {
+        self.clear_alarm2_matched_flag().await?;
+        self.set_alarm2_hm(t).await?;
+        self.enable_alarm2_interrupts().await
+    }
+
+    async fn clear_alarm2(&mut self) -> Result<(), Self::Error> {
+        self.disable_alarm2_interrupts().await?;
+        self.clear_alarm2_matched_flag().await
+    }
+
+    async fn alarm2_matched(&mut self) -> Result<bool, Self::Error> {
+        self.has_alarm2_matched().await
+    }
+
+    async fn clear_alarm2_matched(&mut self) -> Result<(), Self::Error> {
+        self.clear_alarm2_matched_flag().await
+    }
+
+    async fn alarms_matched(&mut self) -> Result<(bool, bool), Self::Error> {
+        let status = self.read_register(STATUS).await?;
+        Ok((status & STATUS_A1F != 0, status & STATUS_A2F != 0))
+    }
+
+    async fn temperature(&mut self) -> Result<Option<f32>, Self::Error> {
+        Ds3231::temperature(self).await.map(Some)
+    }
+
+    async fn start_temperature_conversion(&mut self) -> Result<bool, Self::Error> {
+        self.start_conversion().await.map(|_| true)
+    }
+
+    async fn temperature_converting(&mut self) -> Result<bool, Self::Error> {
+        self.is_converting().await
+    }
+
+    async fn aging(&mut self) -> Result<Option<i8>, Self::Error> {
+        self.aging_offset().await.map(Some)
+    }
+
+    async fn set_aging(&mut self, offset: i8) -> Result<(), Self::Error> {
+        self.set_aging_offset(offset).await
+    }
+
+    async fn enable_square_wave(&mut self) -> Result<bool, Self::Error> {
+        self.use_int_sqw_output_as_square_wave().await.map(|_| true)
+    }
+
+    async fn oscillator_stopped(&mut self) -> Result<bool, Self::Error> {
+        self.has_been_stopped().await
+    }
+
+    async fn clear_oscillator_stopped(&mut self) -> Result<(), Self::Error> {
+        self.clear_has_been_stopped_flag().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_on;
+    use crate::ds3231::emulator::Ds3231Emulator;
+    use chrono::NaiveDate;
+
+    fn dt(h: u32, m: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 6, 30)
+            .unwrap()
+            .and_hms_opt(h, m, s)
+            .unwrap()
+    }
+
+    #[test]
+    fn backend_against_emulator() {
+        block_on(async {
+            let mut rtc = Ds3231::new(Ds3231Emulator::new());
+            rtc.init().await.unwrap();
+            assert_eq!(rtc.oscillator_stopped().await, Ok(true));
+            RtcBackend::set_datetime(&mut rtc, &dt(6, 59, 59))
+                .await
+                .unwrap();
+            rtc.clear_oscillator_stopped().await.unwrap();
+            let t = NaiveTime::from_hms_opt(7, 0, 0).unwrap();
+            assert_eq!(rtc.alarm1().await, Ok(None));
+            rtc.set_alarm1(AlarmMatch::Time(t)).await.unwrap();
+            assert_eq!(rtc.alarm1().await, Ok(Some(AlarmMatch::Time(t))));
+
+            let mut emulator = rtc.release();
+            emulator.advance_ms(1000);
+            assert!(emulator.int_sqw_low());
+
+            let mut rtc = Ds3231::new(emulator);
+            assert_eq!(RtcBackend::datetime(&mut rtc).await, Ok(dt(7, 0, 0)));
+            assert_eq!(rtc.alarm1_matched().await, Ok(true));
+            rtc.clear_alarm1_matched().await.unwrap();
+            assert_eq!(rtc.alarm1_matched().await, Ok(false));
+            assert_eq!(rtc.oscillator_stopped().await, Ok(false));
+            assert_eq!(RtcBackend::temperature(&mut rtc).await, Ok(Some(25.0)));
+        });
+    }
+
+    #[test]
+    fn square_wave_ticks() {
+        block_on(async {
+            let mut rtc = Ds3231::new(Ds3231Emulator::new());
+            rtc.init().await.unwrap();
+            RtcBackend::set_datetime(&mut rtc, &dt(6, 59, 58))
+                .await
+                .unwrap();
+            rtc.set_alarm1(AlarmMatch::Time(NaiveTime::from_hms_opt(7, 0, 0).unwrap()))
+                .await
+                .unwrap();
+            assert_eq!(rtc.enable_square_wave().await, Ok(true));
+            // Alarm still set (polled)
+            assert!(rtc.alarm1().await.unwrap().is_some());
+
+            let mut emulator = rtc.release();
+            // Low for the first half of each second
+            assert!(emulator.int_sqw_low());
+            emulator.advance_ms(600);
+            assert!(!emulator.int_sqw_low());
+            emulator.advance_ms(400);
+            assert!(emulator.int_sqw_low());
+            emulator.advance_ms(1000);
+            let mut rtc = Ds3231::new(emulator);
+            assert_eq!(RtcBackend::datetime(&mut rtc).await, Ok(dt(7, 0, 0)));
+            assert_eq!(rtc.alarm1_matched().await, Ok(true));
+        });
+    }
+
+    #[test]
+    fn forced_temperature_conversion() {
+        block_on(async {
+            let mut emulator = Ds3231Emulator::new();
+            emulator.set_temperature(31.75);
+            let mut rtc = Ds3231::new(emulator);
+            assert_eq!(RtcBackend::temperature(&mut rtc).await, Ok(Some(25.0)));
+            assert_eq!(rtc.start_temperature_conversion().await, Ok(true));
+            assert_eq!(rtc.temperature_converting().await, Ok(true));
+            // Not restarted while busy
+            rtc.start_temperature_conversion().await.unwrap();
+
+            let mut emulator = rtc.release();
+            emulator.advance_ms(200);
+            let mut rtc = Ds3231::new(emulator);
+            assert_eq!(rtc.temperature_converting().await, Ok(false));
+            assert_eq!(RtcBackend::temperature(&mut rtc).await, Ok(Some(31.75)));
+        });
+    }
+
+    #[test]
+    fn aging_offset() {
+        block_on(async {
+            let mut rtc = Ds3231::new(Ds3231Emulator::new());
+            assert_eq!(rtc.aging().await, Ok(Some(0)));
+            rtc.set_aging(-20).await.unwrap();
+            assert_eq!(rtc.aging().await, Ok(Some(-20)));
+            let emulator = rtc.release();
+            assert_eq!(emulator.register(AGING), 0xec);
+            assert_eq!(emulator.effective_drift_ppb(), 2_000);
+        });
+    }
+
+    #[test]
+    fn alarm1_match_modes() {
+        block_on(async {
+            let mut rtc = Ds3231::new(Ds3231Emulator::new());
+            let t = NaiveTime::from_hms_opt(9, 30, 15).unwrap();
+            for m in [
+                AlarmMatch::MinuteSecond(15, 0),
+                AlarmMatch::Time(t),
+                AlarmMatch::DayTime(6, t),
+                AlarmMatch::DateTime(24, t),
+            ] {
+                rtc.set_alarm1(m).await.unwrap();
+                assert_eq!(rtc.alarm1().await, Ok(Some(m)));
+            }
+            rtc.clear_alarm1().await.unwrap();
+            assert_eq!(rtc.alarm1().await, Ok(None));
+
+            // Day of week match fires on Saturday only (2026-03-07)
+            let sat = NaiveDate::from_ymd_opt(2026, 3, 7).unwrap();
+            RtcBackend::set_datetime(&mut rtc, &sat.and_hms_opt(9, 30, 14).unwrap())
+                .await
+                .unwrap();
+            rtc.set_alarm1(AlarmMatch::DayTime(6, t)).await.unwrap();
+            let mut emulator = rtc.release();
+            emulator.advance_ms(1000);
+            assert!(emulator.int_sqw_low());
+        });
+    }
+
+    #[test]
+    fn alarm2_set_and_clear() {
+        block_on(async {
+            let mut rtc = Ds3231::new(Ds3231Emulator::new());
+            rtc.init().await.unwrap();
+            RtcBackend::set_datetime(&mut rtc, &dt(21, 29, 59))
+                .await
+                .unwrap();
+            assert_eq!(rtc.alarm2().await, Ok(None));
+            let t = NaiveTime::from_hms_opt(21, 30, 0).unwrap();
+            rtc.set_alarm2(t).await.unwrap();
+            assert_eq!(rtc.alarm2().await, Ok(Some(t)));
+
+            let mut emulator = rtc.release();
+            emulator.advance_ms(1000);
+            assert!(emulator.int_sqw_low());
+
+            let mut rtc = Ds3231::new(emulator);
+            assert_eq!(rtc.alarm1_matched().await, Ok(false));
+            assert_eq!(rtc.alarm2_matched().await, Ok(true));
+            assert_eq!(rtc.alarms_matched().await, Ok((false, true)));
+            rtc.clear_alarm2().await.unwrap();
+            assert_eq!(rtc.alarm2().await, Ok(None));
+            assert!(!rtc.release().int_sqw_low());
+        });
+    }
+
+    #[test]
+    fn comm_errors_are_reported() {
+        block_on(async {
+            let mut emulator = Ds3231Emulator::new();
+            emulator.fail_transactions(1);
+            let mut rtc = Ds3231::new(emulator);
+            assert!(matches!(rtc.init().await, Err(Error::Comm(_))));
+            assert!(rtc.init().await.is_ok());
+        });
+    }
+
+    #[test]
+    fn year_range() {
+        block_on(async {
+            let mut rtc = Ds3231::new(Ds3231Emulator::new());
+            for y in [1999, 2200] {
+                let dt = NaiveDate::from_ymd_opt(y, 12, 31)
+                    .unwrap()
+                    .and_hms_opt(23, 59, 59)
+                    .unwrap();
+                assert_eq!(
+                    RtcBackend::set_datetime(&mut rtc, &dt).await,
+                    Err(Error::InvalidInput)
+                );
+            }
+            let dt = NaiveDate::from_ymd_opt(2199, 12, 31)
+                .unwrap()
+                .and_hms_opt(23, 59, 59)
+                .unwrap();
+            RtcBackend::set_datetime(&mut rtc, &dt).await.unwrap();
+            assert_eq!(RtcBackend::datetime(&mut rtc).await, Ok(dt));
+        });
+    }
+}
@@ -0,0 +1,456 @@
+use super::*;
+use chrono::{Datelike, NaiveDateTime, TimeDelta, Timelike};
+use embedded_hal::i2c::{ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation};
+
+const NS_PER_SEC: i64 = 1_000_000_000;
+// The DS3231 runs a temperature conversion (and TCXO adjustment) every 64s
+const TEMP_CONVERSION_INTERVAL: u32 = 64;
+
+// Register-level DS3231 emulator
+//
+// Implements the embedded-hal I2c traits (address 0x68, auto-incrementing
+// register pointer) over the full register map. Time is advanced from a
+// virtual clock with `advance_ms`, which ticks the time registers, matches
+// alarms, runs temperature conversions and drives the INT/SQW output.
+pub struct Ds3231Emulator {
+    regs: [u8; REGISTER_COUNT],
+    pointer: u8,
+    // Position within the current second (ns)
+    sub_second: i64,
+    // Crystal error before aging offset is applied (ppb, +ve runs fast)
+    drift_ppb: i64,
+    temperature: f32,
+    conversion_countdown: u32,
+    running: bool,
+    fail_transactions: u32,
+}
+
+impl Default for Ds3231Emulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ds3231Emulator {
+    // Power-on state: 00:00:00 01/01/2000, OSF set, INTCN set, 25°C
+    pub fn new() -> Self {
+        let mut emulator = Ds3231Emulator {
+            regs: [0; REGISTER_COUNT],
+            pointer: 0,
+            sub_second: 0,
+            drift_ppb: 0,
+            temperature: 25.0,
+            conversion_countdown: TEMP_CONVERSION_INTERVAL,
+            running: true,
+            fail_transactions: 0,
+        };
+        emulator.reset_registers();
+        emulator
+    }
+
+    fn reset_registers(&mut self) {
+        self.regs = [0; REGISTER_COUNT];
+        self.regs[DAY as usize] = 1;
+        self.regs[DATE as usize] = 1;
+        self.regs[MONTH as usize] = 1;
+        self.regs[CONTROL as usize] = CONTROL_DEFAULT;
+        self.regs[STATUS as usize] = STATUS_DEFAULT;
+        self.convert_temperature();
+        self.pointer = 0;
+        self.sub_second = 0;
+    }
+
+    // Simulate loss of both VCC and VBAT - registers return to power-on state
+    pub fn power_loss(&mut self) {
+        self.reset_registers();
+    }
+
+    // Simulate the oscillator stopping (e.g. flat battery while VCC off).
+    // Time stops and OSF is set until `restart_oscillator` is called.
+    pub fn stop_oscillator(&mut self) {
+        self.running = false;
+        self.regs[STATUS as usize] |= STATUS_OSF;
+    }
+
+    pub fn restart_oscillator(&mut self) {
+        self.running = true;
+    }
+
+    // Fail the next `n` I2C transactions with an address NACK
+    pub fn fail_transactions(&mut self, n: u32) {
+        self.fail_transactions = n;
+    }
+
+    pub fn set_drift_ppb(&mut self, ppb: i64) {
+        self.drift_ppb = ppb;
+    }
+
+    // Die temperature reported at the next conversion
+    pub fn set_temperature(&mut self, temp: f32) {
+        self.temperature = temp;
+    }
+
+    pub fn registers(&self) -> &[u8; REGISTER_COUNT] {
+        &self.regs
+    }
+
+    pub fn register(&self, reg: u8) -> u8 {
+        self.regs[reg as usize]
+    }
+
+    pub fn set_register(&mut self, reg: u8, value: u8) {
+        self.write_register(reg, value);
+    }
+
+    pub fn datetime(&self) -> Option<NaiveDateTime> {
+        decode_datetime(&self.regs[..7])
+    }
+
+    pub fn set_datetime(&mut self, dt: &NaiveDateTime) {
+        self.regs[..7].copy_from_slice(&encode_datetime(dt));
+        self.sub_second = 0;
+    }
+
+    // Position within the current second (ms)
+    pub fn sub_second_ms(&self) -> u32 {
+        (self.sub_second / 1_000_000) as u32
+    }
+
+    // Effective oscillator error including the aging offset (ppb)
+    pub fn effective_drift_ppb(&self) -> i64 {
+        self.drift_ppb - self.regs[AGING as usize] as i8 as i64 * AGING_PPB_PER_LSB
+    }
+
+    // Advance the virtual clock by `ms` of real time
+    pub fn advance_ms(&mut self, ms: u32) {
+        // Any pending CONV request completes within the advance
+        if self.regs[CONTROL as usize] & CONTROL_CONV != 0 {
+            self.convert_temperature();
+        }
+        if !self.running {
+            return;
+        }
+        let ns = ms as i64 * 1_000_000;
+        self.sub_second += ns + ns * self.effective_drift_ppb() / NS_PER_SEC;
+        while self.sub_second >= NS_PER_SEC {
+            self.sub_second -= NS_PER_SEC;
+            self.tick();
+        }
+    }
+
+    // State of the (active low, open drain) INT/SQW output - true if pulled low
+    pub fn int_sqw_low(&self) -> bool {
+        let control = self.regs[CONTROL as usize];
+        let status = self.regs[STATUS as usize];
+        if control & CONTROL_INTCN != 0 {
+            (control & CONTROL_A1IE != 0 && status & STATUS_A1F != 0)
+                || (control & CONTROL_A2IE != 0 && status & STATUS_A2F != 0)
+        } else if !self.running {
+            false
+        } else {
+            // Square wave (falling edge aligned with the seconds rollover)
+            let hz: i64 = match control & (CONTROL_RS2 | CONTROL_RS1) {
+                0 => 1,
+                CONTROL_RS1 => 1024,
+                CONTROL_RS2 => 4096,
+                _ => 8192,
+            };
+            let period = NS_PER_SEC / hz;
+            self.sub_second % period < period / 2
+        }
+    }
+
+    fn tick(&mut self) {
+        if let Some(dt) = self.datetime() {
+            let next = dt + TimeDelta::seconds(1);
+            let mode = self.regs[HOURS as usize];
+            let mut regs = encode_datetime(&next);
+            regs[HOURS as usize] = encode_hours(next.hour() as u8, mode);
+            // Day of week is user defined - increment at midnight
+            regs[DAY as usize] = if next.date() != dt.date() {
+                self.regs[DAY as usize] % 7 + 1
+            } else {
+                self.regs[DAY as usize]
+            };
+            self.regs[..7].copy_from_slice(&regs);
+            if self.alarm1_matches(&next) {
+                self.regs[STATUS as usize] |= STATUS_A1F;
+            }
+            if next.second() == 0 && self.alarm2_matches(&next) {
+                self.regs[STATUS as usize] |= STATUS_A2F;
+            }
+        }
+        self.conversion_countdown -= 1;
+        if self.conversion_countdown == 0 {
+            self.convert_temperature();
+        }
+    }
+
+    fn alarm1_matches(&self, dt: &NaiveDateTime) -> bool {
+        let a = &self.regs[ALARM1_SECONDS as usize..=ALARM1_DAY_DATE as usize];
+        field_matches(a[0], bcd_to_bin(a[0] & 0x7f) as u32 == dt.second())
+            && self.alarm_hm_matches(&a[1..], dt)
+    }
+
+    fn alarm2_matches(&self, dt: &NaiveDateTime) -> bool {
+        self.alarm_hm_matches(
+            &self.regs[ALARM2_MINUTES as usize..=ALARM2_DAY_DATE as usize],
+            dt,
+        )
+    }
+
+    // Match [minutes, hours, day/date] alarm registers
+    fn alarm_hm_matches(&self, a: &[u8], dt: &NaiveDateTime) -> bool {
+        let day_date = if a[2] & ALARM_DAY != 0 {
+            a[2] & 0x0f == self.regs[DAY as usize]
+        } else {
+            bcd_to_bin(a[2] & 0x3f) as u32 == dt.day()
+        };
+        field_matches(a[0], bcd_to_bin(a[0] & 0x7f) as u32 == dt.minute())
+            && field_matches(a[1], decode_hours(a[1] & 0x7f) as u32 == dt.hour())
+            && field_matches(a[2], day_date)
+    }
+
+    fn convert_temperature(&mut self) {
+        let (msb, lsb) = encode_temperature(self.temperature);
+        self.regs[TEMP_MSB as usize] = msb;
+        self.regs[TEMP_LSB as usize] = lsb;
+        self.regs[CONTROL as usize] &= !CONTROL_CONV;
+        self.regs[STATUS as usize] &= !STATUS_BSY;
+        self.conversion_countdown = TEMP_CONVERSION_INTERVAL;
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) {
+        match reg {
+            SECONDS => {
+                // Writing seconds resets the countdown chain
+                self.regs[SECONDS as usize] = value;
+                self.sub_second = 0;
+            }
+            CONTROL => {
+                self.regs[CONTROL as usize] = value;
+                if value & CONTROL_CONV != 0 {
+                    self.regs[STATUS as usize] |= STATUS_BSY;
+                }
+            }
+            STATUS => {
+                // OSF/A2F/A1F can only be cleared, BSY is read only
+                let status = self.regs[STATUS as usize];
+                let clearable = STATUS_OSF | STATUS_A2F | STATUS_A1F;
+                self.regs[STATUS as usize] = (status & !clearable & !STATUS_EN32KHZ)
+                    | (status & clearable & value)
+                    | (value & STATUS_EN32KHZ);
+            }
+            TEMP_MSB | TEMP_LSB => {} // Read only
+            reg => self.regs[reg as usize] = value,
+        }
+    }
+
+    fn next_pointer(&mut self) {
+        self.pointer = (self.pointer + 1) % REGISTER_COUNT as u8;
+    }
+}
+
+fn field_matches(reg: u8, equal: bool) -> bool {
+    reg & ALARM_MASK != 0 || equal
+}
+
+impl ErrorType for Ds3231Emulator {
+    type Error = ErrorKind;
+}
+
+impl I2c for Ds3231Emulator {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if address != ADDRESS {
+            return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+        }
+        if self.fail_transactions > 0 {
+            self.fail_transactions -= 1;
+            return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+        }
+        let mut prev_write = false;
+        for op in operations {
+            match op {
+                Operation::Write(bytes) => {
+                    let mut data = bytes.iter();
+                    // First byte after a START sets the register pointer
+                    if !prev_write {
+                        if let Some(&reg) = data.next() {
+                            if reg as usize >= REGISTER_COUNT {
+                                return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data));
+                            }
+                            self.pointer = reg;
+                        }
+                    }
+                    for &b in data {
+                        self.write_register(self.pointer, b);
+                        self.next_pointer();
+                    }
+                    prev_write = true;
+                }
+                Operation::Read(buf) => {
+                    for b in buf.iter_mut() {
+                        *b = self.regs[self.pointer as usize];
+                        self.next_pointer();
+                    }
+                    prev_write = false;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Transactions complete immediately
+impl embedded_hal_async::i2c::I2c for Ds3231Emulator {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        I2c::transaction(self, address, operations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn dt(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, mo, d)
+            .unwrap()
+            .and_hms_opt(h, mi, s)
+            .unwrap()
+    }
+
+    #[test]
+    fn read_write_time_over_i2c() {
+        let mut rtc = Ds3231Emulator::new();
+        let t = dt(2026, 12, 31, 23, 59, 58);
+        let mut regs = [0; 8];
+        regs[1..].copy_from_slice(&encode_datetime(&t));
+        rtc.write(ADDRESS, &regs).unwrap();
+
+        let mut buf = [0; 7];
+        rtc.write_read(ADDRESS, &[SECONDS], &mut buf).unwrap();
+        assert_eq!(decode_datetime(&buf), Some(t));
+        assert!(rtc.write(0x57, &[0]).is_err());
+    }
+
+    #[test]
+    fn time_rolls_over_year_and_weekday() {
+        let mut rtc = Ds3231Emulator::new();
+        rtc.set_datetime(&dt(2099, 12, 31, 23, 59, 59));
+        let day = rtc.register(DAY);
+        rtc.advance_ms(1000);
+        assert_eq!(rtc.datetime(), Some(dt(2100, 1, 1, 0, 0, 0)));
+        assert_ne!(rtc.register(MONTH) & MONTH_CENTURY, 0);
+        assert_eq!(rtc.register(DAY), day % 7 + 1);
+    }
+
+    #[test]
+    fn twelve_hour_mode_is_preserved() {
+        let mut rtc = Ds3231Emulator::new();
+        rtc.set_datetime(&dt(2026, 1, 1, 11, 59, 59));
+        rtc.set_register(HOURS, encode_hours(11, HOURS_12H));
+        rtc.advance_ms(1000);
+        assert_eq!(rtc.register(HOURS), HOURS_12H | HOURS_PM | 0x12);
+        assert_eq!(rtc.datetime(), Some(dt(2026, 1, 1, 12, 0, 0)));
+    }
+
+    #[test]
+    fn alarm1_sets_flag_and_asserts_int() {
+        let mut rtc = Ds3231Emulator::new();
+        rtc.set_datetime(&dt(2026, 1, 1, 6, 59, 58));
+        // Match hours, minutes and seconds (A1M4 set)
+        rtc.write(ADDRESS, &[ALARM1_SECONDS, 0x00, 0x00, 0x07, ALARM_MASK])
+            .unwrap();
+        rtc.write(ADDRESS, &[CONTROL, CONTROL_INTCN | CONTROL_A1IE])
+            .unwrap();
+        rtc.advance_ms(1000);
+        assert!(!rtc.int_sqw_low());
+        rtc.advance_ms(1000);
+        assert!(rtc.int_sqw_low());
+        assert_ne!(rtc.register(STATUS) & STATUS_A1F, 0);
+
+        // Writing 1 does not set flags, writing 0 clears them
+        let status = rtc.register(STATUS);
+        rtc.write(ADDRESS, &[STATUS, status | STATUS_A2F]).unwrap();
+        assert_eq!(rtc.register(STATUS) & STATUS_A2F, 0);
+        rtc.write(ADDRESS, &[STATUS, status & !STATUS_A1F]).unwrap();
+        assert!(!rtc.int_sqw_low());
+    }
+
+    #[test]
+    fn alarm2_matches_day_of_week_on_minute() {
+        let mut rtc = Ds3231Emulator::new();
+        rtc.set_datetime(&dt(2026, 1, 2, 9, 29, 59));
+        let day = rtc.register(DAY);
+        rtc.write(ADDRESS, &[ALARM2_MINUTES, 0x30, 0x09, ALARM_DAY | day])
+            .unwrap();
+        rtc.advance_ms(1000);
+        assert_ne!(rtc.register(STATUS) & STATUS_A2F, 0);
+        // INT not asserted without A2IE
+        assert!(!rtc.int_sqw_low());
+    }
+
+    #[test]
+    fn square_wave_and_osf() {
+        let mut rtc = Ds3231Emulator::new();
+        assert_ne!(rtc.register(STATUS) & STATUS_OSF, 0);
+        rtc.write(ADDRESS, &[STATUS, 0]).unwrap();
+        rtc.write(ADDRESS, &[CONTROL, 0]).unwrap();
+        rtc.advance_ms(100);
+        assert!(rtc.int_sqw_low());
+        rtc.advance_ms(500);
+        assert!(!rtc.int_sqw_low());
+
+        rtc.stop_oscillator();
+        let t = rtc.datetime();
+        rtc.advance_ms(5000);
+        assert_eq!(rtc.datetime(), t);
+        assert_ne!(rtc.register(STATUS) & STATUS_OSF, 0);
+    }
+
+    #[test]
+    fn temperature_conversion() {
+        let mut rtc = Ds3231Emulator::new();
+        rtc.set_temperature(-10.25);
+        rtc.advance_ms(1000);
+        assert_eq!(
+            decode_temperature(rtc.register(TEMP_MSB), rtc.register(TEMP_LSB)),
+            25.0
+        );
+        rtc.write(ADDRESS, &[CONTROL, CONTROL_DEFAULT | CONTROL_CONV])
+            .unwrap();
+        assert_ne!(rtc.register(STATUS) & STATUS_BSY, 0);
+        rtc.advance_ms(200);
+        assert_eq!(rtc.register(STATUS) & STATUS_BSY, 0);
+        assert_eq!(rtc.register(CONTROL) & CONTROL_CONV, 0);
+        assert_eq!(
+            decode_temperature(rtc.register(TEMP_MSB), rtc.register(TEMP_LSB)),
+            -10.25
+        );
+    }
+
+    #[test]
+    fn aging_offset_corrects_drift() {
+        let mut rtc = Ds3231Emulator::new();
+        rtc.set_datetime(&dt(2026, 1, 1, 0, 0, 0));
+        // 20ppm fast - one day gains ~1.7s
+        rtc.set_drift_ppb(20_000);
+        for _ in 0..86_400 {
+            rtc.advance_ms(1000);
+        }
+        assert_eq!(rtc.datetime(), Some(dt(2026, 1, 2, 0, 0, 1)));
+        rtc.write(ADDRESS, &[AGING, 100]).unwrap();
+        assert_eq!(rtc.effective_drift_ppb(), 10_000);
+        rtc.write(ADDRESS, &[AGING, 200]).unwrap();
+        assert_eq!(rtc.effective_drift_ppb(), 20_000 + 56 * 100);
+    }
+}
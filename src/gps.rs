@@ -0,0 +1,191 @@
+// GPS time source (NMEA sentences over a UART, optional PPS input)
+//
+// Sentences report the UTC time of the PPS edge before them. With PPS the RTC
+// is set to the following second exactly on the next edge (and re-aligned
+// every PPS_SYNC_INTERVAL_S). Without PPS the sentence arrives up to a few
+// hundred ms late, so the RTC is only set when it is out by more than a second.
+//
+// The RTC is set through Msg::SetDate / Msg::SetTime (local time).
+use crate::nmea::Sentence;
+use crate::tz::TimeZone;
+use chrono::{NaiveDateTime, Timelike};
+use core::fmt;
+
+pub const PPS_SYNC_INTERVAL_S: i64 = 3600;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GpsStatus {
+    pub fix: bool,
+    // Satellites in use (from GGA)
+    pub satellites: Option<u8>,
+    // Last valid GPS time (UTC)
+    pub time: Option<NaiveDateTime>,
+    // PPS edge seen on the last sync attempt
+    pub pps: bool,
+    // RTC last set from GPS (UTC)
+    pub last_sync: Option<NaiveDateTime>,
+}
+
+impl GpsStatus {
+    pub const fn new() -> Self {
+        GpsStatus {
+            fix: false,
+            satellites: None,
+            time: None,
+            pps: false,
+            last_sync: None,
+        }
+    }
+
+    // Update from a sentence - returns the GPS time (UTC) if valid. ZDA is only
+    // trusted with a fix (receivers report their own clock until then).
+    pub fn update(&mut self, sentence: &Sentence) -> Option<NaiveDateTime> {
+        let time = match *sentence {
+            Sentence::Rmc { time, date, valid } => {
+                self.fix = valid;
+                date.zip(time).filter(|_| valid)
+            }
+            Sentence::Zda { time, date } => date.zip(time).filter(|_| self.fix),
+            Sentence::Gga {
+                quality,
+                satellites,
+            } => {
+                self.fix = quality > 0;
+                self.satellites = Some(satellites);
+                None
+            }
+        }
+        .map(|(d, t)| d.and_time(t));
+        if time.is_some() {
+            self.time = time;
+        }
+        time
+    }
+
+    // Whether to set the RTC (local time `rtc`) to GPS time `utc` (`local`)
+    pub fn needs_sync(
+        &self,
+        rtc: NaiveDateTime,
+        local: NaiveDateTime,
+        utc: NaiveDateTime,
+        pps: bool,
+    ) -> bool {
+        // RTC_TIME is up to a second behind
+        let wrong = (rtc - local).num_seconds().abs() > 1;
+        match self.last_sync {
+            None => true,
+            Some(last) => wrong || (pps && (utc - last).num_seconds() >= PPS_SYNC_INTERVAL_S),
+        }
+    }
+}
+
+// Local time to set for GPS (or radio clock) time `utc`. None if it can't be
+// set safely - the date and time are set separately (so avoid local midnight)
+// and local time in the hour repeated when DST ends is taken as the first
+// (DST) occurrence.
+pub fn local_time(tz: &TimeZone, utc: NaiveDateTime) -> Option<NaiveDateTime> {
+    let local = tz.to_local(utc);
+    let secs = local.num_seconds_from_midnight();
+    ((2..86_400 - 2).contains(&secs) && tz.to_utc(local) == utc).then_some(local)
+}
+
+// `gps status` report
+impl fmt::Display for GpsStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.fix, self.satellites) {
+            (true, Some(n)) => write!(f, "Fix: Yes ({} satellites)", n)?,
+            (true, None) => write!(f, "Fix: Yes")?,
+            (false, _) => write!(f, "Fix: No")?,
+        }
+        write!(f, "\r\nPPS: {}", if self.pps { "Yes" } else { "No" })?;
+        match self.time {
+            Some(t) => write!(f, "\r\nGPS time: {} UTC", t)?,
+            None => write!(f, "\r\nGPS time: Unknown")?,
+        }
+        match self.last_sync {
+            Some(t) => write!(f, "\r\nLast sync: {} UTC", t),
+            None => write!(f, "\r\nLast sync: Never"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, NaiveTime, TimeDelta};
+
+    fn at(h: u32, m: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 25)
+            .unwrap()
+            .and_hms_opt(h, m, s)
+            .unwrap()
+    }
+
+    #[test]
+    fn status_updates() {
+        let mut status = GpsStatus::new();
+        let zda = Sentence::Zda {
+            time: NaiveTime::from_hms_opt(12, 0, 0),
+            date: NaiveDate::from_ymd_opt(2026, 10, 25),
+        };
+        // Receiver clock before a fix
+        assert_eq!(status.update(&zda), None);
+        assert_eq!(
+            status.update(&Sentence::Gga {
+                quality: 1,
+                satellites: 7
+            }),
+            None
+        );
+        assert_eq!(status.update(&zda), Some(at(12, 0, 0)));
+        let rmc = Sentence::Rmc {
+            time: NaiveTime::from_hms_opt(12, 0, 1),
+            date: NaiveDate::from_ymd_opt(2026, 10, 25),
+            valid: false,
+        };
+        assert_eq!(status.update(&rmc), None);
+        assert!(!status.fix);
+        assert_eq!(status.time, Some(at(12, 0, 0)));
+        assert_eq!(
+            status.to_string(),
+            "Fix: No\r\nPPS: No\r\nGPS time: 2026-10-25 12:00:00 UTC\r\nLast sync: Never"
+        );
+        status.fix = true;
+        status.pps = true;
+        status.last_sync = Some(at(12, 0, 1));
+        assert_eq!(
+            status.to_string(),
+            "Fix: Yes (7 satellites)\r\nPPS: Yes\r\nGPS time: 2026-10-25 12:00:00 UTC\r\n\
+             Last sync: 2026-10-25 12:00:01 UTC"
+        );
+    }
+
+    #[test]
+    fn sync_policy() {
+        let mut status = GpsStatus::new();
+        let t = at(12, 0, 0);
+        // First sync always
+        assert!(status.needs_sync(t, t, t, false));
+        status.last_sync = Some(t);
+        let later = t + TimeDelta::minutes(30);
+        assert!(!status.needs_sync(later - TimeDelta::seconds(1), later, later, false));
+        assert!(status.needs_sync(later - TimeDelta::seconds(2), later, later, false));
+        // Re-aligned hourly with PPS
+        let later = t + TimeDelta::hours(1);
+        assert!(!status.needs_sync(later, later, later, false));
+        assert!(status.needs_sync(later, later, later, true));
+
+        // UK: BST ends 01:00 UTC on 2026-10-25 (01:00-02:00 local twice)
+        let tz = TimeZone::parse("GMT0BST,M3.5.0/1,M10.5.0").unwrap();
+        // Local time is set as the first 01:30 (BST)
+        assert_eq!(local_time(&tz, at(0, 30, 0)), Some(at(1, 30, 0)));
+        assert_eq!(local_time(&tz, at(1, 30, 0)), None);
+        assert_eq!(local_time(&tz, at(2, 30, 0)), Some(at(2, 30, 0)));
+        // Around local midnight
+        assert_eq!(local_time(&tz, at(23, 59, 58)), None);
+        assert_eq!(local_time(&tz, at(23, 59, 57)), Some(at(23, 59, 57)));
+        let utc = TimeZone::utc();
+        assert_eq!(local_time(&utc, at(0, 0, 1)), None);
+        assert_eq!(local_time(&utc, at(0, 0, 2)), Some(at(0, 0, 2)));
+    }
+}
@@ -0,0 +1,119 @@
+use chrono::{NaiveDateTime, Timelike};
+
+// Screen size (portrait)
+pub const SCREEN_WIDTH: u32 = 240;
+pub const SCREEN_HEIGHT: u32 = 320;
+
+// Title / alarm status
+pub const TITLE_X: i32 = 20;
+pub const TITLE_Y: i32 = 29;
+pub const TITLE_WIDTH: u32 = (GPS_X - TITLE_X) as u32;
+pub const TITLE_HEIGHT: u32 = 24;
+
+// GPS indicator (right of the title)
+pub const GPS_WIDTH: u32 = 40;
+pub const GPS_X: i32 = (SCREEN_WIDTH - GPS_WIDTH) as i32;
+pub const GPS_Y: i32 = TITLE_Y;
+pub const GPS_HEIGHT: u32 = 18;
+
+// 7-segment display
+pub const DIGIT_WIDTH: u32 = 32;
+pub const DIGIT_HEIGHT: u32 = 64;
+pub const DIGIT_SPACING: u32 = 4;
+pub const SEGMENT_WIDTH: u32 = 8;
+pub const START_Y: i32 = 60;
+pub const START_X: i32 = 2;
+pub const DATE_X: i32 = 20;
+pub const DATE_Y: i32 = START_Y + DIGIT_HEIGHT as i32 + 40;
+pub const DATE_WIDTH: u32 = 200;
+pub const DATE_HEIGHT: u32 = 24;
+pub const TEMP_X: i32 = 20;
+pub const TEMP_Y: i32 = DATE_Y + 40;
+pub const TEMP_WIDTH: u32 = 200;
+pub const TEMP_HEIGHT: u32 = 24;
+pub const ALARM1_X: i32 = 20;
+pub const ALARM1_Y: i32 = TEMP_Y + 40;
+pub const ALARM1_WIDTH: u32 = SCREEN_WIDTH - ALARM1_X as u32;
+pub const ALARM1_HEIGHT: u32 = 24;
+pub const ALARM2_X: i32 = 20;
+pub const ALARM2_Y: i32 = ALARM1_Y + 40;
+pub const ALARM2_WIDTH: u32 = SCREEN_WIDTH - ALARM2_X as u32;
+pub const ALARM2_HEIGHT: u32 = 24;
+
+// Temperature graph page - one column per minute (a whole number of hours),
+// temperature labels to the left and time labels below
+pub const GRAPH_X: i32 = 36;
+pub const GRAPH_Y: i32 = START_Y;
+pub const GRAPH_WIDTH: u32 = 180;
+pub const GRAPH_HEIGHT: u32 = 180;
+pub const GRAPH_LABEL_Y: i32 = GRAPH_Y + GRAPH_HEIGHT as i32 + 14;
+pub const GRAPH_MARKER_WIDTH: u32 = 8;
+pub const GRAPH_MINMAX_X: i32 = 20;
+pub const GRAPH_MINMAX_Y: i32 = GRAPH_LABEL_Y + 40;
+pub const GRAPH_MINMAX_WIDTH: u32 = SCREEN_WIDTH - GRAPH_MINMAX_X as u32;
+pub const GRAPH_MINMAX_HEIGHT: u32 = 18;
+
+// START_X | DIGIT | SP | DIGIT | SP | SEP | SP | DIGIT | SP | DIGIT | SP | SEP | SP | DIGIT | SEP
+pub const SEPARATOR_OFFSETS: [i32; 2] = [
+    START_X + (2 * DIGIT_WIDTH + 2 * DIGIT_SPACING) as i32,
+    START_X + (4 * DIGIT_WIDTH + 5 * DIGIT_SPACING + SEGMENT_WIDTH) as i32,
+];
+pub const DIGIT_OFFSETS: [i32; 6] = [
+    START_X,
+    START_X + (DIGIT_WIDTH + DIGIT_SPACING) as i32,
+    START_X + (2 * DIGIT_WIDTH + 3 * DIGIT_SPACING + SEGMENT_WIDTH) as i32,
+    START_X + (3 * DIGIT_WIDTH + 4 * DIGIT_SPACING + SEGMENT_WIDTH) as i32,
+    START_X + (4 * DIGIT_WIDTH + 6 * DIGIT_SPACING + 2 * SEGMENT_WIDTH) as i32,
+    START_X + (5 * DIGIT_WIDTH + 7 * DIGIT_SPACING + 2 * SEGMENT_WIDTH) as i32,
+];
+
+// Text rows must fit on screen (including descender)
+const _: () = assert!(TITLE_Y + 4 <= START_Y);
+const _: () = assert!(DATE_Y < TEMP_Y && TEMP_Y < ALARM1_Y && ALARM1_Y < ALARM2_Y);
+const _: () = assert!(ALARM2_Y + 4 <= SCREEN_HEIGHT as i32);
+const _: () = assert!(GRAPH_WIDTH.is_multiple_of(60));
+const _: () = assert!(GRAPH_X as u32 + GRAPH_WIDTH + GRAPH_MARKER_WIDTH <= SCREEN_WIDTH);
+const _: () = assert!(GRAPH_MINMAX_Y + 4 <= SCREEN_HEIGHT as i32);
+
+// Split time into HH:MM:SS digits (as displayed)
+pub fn digits(t: NaiveDateTime) -> [u8; 6] {
+    let (h, m, s) = (
+        t.time().hour() as u8,
+        t.time().minute() as u8,
+        t.time().second() as u8,
+    );
+    [h / 10, h % 10, m / 10, m % 10, s / 10, s % 10]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn digits_split() {
+        let t = NaiveDate::from_ymd_opt(2026, 1, 2)
+            .unwrap()
+            .and_hms_opt(23, 5, 9)
+            .unwrap();
+        assert_eq!(digits(t), [2, 3, 0, 5, 0, 9]);
+    }
+
+    #[test]
+    fn digits_do_not_overlap() {
+        for pair in DIGIT_OFFSETS.windows(2) {
+            assert!(pair[0] + DIGIT_WIDTH as i32 <= pair[1]);
+        }
+        let last = DIGIT_OFFSETS[DIGIT_OFFSETS.len() - 1];
+        assert!(last + DIGIT_WIDTH as i32 <= SCREEN_WIDTH as i32);
+    }
+
+    #[test]
+    fn separators_between_digit_pairs() {
+        for (i, sep) in SEPARATOR_OFFSETS.into_iter().enumerate() {
+            let left = DIGIT_OFFSETS[2 * i + 1] + DIGIT_WIDTH as i32;
+            let right = DIGIT_OFFSETS[2 * i + 2];
+            assert!(left <= sep && sep + SEGMENT_WIDTH as i32 <= right);
+        }
+    }
+}
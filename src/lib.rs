@@ -0,0 +1,37 @@
+#![cfg_attr(not(test), no_std)]
+
+// Hardware independent logic shared by the firmware binaries. Nothing in here
+// depends on embassy or the HAL so it also builds (and is tested) on the host:
+//
+//   cargo test-host
+
+pub mod alarm;
+pub mod at24c32;
+pub mod cli;
+pub mod drift;
+pub mod ds3231;
+pub mod gps;
+pub mod layout;
+pub mod line_editor;
+pub mod msg;
+pub mod nmea;
+pub mod radio_clock;
+pub mod ringer;
+pub mod rtc;
+pub mod rtc_health;
+pub mod settings;
+pub mod sync;
+pub mod temp_graph;
+pub mod temp_log;
+pub mod tz;
+
+// Run a future that never waits (the host emulators complete immediately)
+#[cfg(test)]
+pub(crate) fn block_on<F: core::future::Future>(f: F) -> F::Output {
+    use core::task::{Context, Poll, Waker};
+    let mut f = core::pin::pin!(f);
+    match f.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future is not ready"),
+    }
+}
@@ -0,0 +1,684 @@
+// Line editor for the serial console
+//
+// Terminal input is fed in a character at a time. The line is edited in place
+// and the echo and ANSI cursor movement needed to keep the terminal in step
+// are written to `out`. Cursor movement is relative to the cursor, so the
+// prompt is only needed for a full redraw.
+//
+//   Left/Right, Ctrl-B/F    Move a character
+//   Home/End, Ctrl-A/E      Start/end of line
+//   Backspace               Delete before the cursor
+//   Delete, Ctrl-D          Delete at the cursor
+//   Ctrl-K                  Delete to the end of the line
+//   Ctrl-U                  Delete to the start of the line
+//   Ctrl-W                  Delete the word before the cursor
+//   Ctrl-C                  Discard the line
+//   Ctrl-L                  Redraw the line
+//   Up/Down, Ctrl-P/N       Previous/next line from the history
+//   Ctrl-R                  Search the history (Ctrl-R again for older
+//                           matches, Ctrl-G to give up)
+//   Tab                     Complete the word (twice to list the choices)
+use core::fmt::{self, Write};
+use core::ops::Range;
+use heapless::String;
+
+mod history;
+pub use history::History;
+
+// Clear the terminal line and show the prompt
+pub const PROMPT: &str = "\x1b[2K\r>> ";
+
+// Written when the line is full
+const BELL: &str = "\x07";
+// Clear from the cursor to the end of the line
+const CLEAR_TO_END: &str = "\x1b[K";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    // Line complete - handle `line` then `clear` it before the next prompt
+    Enter,
+    // Tab - call `complete` with the words the one before the cursor could be
+    Complete,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Key {
+    Char(char),
+    Enter,
+    Left,
+    Right,
+    Home,
+    End,
+    Backspace,
+    Delete,
+    KillToEnd,
+    KillToStart,
+    KillWord,
+    Cancel,
+    Redraw,
+    Previous,
+    Next,
+    Search,
+    Complete,
+}
+
+// Escape sequence parsing
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Normal,
+    // After ESC
+    Escape,
+    // After ESC [ (parameters collected until the final byte)
+    Csi,
+    // After ESC O
+    Ss3,
+}
+
+// ESC [ <params> <final>
+fn csi_key(params: &str, c: char) -> Option<Key> {
+    match (params, c) {
+        ("", 'A') => Some(Key::Previous),
+        ("", 'B') => Some(Key::Next),
+        ("", 'C') => Some(Key::Right),
+        ("", 'D') => Some(Key::Left),
+        ("", 'H') | ("1" | "7", '~') => Some(Key::Home),
+        ("", 'F') | ("4" | "8", '~') => Some(Key::End),
+        ("3", '~') => Some(Key::Delete),
+        _ => None,
+    }
+}
+
+// ESC O <c> (application cursor mode)
+fn ss3_key(c: char) -> Option<Key> {
+    match c {
+        'A' => Some(Key::Previous),
+        'B' => Some(Key::Next),
+        'C' => Some(Key::Right),
+        'D' => Some(Key::Left),
+        'H' => Some(Key::Home),
+        'F' => Some(Key::End),
+        _ => None,
+    }
+}
+
+fn move_left<W: Write>(out: &mut W, n: usize) -> fmt::Result {
+    match n {
+        0 => Ok(()),
+        n => write!(out, "\x1b[{}D", n),
+    }
+}
+
+fn move_right<W: Write>(out: &mut W, n: usize) -> fmt::Result {
+    match n {
+        0 => Ok(()),
+        n => write!(out, "\x1b[{}C", n),
+    }
+}
+
+// Ctrl-R search in progress
+struct Search {
+    query: String<32>,
+    // History position of the line shown
+    found: Option<usize>,
+}
+
+// Line editor for lines of up to N bytes, keeping the last H lines entered
+pub struct LineEditor<const N: usize, const H: usize> {
+    buf: String<N>,
+    // Byte offset into `buf` (on a char boundary)
+    cursor: usize,
+    state: State,
+    params: String<8>,
+    // Last character was CR (the LF of a CRLF is ignored)
+    cr: bool,
+    history: History<N, H>,
+    // History position shown by Up/Down, and the line being edited before
+    recall: Option<usize>,
+    saved: String<N>,
+    search: Option<Search>,
+    // Consecutive Tabs
+    tabs: u8,
+}
+
+impl<const N: usize, const H: usize> Default for LineEditor<N, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, const H: usize> LineEditor<N, H> {
+    pub const fn new() -> Self {
+        LineEditor {
+            buf: String::new(),
+            cursor: 0,
+            state: State::Normal,
+            params: String::new(),
+            cr: false,
+            history: History::new(),
+            recall: None,
+            saved: String::new(),
+            search: None,
+            tabs: 0,
+        }
+    }
+
+    pub fn line(&self) -> &String<N> {
+        &self.buf
+    }
+
+    // Lines are added by the caller once they have been expanded
+    pub fn history(&self) -> &History<N, H> {
+        &self.history
+    }
+
+    pub fn history_mut(&mut self) -> &mut History<N, H> {
+        &mut self.history
+    }
+
+    // Text to complete from
+    pub fn before_cursor(&self) -> &str {
+        &self.buf[..self.cursor]
+    }
+
+    // Cursor position (characters from the start of the line)
+    pub fn column(&self) -> usize {
+        self.buf[..self.cursor].chars().count()
+    }
+
+    pub fn clear(&mut self) {
+        self.buf.clear();
+        self.cursor = 0;
+        self.state = State::Normal;
+        self.recall = None;
+        self.search = None;
+    }
+
+    // Prompt and line, with the cursor in place
+    pub fn redraw<W: Write>(&self, out: &mut W) -> fmt::Result {
+        out.write_str(PROMPT)?;
+        out.write_str(&self.buf)?;
+        move_left(out, self.buf[self.cursor..].chars().count())
+    }
+
+    // Handle a character of input
+    pub fn input<W: Write>(&mut self, c: char, out: &mut W) -> Result<Option<Event>, fmt::Error> {
+        let cr = core::mem::replace(&mut self.cr, c == '\r');
+        self.tabs = match c {
+            '\t' => self.tabs.saturating_add(1),
+            _ => 0,
+        };
+        if self.state == State::Normal && self.search_input(c, out)? {
+            return Ok(None);
+        }
+        let key = match self.state {
+            State::Normal => match c {
+                '\r' => Some(Key::Enter),
+                '\n' if cr => None,
+                '\n' => Some(Key::Enter),
+                '\x1b' => {
+                    self.state = State::Escape;
+                    None
+                }
+                '\x7f' | '\x08' => Some(Key::Backspace),
+                '\t' => Some(Key::Complete),
+                '\x01' => Some(Key::Home),
+                '\x02' => Some(Key::Left),
+                '\x03' => Some(Key::Cancel),
+                '\x04' => Some(Key::Delete),
+                '\x05' => Some(Key::End),
+                '\x06' => Some(Key::Right),
+                '\x0b' => Some(Key::KillToEnd),
+                '\x0c' => Some(Key::Redraw),
+                '\x0e' => Some(Key::Next),
+                '\x10' => Some(Key::Previous),
+                '\x12' => Some(Key::Search),
+                '\x15' => Some(Key::KillToStart),
+                '\x17' => Some(Key::KillWord),
+                // Ignore other control characters
+                '\x00'..='\x1f' => None,
+                c => Some(Key::Char(c)),
+            },
+            State::Escape => {
+                self.state = match c {
+                    '[' => State::Csi,
+                    'O' => State::Ss3,
+                    _ => State::Normal,
+                };
+                self.params.clear();
+                None
+            }
+            State::Csi => match c {
+                '0'..='9' | ';' => {
+                    if self.params.push(c).is_err() {
+                        self.state = State::Normal;
+                    }
+                    None
+                }
+                _ => {
+                    self.state = State::Normal;
+                    csi_key(&self.params, c)
+                }
+            },
+            State::Ss3 => {
+                self.state = State::Normal;
+                ss3_key(c)
+            }
+        };
+        match key {
+            Some(key) => self.key(key, out),
+            None => Ok(None),
+        }
+    }
+
+    fn key<W: Write>(&mut self, key: Key, out: &mut W) -> Result<Option<Event>, fmt::Error> {
+        let col = self.column();
+        match key {
+            Key::Enter => return Ok(Some(Event::Enter)),
+            Key::Complete => return Ok(Some(Event::Complete)),
+            Key::Char(c) => {
+                if self.buf.len() + c.len_utf8() > N {
+                    out.write_str(BELL)?;
+                } else {
+                    let mut bytes = [0; 4];
+                    self.splice(self.cursor..self.cursor, c.encode_utf8(&mut bytes));
+                    self.cursor += c.len_utf8();
+                    self.refresh(out, col, col, false)?;
+                }
+            }
+            Key::Left => {
+                if let Some(c) = self.buf[..self.cursor].chars().next_back() {
+                    self.cursor -= c.len_utf8();
+                    move_left(out, 1)?;
+                }
+            }
+            Key::Right => {
+                if let Some(c) = self.buf[self.cursor..].chars().next() {
+                    self.cursor += c.len_utf8();
+                    move_right(out, 1)?;
+                }
+            }
+            Key::Home => {
+                self.cursor = 0;
+                move_left(out, col)?;
+            }
+            Key::End => {
+                move_right(out, self.buf[self.cursor..].chars().count())?;
+                self.cursor = self.buf.len();
+            }
+            Key::Backspace => {
+                if let Some(c) = self.buf[..self.cursor].chars().next_back() {
+                    self.cursor -= c.len_utf8();
+                    self.splice(self.cursor..self.cursor + c.len_utf8(), "");
+                    self.refresh(out, col, col - 1, true)?;
+                }
+            }
+            Key::Delete => {
+                if let Some(c) = self.buf[self.cursor..].chars().next() {
+                    self.splice(self.cursor..self.cursor + c.len_utf8(), "");
+                    self.refresh(out, col, col, true)?;
+                }
+            }
+            Key::KillToEnd => {
+                self.buf.truncate(self.cursor);
+                out.write_str(CLEAR_TO_END)?;
+            }
+            Key::KillToStart => {
+                self.splice(0..self.cursor, "");
+                self.cursor = 0;
+                self.refresh(out, col, 0, true)?;
+            }
+            Key::KillWord => {
+                // Spaces before the cursor, then the word before them
+                let before = &self.buf[..self.cursor];
+                let word = before.trim_end_matches(' ');
+                let start = word.trim_end_matches(|c| c != ' ').len();
+                self.splice(start..self.cursor, "");
+                self.cursor = start;
+                self.refresh(out, col, self.column(), true)?;
+            }
+            Key::Cancel => {
+                out.write_str("^C\r\n")?;
+                self.clear();
+                self.redraw(out)?;
+            }
+            Key::Redraw => self.redraw(out)?,
+            Key::Previous => match self.recall.unwrap_or(self.history.len()).checked_sub(1) {
+                Some(i) => {
+                    if self.recall.is_none() {
+                        self.saved = self.buf.clone();
+                    }
+                    self.recall = Some(i);
+                    self.recall_line(i, out)?;
+                }
+                None => out.write_str(BELL)?,
+            },
+            Key::Next => match self.recall {
+                Some(i) if i + 1 < self.history.len() => {
+                    self.recall = Some(i + 1);
+                    self.recall_line(i + 1, out)?;
+                }
+                Some(_) => {
+                    // Back to the line being edited
+                    self.recall = None;
+                    self.buf = self.saved.clone();
+                    self.cursor = self.buf.len();
+                    self.redraw(out)?;
+                }
+                None => {}
+            },
+            Key::Search => {
+                self.search = Some(Search {
+                    query: String::new(),
+                    found: None,
+                });
+                self.draw_search(out)?;
+            }
+        }
+        Ok(None)
+    }
+
+    // Complete the word before the cursor from the words it could be. A single
+    // candidate is completed with a space after it, otherwise as much as they
+    // have in common is added. A second Tab lists them.
+    pub fn complete<W: Write>(&mut self, candidates: &[&str], out: &mut W) -> fmt::Result {
+        let start = self
+            .before_cursor()
+            .trim_end_matches(|c: char| !c.is_whitespace())
+            .len();
+        let typed = self.cursor - start;
+        let Some(first) = candidates.first() else {
+            return out.write_str(BELL);
+        };
+        let common = candidates.iter().fold(first.len(), |n, c| {
+            first
+                .bytes()
+                .zip(c.bytes())
+                .take(n)
+                .take_while(|(a, b)| a == b)
+                .count()
+        });
+        let mut insert: String<N> = String::new();
+        insert.push_str(first.get(typed..common).unwrap_or("")).ok();
+        if candidates.len() == 1 {
+            insert.push(' ').ok();
+        }
+        if !insert.is_empty() && self.buf.len() + insert.len() <= N {
+            let col = self.column();
+            self.splice(self.cursor..self.cursor, &insert);
+            self.cursor += insert.len();
+            self.refresh(out, col, col, false)
+        } else if self.tabs > 1 && candidates.len() > 1 {
+            out.write_str("\r\n")?;
+            for candidate in candidates {
+                write!(out, "{}  ", candidate)?;
+            }
+            out.write_str("\r\n")?;
+            self.redraw(out)
+        } else {
+            out.write_str(BELL)
+        }
+    }
+
+    // Show history line `i` for editing
+    fn recall_line<W: Write>(&mut self, i: usize, out: &mut W) -> fmt::Result {
+        if let Some(line) = self.history.get(i) {
+            self.buf = line.clone();
+            self.cursor = self.buf.len();
+        }
+        self.redraw(out)
+    }
+
+    // Handle a character while searching. Returns false if it ends the search
+    // (taking the line found) and is then handled as usual.
+    fn search_input<W: Write>(&mut self, c: char, out: &mut W) -> Result<bool, fmt::Error> {
+        let Some(search) = self.search.as_mut() else {
+            return Ok(false);
+        };
+        match c {
+            '\x12' => {
+                // Older match
+                let before = search.found.unwrap_or(self.history.len());
+                match self.history.search(&search.query, before) {
+                    Some(i) => search.found = Some(i),
+                    None => out.write_str(BELL)?,
+                }
+            }
+            '\x7f' | '\x08' => {
+                search.query.pop();
+                search.found = self.history.search(&search.query, self.history.len());
+            }
+            '\x03' | '\x07' => {
+                // Give up - the line is as it was
+                self.search = None;
+                self.redraw(out)?;
+                return Ok(true);
+            }
+            '\x00'..='\x1f' => {
+                if let Some(i) = search.found {
+                    self.recall = None;
+                    self.recall_line(i, out)?;
+                } else {
+                    self.redraw(out)?;
+                }
+                self.search = None;
+                return Ok(false);
+            }
+            c => {
+                if search.query.push(c).is_err() {
+                    out.write_str(BELL)?;
+                }
+                // The line shown may still match
+                let before = search.found.map_or(self.history.len(), |i| i + 1);
+                search.found = self.history.search(&search.query, before);
+            }
+        }
+        self.draw_search(out)?;
+        Ok(true)
+    }
+
+    fn draw_search<W: Write>(&self, out: &mut W) -> fmt::Result {
+        let Some(search) = self.search.as_ref() else {
+            return Ok(());
+        };
+        let found = search.found.and_then(|i| self.history.get(i));
+        write!(
+            out,
+            "\x1b[2K\r(reverse-i-search)`{}': {}",
+            search.query,
+            found.map_or("", |line| line.as_str())
+        )
+    }
+
+    // Replace a byte range of the line (the result must fit)
+    fn splice(&mut self, range: Range<usize>, s: &str) {
+        let mut buf: String<N> = String::new();
+        buf.push_str(&self.buf[..range.start]).ok();
+        buf.push_str(s).ok();
+        buf.push_str(&self.buf[range.end..]).ok();
+        self.buf = buf;
+    }
+
+    // Rewrite the line from column `from` after an edit (the terminal cursor
+    // is at column `col`), leaving the cursor in place. The old end of the
+    // line is cleared if it got shorter.
+    fn refresh<W: Write>(
+        &self,
+        out: &mut W,
+        col: usize,
+        from: usize,
+        shorter: bool,
+    ) -> fmt::Result {
+        move_left(out, col - from)?;
+        let start = self
+            .buf
+            .char_indices()
+            .nth(from)
+            .map_or(self.buf.len(), |(i, _)| i);
+        out.write_str(&self.buf[start..])?;
+        if shorter {
+            out.write_str(CLEAR_TO_END)?;
+        }
+        move_left(out, self.buf[self.cursor..].chars().count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Feed keystrokes, returning the terminal output
+    fn feed(editor: &mut LineEditor<16, 4>, input: &str) -> std::string::String {
+        let mut out = std::string::String::new();
+        for c in input.chars() {
+            assert_eq!(editor.input(c, &mut out), Ok(None));
+        }
+        out
+    }
+
+    #[test]
+    fn cursor_movement_and_insert() {
+        let mut editor = LineEditor::<16, 4>::new();
+        assert_eq!(feed(&mut editor, "get tme"), "get tme");
+        // Left twice and insert
+        assert_eq!(feed(&mut editor, "\x1b[D\x1bOD"), "\x1b[1D\x1b[1D");
+        assert_eq!(feed(&mut editor, "i"), "ime\x1b[2D");
+        assert_eq!(editor.line(), "get time");
+        assert_eq!(editor.column(), 6);
+        // Home / End
+        feed(&mut editor, "\x1b[H");
+        assert_eq!(editor.column(), 0);
+        assert_eq!(feed(&mut editor, "\x1b[F"), "\x1b[8C");
+        assert_eq!(editor.column(), 8);
+        feed(&mut editor, "\x01\x06\x06\x06\x06");
+        assert_eq!(editor.column(), 4);
+        assert_eq!(feed(&mut editor, "\x05"), "\x1b[4C");
+
+        // Full line rings the bell
+        assert_eq!(feed(&mut editor, " 12345678"), " 1234567\x07");
+        assert_eq!(editor.line().len(), 16);
+
+        let mut out = std::string::String::new();
+        assert_eq!(editor.input('\r', &mut out), Ok(Some(Event::Enter)));
+        // LF of CRLF ignored
+        assert_eq!(editor.input('\n', &mut out), Ok(None));
+        assert_eq!(out, "");
+        editor.clear();
+        editor.redraw(&mut out).unwrap();
+        assert_eq!(out, PROMPT);
+    }
+
+    #[test]
+    fn deletion() {
+        let mut editor = LineEditor::<16, 4>::new();
+        feed(&mut editor, "set time 12:00");
+        // Backspace at the end
+        assert_eq!(feed(&mut editor, "\x7f"), "\x1b[1D\x1b[K");
+        // Delete in the middle
+        feed(&mut editor, "\x1b[D\x1b[D");
+        assert_eq!(feed(&mut editor, "\x1b[3~"), "0\x1b[K\x1b[1D");
+        assert_eq!(editor.line(), "set time 120");
+        assert_eq!(feed(&mut editor, "\x04\x04\x04"), "\x1b[K");
+        assert_eq!(editor.line(), "set time 12");
+
+        // Ctrl-W deletes the word before the cursor (and trailing spaces)
+        feed(&mut editor, "\x1b[D\x1b[D ");
+        assert_eq!(editor.line(), "set time  12");
+        feed(&mut editor, "\x17");
+        assert_eq!(editor.line(), "set 12");
+        assert_eq!(editor.column(), 4);
+        // Ctrl-K / Ctrl-U
+        feed(&mut editor, "\x0b");
+        assert_eq!(editor.line(), "set ");
+        feed(&mut editor, "\x1b[Dx\x15");
+        assert_eq!((editor.line().as_str(), editor.column()), (" ", 0));
+
+        // Ctrl-C discards the line
+        feed(&mut editor, "abc");
+        assert_eq!(feed(&mut editor, "\x03"), ["^C\r\n", PROMPT].concat());
+        assert_eq!(editor.line(), "");
+        // Unknown sequences are ignored
+        feed(&mut editor, "\x1b[15~\x1bx\x1f");
+        assert_eq!(editor.line(), "");
+    }
+
+    #[test]
+    fn history_recall_and_search() {
+        let mut editor = LineEditor::<16, 4>::new();
+        for line in ["get time", "set tz UTC0", "get date"] {
+            editor.history_mut().push(line);
+        }
+        feed(&mut editor, "ge");
+        // Up from the newest line, back to the line being edited
+        assert_eq!(feed(&mut editor, "\x1b[A"), [PROMPT, "get date"].concat());
+        feed(&mut editor, "\x10\x10");
+        assert_eq!(editor.line(), "get time");
+        assert_eq!(feed(&mut editor, "\x1b[A"), "\x07");
+        feed(&mut editor, "\x1bOB\x0e");
+        assert_eq!(editor.line(), "get date");
+        feed(&mut editor, "\x0e");
+        assert_eq!((editor.line().as_str(), editor.column()), ("ge", 2));
+        // Down past the line being edited does nothing
+        assert_eq!(feed(&mut editor, "\x1b[B"), "");
+
+        // Ctrl-R searches older lines as the query is typed
+        editor.clear();
+        feed(&mut editor, "\x12");
+        assert_eq!(
+            feed(&mut editor, "get"),
+            "\x1b[2K\r(reverse-i-search)`g': get date\
+             \x1b[2K\r(reverse-i-search)`ge': get date\
+             \x1b[2K\r(reverse-i-search)`get': get date"
+        );
+        feed(&mut editor, "\x12");
+        assert_eq!(editor.line(), "");
+        assert_eq!(
+            feed(&mut editor, "\x12"),
+            "\x07\x1b[2K\r(reverse-i-search)`get': get time"
+        );
+        // Ending the search takes the line, and the key is handled as usual
+        assert_eq!(
+            feed(&mut editor, "\x1b[D"),
+            [PROMPT, "get time\x1b[1D"].concat()
+        );
+        assert_eq!(editor.column(), 7);
+        // Ctrl-G gives up, leaving the line as it was
+        feed(&mut editor, "\x12tz\x07");
+        assert_eq!(editor.line(), "get time");
+        let mut out = std::string::String::new();
+        feed(&mut editor, "\x12UTC");
+        assert_eq!(editor.input('\r', &mut out), Ok(Some(Event::Enter)));
+        assert_eq!(editor.line(), "set tz UTC0");
+    }
+
+    #[test]
+    fn completion() {
+        let mut editor = LineEditor::<16, 4>::new();
+        let mut out = std::string::String::new();
+        feed(&mut editor, "set ti");
+        assert_eq!(editor.input('\t', &mut out), Ok(Some(Event::Complete)));
+        assert_eq!(editor.before_cursor(), "set ti");
+        editor.complete(&["time"], &mut out).unwrap();
+        assert_eq!(out, "me ");
+        assert_eq!(editor.line(), "set time ");
+
+        // Common part of several, then the list on the second Tab
+        editor.clear();
+        feed(&mut editor, "set a");
+        out.clear();
+        editor.input('\t', &mut out).unwrap();
+        editor.complete(&["alarm", "alarm2"], &mut out).unwrap();
+        assert_eq!(out, "larm");
+        out.clear();
+        editor.input('\t', &mut out).unwrap();
+        editor.complete(&["alarm", "alarm2"], &mut out).unwrap();
+        assert_eq!(
+            out,
+            ["\r\nalarm  alarm2  \r\n", PROMPT, "set alarm"].concat()
+        );
+
+        // Nothing to complete
+        out.clear();
+        editor.complete(&[], &mut out).unwrap();
+        assert_eq!(out, "\x07");
+    }
+}
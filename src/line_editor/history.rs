@@ -0,0 +1,119 @@
+use heapless::{Deque, String};
+
+// The last H lines entered, numbered from 1 (for `history` and `!n`)
+pub struct History<const N: usize, const H: usize> {
+    lines: Deque<String<N>, H>,
+    // Number of the oldest line kept
+    first: usize,
+}
+
+impl<const N: usize, const H: usize> Default for History<N, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, const H: usize> History<N, H> {
+    pub const fn new() -> Self {
+        History {
+            lines: Deque::new(),
+            first: 1,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    // Add a line, dropping the oldest when full. Blank lines and repeats of
+    // the last line are not added.
+    pub fn push(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() || self.lines.back().is_some_and(|last| last == line) {
+            return;
+        }
+        let Ok(line) = String::try_from(line) else {
+            return;
+        };
+        if self.lines.is_full() {
+            self.lines.pop_front();
+            self.first += 1;
+        }
+        self.lines.push_back(line).ok();
+    }
+
+    // Line by position (0 is the oldest)
+    pub fn get(&self, i: usize) -> Option<&String<N>> {
+        self.lines.iter().nth(i)
+    }
+
+    // (number, line), oldest first
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+        (self.first..).zip(self.lines.iter().map(|line| line.as_str()))
+    }
+
+    // Expand `!!` (the last line) or `!n` (line n). Other lines are returned
+    // as they are; None if the line referred to isn't in the history.
+    pub fn expand<'a>(&'a self, line: &'a str) -> Option<&'a str> {
+        let Some(reference) = line.trim().strip_prefix('!') else {
+            return Some(line);
+        };
+        let n = match reference {
+            "!" => (self.first + self.lines.len()).checked_sub(1)?,
+            n => n.parse().ok()?,
+        };
+        self.get(n.checked_sub(self.first)?)
+            .map(|line| line.as_str())
+    }
+
+    // Position of the most recent line before position `before` containing
+    // `query`
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        self.lines
+            .iter()
+            .take(before)
+            .rposition(|line| line.contains(query))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbered_ring() {
+        let mut history = History::<16, 3>::new();
+        assert_eq!(history.expand("!!"), None);
+        for line in [
+            "get time",
+            " ",
+            "get time",
+            "get date",
+            "set tz UTC0",
+            "get temp",
+        ] {
+            history.push(line);
+        }
+        assert_eq!(
+            history.iter().collect::<Vec<_>>(),
+            [(2, "get date"), (3, "set tz UTC0"), (4, "get temp")]
+        );
+        assert_eq!(history.expand("get alarm"), Some("get alarm"));
+        assert_eq!(history.expand("!!"), Some("get temp"));
+        assert_eq!(history.expand(" !3"), Some("set tz UTC0"));
+        assert_eq!(history.expand("!1"), None);
+        assert_eq!(history.expand("!5"), None);
+        assert_eq!(history.expand("!x"), None);
+
+        assert_eq!(history.search("get", 3), Some(2));
+        assert_eq!(history.search("get", 2), Some(0));
+        assert_eq!(history.search("alarm", 3), None);
+        // Too long to keep
+        history.push("set alarm weekdays 07:00");
+        assert_eq!(history.len(), 3);
+    }
+}
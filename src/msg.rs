@@ -0,0 +1,69 @@
+use crate::ringer::AlarmState;
+use crate::settings::{Colour, DateFormat};
+use crate::sync::SyncRequest;
+use chrono::{NaiveDate, NaiveTime};
+use defmt::Formatter;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Msg {
+    SetTime(NaiveTime),
+    SetDate(NaiveDate),
+    // Alarm table edited - re-arm the next alarm
+    AlarmsChanged,
+    SetAlarm2(NaiveTime),
+    ClearAlarm2,
+    // Snooze / max ring time (minutes)
+    SetSnooze(u8),
+    SetRingTime(u8),
+    // Minutes between temperature samples
+    SetTempInterval(u8),
+    // Force a temperature conversion (result in TEMP_NOW)
+    ConvertTemp,
+    SetDateFormat(DateFormat),
+    SetColour(Colour),
+    SetBrightness(u8),
+    // Time zone changed in SETTINGS
+    SetTimeZone,
+    // Set the RTC from the host (result in SYNC_RESULT)
+    Sync(SyncRequest),
+    // RTC aging offset (drift calibration)
+    SetAging(i8),
+    // Settings store (config save/load/reset)
+    SaveSettings,
+    LoadSettings,
+    ResetSettings,
+    // Settings replaced by load/reset - re-read SETTINGS
+    SettingsLoaded,
+    // Published by the ringer on each state change
+    AlarmState(AlarmState),
+    // Short button press - changes the display page when no alarm is active
+    NextPage,
+}
+
+impl defmt::Format for Msg {
+    fn format(&self, fmt: Formatter) {
+        match self {
+            Msg::SetTime(_) => defmt::write!(fmt, "<SetTime>"),
+            Msg::SetDate(_) => defmt::write!(fmt, "<SetDate>"),
+            Msg::AlarmsChanged => defmt::write!(fmt, "<AlarmsChanged>"),
+            Msg::SetAlarm2(_) => defmt::write!(fmt, "<SetAlarm2>"),
+            Msg::ClearAlarm2 => defmt::write!(fmt, "<ClearAlarm2>"),
+            Msg::SetSnooze(_) => defmt::write!(fmt, "<SetSnooze>"),
+            Msg::SetRingTime(_) => defmt::write!(fmt, "<SetRingTime>"),
+            Msg::SetTempInterval(_) => defmt::write!(fmt, "<SetTempInterval>"),
+            Msg::ConvertTemp => defmt::write!(fmt, "<ConvertTemp>"),
+            Msg::SetDateFormat(_) => defmt::write!(fmt, "<SetDateFormat>"),
+            Msg::SetColour(_) => defmt::write!(fmt, "<SetColour>"),
+            Msg::SetBrightness(_) => defmt::write!(fmt, "<SetBrightness>"),
+            Msg::SetTimeZone => defmt::write!(fmt, "<SetTimeZone>"),
+            Msg::Sync(_) => defmt::write!(fmt, "<Sync>"),
+            Msg::SetAging(_) => defmt::write!(fmt, "<SetAging>"),
+            Msg::SaveSettings => defmt::write!(fmt, "<SaveSettings>"),
+            Msg::LoadSettings => defmt::write!(fmt, "<LoadSettings>"),
+            Msg::ResetSettings => defmt::write!(fmt, "<ResetSettings>"),
+            Msg::SettingsLoaded => defmt::write!(fmt, "<SettingsLoaded>"),
+            Msg::AlarmState(state) => defmt::write!(fmt, "<AlarmState {}>", state),
+            Msg::NextPage => defmt::write!(fmt, "<NextPage>"),
+        }
+    }
+}
@@ -0,0 +1,269 @@
+// NMEA 0183 sentences from a GPS receiver (see gps)
+//
+// Only sentences carrying time or fix status are decoded:
+//   RMC - UTC time, date and fix status
+//   ZDA - UTC time and date
+//   GGA - fix quality and satellites in use
+// Any talker is accepted ($GP, $GN, $GL...) and the checksum is required.
+use chrono::{NaiveDate, NaiveTime};
+use heapless::String;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take, take_till, take_while_m_n};
+use nom::character::complete::{char, digit1};
+use nom::combinator::{all_consuming, map, map_opt, map_res, opt};
+use nom::error::Error;
+use nom::multi::fold_many_m_n;
+use nom::sequence::{preceded, tuple};
+use nom::IResult;
+
+// Longest sentence allowed by the standard (including "$" and CR LF)
+pub const MAX_SENTENCE_LEN: usize = 82;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sentence {
+    Rmc {
+        time: Option<NaiveTime>,
+        date: Option<NaiveDate>,
+        // Status A (valid) / V (warning)
+        valid: bool,
+    },
+    Zda {
+        time: Option<NaiveTime>,
+        date: Option<NaiveDate>,
+    },
+    Gga {
+        // 0 = no fix
+        quality: u8,
+        satellites: u8,
+    },
+}
+
+fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0, |sum, b| sum ^ b)
+}
+
+// "$<data>*hh" - returns <data> if the checksum matches
+fn frame(line: &str) -> Option<&str> {
+    let (data, sum) = line.trim_end().strip_prefix('$')?.split_once('*')?;
+    let sum = (sum.len() == 2).then(|| u8::from_str_radix(sum, 16).ok())??;
+    (sum == checksum(data)).then_some(data)
+}
+
+fn number(digits: usize) -> impl Fn(&str) -> IResult<&str, u32, Error<&str>> {
+    move |input| {
+        map_res(
+            take_while_m_n(digits, digits, |c: char| c.is_ascii_digit()),
+            str::parse,
+        )(input)
+    }
+}
+
+// hhmmss[.sss] (fraction ignored - sentences refer to the PPS edge)
+fn time(input: &str) -> IResult<&str, NaiveTime, Error<&str>> {
+    map_opt(
+        tuple((
+            number(2),
+            number(2),
+            number(2),
+            opt(preceded(char('.'), digit1)),
+        )),
+        |(h, m, s, _)| NaiveTime::from_hms_opt(h, m, s),
+    )(input)
+}
+
+// RMC date: ddmmyy
+fn date(input: &str) -> IResult<&str, NaiveDate, Error<&str>> {
+    map_opt(tuple((number(2), number(2), number(2))), |(d, m, y)| {
+        NaiveDate::from_ymd_opt(2000 + y as i32, m, d)
+    })(input)
+}
+
+fn field(input: &str) -> IResult<&str, &str, Error<&str>> {
+    preceded(char(','), take_till(|c| c == ','))(input)
+}
+
+fn skip_fields(n: usize) -> impl FnMut(&str) -> IResult<&str, (), Error<&str>> {
+    move |input| fold_many_m_n(n, n, field, || (), |_, _| ())(input)
+}
+
+// Whole field, None if empty or invalid
+fn parse_field<'a, O>(
+    field: &'a str,
+    parser: impl FnMut(&'a str) -> IResult<&'a str, O, Error<&'a str>>,
+) -> Option<O> {
+    all_consuming(parser)(field).ok().map(|(_, value)| value)
+}
+
+// RMC,time,status,lat,N/S,lon,E/W,speed,course,date,...
+fn rmc(input: &str) -> IResult<&str, Sentence, Error<&str>> {
+    map(
+        tuple((tag("RMC"), field, field, skip_fields(6), field)),
+        |(_, t, status, _, d)| Sentence::Rmc {
+            time: parse_field(t, time),
+            date: parse_field(d, date),
+            valid: status == "A",
+        },
+    )(input)
+}
+
+// ZDA,time,day,month,year,...
+fn zda(input: &str) -> IResult<&str, Sentence, Error<&str>> {
+    map(
+        tuple((tag("ZDA"), field, field, field, field)),
+        |(_, t, d, m, y)| Sentence::Zda {
+            time: parse_field(t, time),
+            date: parse_field(d, number(2))
+                .zip(parse_field(m, number(2)))
+                .zip(parse_field(y, number(4)))
+                .and_then(|((d, m), y)| NaiveDate::from_ymd_opt(y as i32, m, d)),
+        },
+    )(input)
+}
+
+// GGA,time,lat,N/S,lon,E/W,quality,satellites,...
+fn gga(input: &str) -> IResult<&str, Sentence, Error<&str>> {
+    map(
+        tuple((tag("GGA"), skip_fields(5), field, field)),
+        |(_, _, quality, satellites)| Sentence::Gga {
+            quality: quality.parse().unwrap_or(0),
+            satellites: satellites.parse().unwrap_or(0),
+        },
+    )(input)
+}
+
+// Parse a received line (None if corrupt or not a sentence we use)
+pub fn parse(line: &str) -> Option<Sentence> {
+    let data = frame(line)?;
+    preceded(take(2usize), alt((rmc, zda, gga)))(data)
+        .ok()
+        .map(|(_, sentence)| sentence)
+}
+
+// Assembles lines from received bytes
+#[derive(Default)]
+pub struct LineBuffer {
+    line: String<MAX_SENTENCE_LEN>,
+    // Line complete (cleared on the next byte)
+    done: bool,
+    // Line too long or not ASCII - discard up to the next line
+    discard: bool,
+}
+
+impl LineBuffer {
+    pub const fn new() -> Self {
+        LineBuffer {
+            line: String::new(),
+            done: false,
+            discard: false,
+        }
+    }
+
+    // Add a received byte - returns the line (without CR LF) on LF
+    pub fn push(&mut self, b: u8) -> Option<&str> {
+        if self.done {
+            self.line.clear();
+            self.done = false;
+        }
+        match b {
+            b'\n' => {
+                let discard = core::mem::take(&mut self.discard);
+                self.done = true;
+                (!discard && !self.line.is_empty()).then_some(self.line.as_str())
+            }
+            b'\r' => None,
+            // A new sentence always starts with '$' (resync after noise)
+            b'$' => {
+                self.line.clear();
+                self.discard = false;
+                self.line.push('$').ok();
+                None
+            }
+            b if b.is_ascii() && !self.discard => {
+                self.discard = self.line.push(b as char).is_err();
+                None
+            }
+            _ => {
+                self.discard = true;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hms(h: u32, m: u32, s: u32) -> Option<NaiveTime> {
+        NaiveTime::from_hms_opt(h, m, s)
+    }
+
+    #[test]
+    fn sentences() {
+        assert_eq!(
+            parse("$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230326,003.1,W*63"),
+            Some(Sentence::Rmc {
+                time: hms(12, 35, 19),
+                date: NaiveDate::from_ymd_opt(2026, 3, 23),
+                valid: true,
+            })
+        );
+        // No fix yet
+        assert_eq!(
+            parse("$GNRMC,,V,,,,,,,,,,N*4D\r\n"),
+            Some(Sentence::Rmc {
+                time: None,
+                date: None,
+                valid: false,
+            })
+        );
+        assert_eq!(
+            parse("$GPZDA,201530.00,04,07,2026,00,00*66"),
+            Some(Sentence::Zda {
+                time: hms(20, 15, 30),
+                date: NaiveDate::from_ymd_opt(2026, 7, 4),
+            })
+        );
+        assert_eq!(
+            parse("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47"),
+            Some(Sentence::Gga {
+                quality: 1,
+                satellites: 8,
+            })
+        );
+        // Bad checksum, unused sentence, missing checksum
+        assert_eq!(parse("$GPZDA,201530.00,04,07,2026,00,00*6C"), None);
+        assert_eq!(
+            parse("$GPGSA,A,3,04,05,,09,12,,,24,,,,,2.5,1.3,2.1*39"),
+            None
+        );
+        assert_eq!(parse("$GPZDA,201530.00,04,07,2026,00,00"), None);
+    }
+
+    #[test]
+    fn line_buffer() {
+        let mut buf = LineBuffer::new();
+        let mut lines: heapless::Vec<Option<Sentence>, 4> = heapless::Vec::new();
+        for &b in b"noise$GPZDA,201530.00,04,07,2026,00,00*66\r\n\xff\r\n$GPZDA\r\n\r\n" {
+            if let Some(line) = buf.push(b) {
+                lines.push(parse(line)).unwrap();
+            }
+        }
+        // Noise before '$', non-ASCII line and blank line dropped
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].is_some() && lines[1].is_none());
+        // Too long
+        for _ in 0..=MAX_SENTENCE_LEN {
+            assert_eq!(buf.push(b'A'), None);
+        }
+        assert_eq!(buf.push(b'\n'), None);
+        let mut line = None;
+        for &b in b"$GPZDA\r\n" {
+            line = buf
+                .push(b)
+                .map(heapless::String::<8>::try_from)
+                .map(Result::unwrap);
+        }
+        assert_eq!(line.as_deref(), Some("$GPZDA"));
+    }
+}
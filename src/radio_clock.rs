@@ -0,0 +1,361 @@
+// DCF77 / MSF longwave time signal decoder
+//
+// Fed with the demodulated receiver output (carrier off/on edges). Each second
+// starts with the carrier switched off; the pulse width carries the data:
+//
+//   DCF77  100ms = 0, 200ms = 1, no pulse in second 59 (minute marker)
+//          BCD LSB first, even parity, German time (CET/CEST)
+//   MSF    500ms minute marker in second 0, then bits A/B per second:
+//          100ms = 00, 200ms = 10, 300ms = 11, 100ms off/on/off = 01
+//          BCD MSB first, odd parity, UK time (GMT/BST)
+//
+// The frame sent during a minute gives the time at the next minute marker.
+// A frame is only returned once the previous frame agrees with it.
+use chrono::{Datelike, NaiveDate, NaiveDateTime, TimeDelta};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protocol {
+    Dcf77,
+    Msf,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    // UTC at the minute marker
+    pub utc: NaiveDateTime,
+    // Monotonic time (ms) the minute marker started
+    pub at_ms: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Pulse {
+    Bit(bool, bool),
+    Marker,
+    Invalid,
+}
+
+impl Protocol {
+    fn pulse(&self, width_ms: u64) -> Pulse {
+        match (self, width_ms) {
+            (Protocol::Dcf77, 40..=140) => Pulse::Bit(false, false),
+            (Protocol::Dcf77, 150..=250) => Pulse::Bit(true, false),
+            (Protocol::Msf, 50..=150) => Pulse::Bit(false, false),
+            (Protocol::Msf, 170..=250) => Pulse::Bit(true, false),
+            (Protocol::Msf, 270..=350) => Pulse::Bit(true, true),
+            (Protocol::Msf, 450..=550) => Pulse::Marker,
+            _ => Pulse::Invalid,
+        }
+    }
+
+    // Last second with data before the next minute marker
+    fn last_second(&self) -> usize {
+        match self {
+            Protocol::Dcf77 => 58,
+            Protocol::Msf => 59,
+        }
+    }
+}
+
+// Sum of `weights` for the bits set from second `from`
+fn value(bits: u64, from: usize, weights: &[u32]) -> u32 {
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| bits & (1 << (from + i)) != 0)
+        .map(|(_, w)| w)
+        .sum()
+}
+
+// Bits set in seconds `from..=to`
+fn ones(bits: u64, from: usize, to: usize) -> u32 {
+    (bits >> from & ((1 << (to - from + 1)) - 1)).count_ones()
+}
+
+fn bit(bits: u64, second: usize) -> bool {
+    bits & (1 << second) != 0
+}
+
+// Local time with the UTC offset (hours) and the ISO weekday (1 = Monday)
+fn datetime(
+    (year, month, day): (u32, u32, u32),
+    (hour, minute): (u32, u32),
+    weekday: u32,
+    offset: i64,
+) -> Option<NaiveDateTime> {
+    let date = NaiveDate::from_ymd_opt(2000 + year as i32, month, day)?;
+    if date.weekday().number_from_monday() != weekday {
+        return None;
+    }
+    Some(date.and_hms_opt(hour, minute, 0)? - TimeDelta::hours(offset))
+}
+
+fn decode_dcf77(a: u64) -> Option<NaiveDateTime> {
+    // Start of minute 0, start of time 1, exactly one of CEST/CET
+    if bit(a, 0) || !bit(a, 20) || bit(a, 17) == bit(a, 18) {
+        return None;
+    }
+    let parity = [(21, 28), (29, 35), (36, 58)];
+    if parity
+        .iter()
+        .any(|&(from, to)| !ones(a, from, to).is_multiple_of(2))
+    {
+        return None;
+    }
+    datetime(
+        (
+            value(a, 50, &[1, 2, 4, 8, 10, 20, 40, 80]),
+            value(a, 45, &[1, 2, 4, 8, 10]),
+            value(a, 36, &[1, 2, 4, 8, 10, 20]),
+        ),
+        (
+            value(a, 29, &[1, 2, 4, 8, 10, 20]),
+            value(a, 21, &[1, 2, 4, 8, 10, 20, 40]),
+        ),
+        value(a, 42, &[1, 2, 4]),
+        if bit(a, 17) { 2 } else { 1 },
+    )
+}
+
+fn decode_msf(a: u64, b: u64) -> Option<NaiveDateTime> {
+    // Marker 01111110 in 52A-59A
+    if a >> 52 & 0xff != 0b0111_1110 {
+        return None;
+    }
+    let parity = [(17, 24, 54), (25, 35, 55), (36, 38, 56), (39, 51, 57)];
+    if parity
+        .iter()
+        .any(|&(from, to, p)| (ones(a, from, to) + bit(b, p) as u32) % 2 != 1)
+    {
+        return None;
+    }
+    datetime(
+        (
+            value(a, 17, &[80, 40, 20, 10, 8, 4, 2, 1]),
+            value(a, 25, &[10, 8, 4, 2, 1]),
+            value(a, 30, &[20, 10, 8, 4, 2, 1]),
+        ),
+        (
+            value(a, 39, &[20, 10, 8, 4, 2, 1]),
+            value(a, 45, &[40, 20, 10, 8, 4, 2, 1]),
+        ),
+        // 0 = Sunday
+        match value(a, 36, &[4, 2, 1]) {
+            0 => 7,
+            d => d,
+        },
+        bit(b, 58) as i64,
+    )
+}
+
+pub struct Decoder {
+    protocol: Protocol,
+    // Carrier off since
+    pulse_start: Option<u64>,
+    // Start of the current second
+    second_start: Option<u64>,
+    // Current second (None until a minute marker is seen)
+    second: Option<usize>,
+    // Bits (A/B) by second
+    a: u64,
+    b: u64,
+    // Previous frame decoded
+    last: Option<Frame>,
+}
+
+impl Decoder {
+    pub const fn new(protocol: Protocol) -> Self {
+        Decoder {
+            protocol,
+            pulse_start: None,
+            second_start: None,
+            second: None,
+            a: 0,
+            b: 0,
+            last: None,
+        }
+    }
+
+    // Carrier switched off (`off`) or back on at `at_ms`. Returns a frame once
+    // it is confirmed by the previous one.
+    pub fn edge(&mut self, off: bool, at_ms: u64) -> Option<Frame> {
+        if off {
+            self.pulse_start.get_or_insert(at_ms);
+            return None;
+        }
+        let start = self.pulse_start.take()?;
+        let frame = self.pulse(start, at_ms.saturating_sub(start))?;
+        // Both frames must give the same time for the marker
+        let confirmed = self.last.is_some_and(|last| {
+            let expected = (frame.at_ms - last.at_ms) as i64;
+            ((frame.utc - last.utc).num_milliseconds() - expected).abs() < 1000
+        });
+        self.last = Some(frame);
+        confirmed.then_some(frame)
+    }
+
+    fn pulse(&mut self, start: u64, width: u64) -> Option<Frame> {
+        let since = self.second_start.map(|s| start.saturating_sub(s));
+        let pulse = self.protocol.pulse(width);
+
+        // MSF bit B without A (second pulse in the same second)
+        if let (Protocol::Msf, Some(150..=350), Pulse::Bit(false, false), Some(second)) =
+            (self.protocol, since, pulse, self.second)
+        {
+            self.b |= 1 << second;
+            return None;
+        }
+
+        let minute = match self.protocol {
+            Protocol::Dcf77 => matches!(since, Some(1800..=2200)),
+            Protocol::Msf => pulse == Pulse::Marker,
+        };
+        let mut frame = None;
+        if minute {
+            let complete = self.second == Some(self.protocol.last_second())
+                && (self.protocol == Protocol::Dcf77 || matches!(since, Some(900..=1100)));
+            if complete {
+                let utc = match self.protocol {
+                    Protocol::Dcf77 => decode_dcf77(self.a),
+                    Protocol::Msf => decode_msf(self.a, self.b),
+                };
+                frame = utc.map(|utc| Frame { utc, at_ms: start });
+            }
+            self.second = Some(0);
+            self.a = 0;
+            self.b = 0;
+        } else if matches!(since, Some(900..=1100)) {
+            self.second = self.second.map(|s| s + 1).filter(|s| *s < 64);
+        } else {
+            // Noise or a missed pulse - wait for the next minute marker
+            self.second = None;
+        }
+        self.second_start = Some(start);
+
+        match (pulse, self.second) {
+            (Pulse::Bit(a, b), Some(second)) => {
+                self.a |= (a as u64) << second;
+                self.b |= (b as u64) << second;
+            }
+            (Pulse::Invalid, _) => self.second = None,
+            _ => {}
+        }
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Frames as transmitted (one symbol per second)
+    //   DCF77: 0/1 pulse width, '-' no pulse
+    //   MSF: M marker, then A+2B (0-3)
+    // DCF77 12:34 and 12:35 CET on Sunday 2026-03-01
+    const DCF77_1234: &str = "00000000000000000010100101101010010010000011111000011001001-";
+    const DCF77_1235: &str = "00000000000000000010110101100010010010000011111000011001001-";
+    // MSF 10:15 and 10:16 BST on Saturday 2026-07-04
+    const MSF_1015: &str = "M00000000000000000010011000111000100110010000001010101133330";
+    const MSF_1016: &str = "M00000000000000000010011000111000100110010000001011001133330";
+
+    // Receiver output edges (carrier off, ms) with some jitter
+    fn trace(protocol: Protocol, frames: &[&str], start_ms: u64) -> Vec<(bool, u64)> {
+        let mut edges = Vec::new();
+        let mut t = start_ms;
+        for (n, symbol) in frames.iter().flat_map(|f| f.chars()).enumerate() {
+            let jitter = [0, 7, 3, 12, 5][n % 5] - 6;
+            let pulses: &[(u64, u64)] = match (protocol, symbol) {
+                (_, '-') => &[],
+                (Protocol::Msf, 'M') => &[(0, 500)],
+                (Protocol::Msf, '2') => &[(0, 100), (200, 100)],
+                (Protocol::Msf, '3') => &[(0, 300)],
+                (_, '1') => &[(0, 200)],
+                _ => &[(0, 100)],
+            };
+            for &(offset, width) in pulses {
+                edges.push((true, t + offset));
+                edges.push((false, (t + offset + width).saturating_add_signed(jitter)));
+            }
+            t += 1000;
+        }
+        edges
+    }
+
+    fn flip(frame: &str, second: usize) -> String {
+        frame
+            .char_indices()
+            .map(|(i, c)| match (i == second, c) {
+                (true, '0') => '1',
+                (true, _) => '0',
+                _ => c,
+            })
+            .collect()
+    }
+
+    fn decode(protocol: Protocol, edges: &[(bool, u64)]) -> Vec<Frame> {
+        let mut decoder = Decoder::new(protocol);
+        edges
+            .iter()
+            .filter_map(|&(off, at)| decoder.edge(off, at))
+            .collect()
+    }
+
+    fn utc(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        let month = if d == 1 { 3 } else { 7 };
+        NaiveDate::from_ymd_opt(2026, month, d)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn dcf77() {
+        // Starts mid-minute: the first full frame is 12:34, confirmed by 12:35.
+        // A marker follows the last frame so that it is complete.
+        let edges = trace(
+            Protocol::Dcf77,
+            &["000000-", DCF77_1234, DCF77_1235, "0"],
+            5_000,
+        );
+        let frames = decode(Protocol::Dcf77, &edges);
+        assert_eq!(
+            frames,
+            [Frame {
+                utc: utc(1, 11, 35),
+                at_ms: 5_000 + 127_000,
+            }]
+        );
+
+        // Parity error in the second frame
+        let bad = flip(DCF77_1235, 30);
+        let edges = trace(Protocol::Dcf77, &["-", DCF77_1234, &bad, "0"], 0);
+        assert!(decode(Protocol::Dcf77, &edges).is_empty());
+        // Frames that disagree
+        let edges = trace(Protocol::Dcf77, &["-", DCF77_1235, DCF77_1234, "0"], 0);
+        assert!(decode(Protocol::Dcf77, &edges).is_empty());
+    }
+
+    #[test]
+    fn msf() {
+        let edges = trace(Protocol::Msf, &["0000", MSF_1015, MSF_1016, "M"], 0);
+        assert_eq!(
+            decode(Protocol::Msf, &edges),
+            [Frame {
+                utc: utc(4, 9, 16),
+                at_ms: 124_000,
+            }]
+        );
+        // Missing B bit (BST flag and parity)
+        let bad = MSF_1016.replace("133330", "133310");
+        let edges = trace(Protocol::Msf, &["0000", MSF_1015, &bad, "M"], 0);
+        assert!(decode(Protocol::Msf, &edges).is_empty());
+    }
+
+    #[test]
+    fn noise() {
+        let mut edges = trace(Protocol::Dcf77, &["-", DCF77_1234, DCF77_1235, "0"], 0);
+        // Glitch in the second frame - the frame is dropped
+        edges.insert(118, (true, 61_500));
+        edges.insert(119, (false, 61_520));
+        assert!(decode(Protocol::Dcf77, &edges).is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+// Alarm ringing state machine:
+//
+//   Idle -> Ringing -> Snoozed -> Ringing ... -> Dismissed -> Idle
+//
+// Times are milliseconds from a monotonic timer (e.g. `embassy_time::Instant`)
+// so that the state machine can be tested on the host.
+
+// Alarms ringing (bit mask)
+pub const ALARM1_FIRED: u8 = 1 << 0;
+pub const ALARM2_FIRED: u8 = 1 << 1;
+
+// Button held this long dismisses (shorter press snoozes)
+pub const LONG_PRESS_MS: u64 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RingConfig {
+    pub snooze_ms: u64,
+    // Stop ringing (dismiss) if not acknowledged
+    pub max_ring_ms: u64,
+}
+
+impl RingConfig {
+    pub const fn from_minutes(snooze: u8, max_ring: u8) -> Self {
+        RingConfig {
+            snooze_ms: snooze as u64 * 60_000,
+            max_ring_ms: max_ring as u64 * 60_000,
+        }
+    }
+}
+
+impl Default for RingConfig {
+    fn default() -> Self {
+        RingConfig::from_minutes(9, 5)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlarmState {
+    Idle,
+    Ringing { alarms: u8, since_ms: u64 },
+    Snoozed { alarms: u8, until_ms: u64 },
+    Dismissed,
+}
+
+impl AlarmState {
+    // Alarms ringing now (for the LED)
+    pub fn ringing(&self) -> u8 {
+        match self {
+            AlarmState::Ringing { alarms, .. } => *alarms,
+            _ => 0,
+        }
+    }
+}
+
+impl defmt::Format for AlarmState {
+    fn format(&self, fmt: defmt::Formatter) {
+        match self {
+            AlarmState::Idle => defmt::write!(fmt, "Idle"),
+            AlarmState::Ringing { alarms, .. } => defmt::write!(fmt, "Ringing({})", alarms),
+            AlarmState::Snoozed { alarms, .. } => defmt::write!(fmt, "Snoozed({})", alarms),
+            AlarmState::Dismissed => defmt::write!(fmt, "Dismissed"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RingEvent {
+    // Alarm(s) matched
+    Fire(u8),
+    // Alarm(s) deleted/cleared while ringing
+    Cancel(u8),
+    // Short press
+    Snooze,
+    // Long press
+    Dismiss,
+}
+
+impl defmt::Format for RingEvent {
+    fn format(&self, fmt: defmt::Formatter) {
+        match self {
+            RingEvent::Fire(alarms) => defmt::write!(fmt, "Fire({})", alarms),
+            RingEvent::Cancel(alarms) => defmt::write!(fmt, "Cancel({})", alarms),
+            RingEvent::Snooze => defmt::write!(fmt, "Snooze"),
+            RingEvent::Dismiss => defmt::write!(fmt, "Dismiss"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Ringer {
+    pub config: RingConfig,
+    state: AlarmState,
+}
+
+impl Ringer {
+    pub const fn new(config: RingConfig) -> Self {
+        Ringer {
+            config,
+            state: AlarmState::Idle,
+        }
+    }
+
+    pub fn state(&self) -> AlarmState {
+        self.state
+    }
+
+    pub fn handle(&mut self, event: RingEvent, now_ms: u64) -> AlarmState {
+        self.state = match (self.state, event) {
+            (AlarmState::Ringing { alarms, since_ms }, RingEvent::Fire(fired)) => {
+                AlarmState::Ringing {
+                    alarms: alarms | fired,
+                    since_ms,
+                }
+            }
+            (AlarmState::Snoozed { alarms, .. }, RingEvent::Fire(fired)) => AlarmState::Ringing {
+                alarms: alarms | fired,
+                since_ms: now_ms,
+            },
+            (_, RingEvent::Fire(fired)) => AlarmState::Ringing {
+                alarms: fired,
+                since_ms: now_ms,
+            },
+            (AlarmState::Ringing { alarms, .. }, RingEvent::Snooze) => AlarmState::Snoozed {
+                alarms,
+                until_ms: now_ms + self.config.snooze_ms,
+            },
+            (AlarmState::Ringing { .. } | AlarmState::Snoozed { .. }, RingEvent::Dismiss) => {
+                AlarmState::Dismissed
+            }
+            (AlarmState::Ringing { alarms, since_ms }, RingEvent::Cancel(cancel)) => {
+                match alarms & !cancel {
+                    0 => AlarmState::Idle,
+                    alarms => AlarmState::Ringing { alarms, since_ms },
+                }
+            }
+            (AlarmState::Snoozed { alarms, until_ms }, RingEvent::Cancel(cancel)) => {
+                match alarms & !cancel {
+                    0 => AlarmState::Idle,
+                    alarms => AlarmState::Snoozed { alarms, until_ms },
+                }
+            }
+            (state, _) => state,
+        };
+        self.state
+    }
+
+    // Handle timeouts - call periodically
+    pub fn tick(&mut self, now_ms: u64) -> AlarmState {
+        self.state = match self.state {
+            AlarmState::Ringing { since_ms, .. }
+                if now_ms >= since_ms + self.config.max_ring_ms =>
+            {
+                AlarmState::Dismissed
+            }
+            AlarmState::Snoozed { alarms, until_ms } if now_ms >= until_ms => AlarmState::Ringing {
+                alarms,
+                since_ms: now_ms,
+            },
+            AlarmState::Dismissed => AlarmState::Idle,
+            state => state,
+        };
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN: u64 = 60_000;
+
+    #[test]
+    fn snooze_then_dismiss() {
+        let mut ringer = Ringer::new(RingConfig::from_minutes(9, 5));
+        assert_eq!(ringer.handle(RingEvent::Snooze, 0), AlarmState::Idle);
+        assert_eq!(ringer.handle(RingEvent::Fire(ALARM1_FIRED), 0).ringing(), 1);
+        let snoozed = ringer.handle(RingEvent::Snooze, MIN);
+        assert_eq!(
+            snoozed,
+            AlarmState::Snoozed {
+                alarms: ALARM1_FIRED,
+                until_ms: 10 * MIN
+            }
+        );
+        assert_eq!(ringer.tick(10 * MIN - 1), snoozed);
+        assert_eq!(
+            ringer.tick(10 * MIN),
+            AlarmState::Ringing {
+                alarms: ALARM1_FIRED,
+                since_ms: 10 * MIN
+            }
+        );
+        assert_eq!(
+            ringer.handle(RingEvent::Dismiss, 11 * MIN),
+            AlarmState::Dismissed
+        );
+        assert_eq!(ringer.tick(11 * MIN), AlarmState::Idle);
+    }
+
+    #[test]
+    fn ring_timeout_and_cancel() {
+        let mut ringer = Ringer::new(RingConfig::from_minutes(9, 5));
+        ringer.handle(RingEvent::Fire(ALARM1_FIRED), 0);
+        ringer.handle(RingEvent::Fire(ALARM2_FIRED), MIN);
+        assert_eq!(ringer.state().ringing(), ALARM1_FIRED | ALARM2_FIRED);
+        // Max ring time counts from the first alarm
+        assert_eq!(ringer.tick(5 * MIN), AlarmState::Dismissed);
+        assert_eq!(ringer.tick(5 * MIN), AlarmState::Idle);
+        ringer.handle(RingEvent::Fire(ALARM1_FIRED | ALARM2_FIRED), 0);
+        assert_eq!(
+            ringer.handle(RingEvent::Cancel(ALARM2_FIRED), 0).ringing(),
+            1
+        );
+        assert_eq!(
+            ringer.handle(RingEvent::Cancel(ALARM1_FIRED), 0),
+            AlarmState::Idle
+        );
+    }
+}
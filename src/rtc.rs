@@ -0,0 +1,327 @@
+use crate::alarm::AlarmMatch;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Timelike};
+use core::convert::Infallible;
+use core::fmt::Debug;
+use core::ops::RangeInclusive;
+
+// Years the clock can be set to (the DS3231 counts from 2000 to 2199)
+pub const YEARS: RangeInclusive<i32> = 2000..=2199;
+
+// Hardware independent interface to the clock source used by the RTC task.
+// Only used with concrete types from a single executor, so the futures don't
+// need to be `Send`.
+#[allow(async_fn_in_trait)]
+pub trait RtcBackend {
+    type Error: Debug;
+
+    // Start the oscillator and configure interrupts
+    async fn init(&mut self) -> Result<(), Self::Error>;
+
+    async fn datetime(&mut self) -> Result<NaiveDateTime, Self::Error>;
+    async fn set_datetime(&mut self, dt: &NaiveDateTime) -> Result<(), Self::Error>;
+
+    // None if alarm 1 is disabled
+    async fn alarm1(&mut self) -> Result<Option<AlarmMatch>, Self::Error>;
+    async fn set_alarm1(&mut self, m: AlarmMatch) -> Result<(), Self::Error>;
+    async fn clear_alarm1(&mut self) -> Result<(), Self::Error>;
+    async fn alarm1_matched(&mut self) -> Result<bool, Self::Error>;
+    async fn clear_alarm1_matched(&mut self) -> Result<(), Self::Error>;
+
+    // Alarm 2 has minute resolution (seconds ignored)
+    async fn alarm2(&mut self) -> Result<Option<NaiveTime>, Self::Error>;
+    async fn set_alarm2(&mut self, t: NaiveTime) -> Result<(), Self::Error>;
+    async fn clear_alarm2(&mut self) -> Result<(), Self::Error>;
+    async fn alarm2_matched(&mut self) -> Result<bool, Self::Error>;
+    async fn clear_alarm2_matched(&mut self) -> Result<(), Self::Error>;
+
+    // (alarm 1, alarm 2) matched - backends with both flags in one register
+    // read them together
+    async fn alarms_matched(&mut self) -> Result<(bool, bool), Self::Error> {
+        Ok((self.alarm1_matched().await?, self.alarm2_matched().await?))
+    }
+
+    // None if the backend has no temperature sensor
+    async fn temperature(&mut self) -> Result<Option<f32>, Self::Error>;
+    // Start a temperature conversion now, so that `temperature` returns a fresh
+    // reading once it completes. Returns false if the backend can't.
+    async fn start_temperature_conversion(&mut self) -> Result<bool, Self::Error>;
+    async fn temperature_converting(&mut self) -> Result<bool, Self::Error>;
+
+    // Crystal aging offset register (None if the backend has no aging trim).
+    // Setting it is ignored by backends without one.
+    async fn aging(&mut self) -> Result<Option<i8>, Self::Error>;
+    async fn set_aging(&mut self, offset: i8) -> Result<(), Self::Error>;
+
+    // Output a 1Hz square wave on the interrupt pin instead of alarm interrupts
+    // (falling edge on each seconds rollover). Alarm flags are still set and
+    // must be polled. Returns false if the backend has no such output.
+    async fn enable_square_wave(&mut self) -> Result<bool, Self::Error>;
+
+    // True if the oscillator has stopped since the time was last set (time invalid)
+    async fn oscillator_stopped(&mut self) -> Result<bool, Self::Error>;
+    async fn clear_oscillator_stopped(&mut self) -> Result<(), Self::Error>;
+}
+
+// Alarm matched in software, for backends without alarm hardware.
+// `check` must be called with each new reading of the clock.
+#[derive(Clone, Debug, Default)]
+pub struct SoftAlarm {
+    alarm: Option<AlarmMatch>,
+    last: Option<NaiveDateTime>,
+    matched: bool,
+}
+
+impl SoftAlarm {
+    pub const fn new() -> Self {
+        SoftAlarm {
+            alarm: None,
+            last: None,
+            matched: false,
+        }
+    }
+
+    pub fn alarm(&self) -> Option<AlarmMatch> {
+        self.alarm
+    }
+
+    // Daily alarm time
+    pub fn time(&self) -> Option<NaiveTime> {
+        match self.alarm {
+            Some(AlarmMatch::Time(t)) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, m: AlarmMatch) {
+        self.alarm = Some(m);
+        self.matched = false;
+    }
+
+    pub fn clear(&mut self) {
+        self.alarm = None;
+        self.matched = false;
+    }
+
+    pub fn matched(&self) -> bool {
+        self.matched
+    }
+
+    pub fn clear_matched(&mut self) {
+        self.matched = false;
+    }
+
+    // Match any second in (last, now]. Large jumps (clock set) are ignored
+    // so that setting the time does not fire the alarm.
+    pub fn check(&mut self, now: NaiveDateTime) {
+        let now = now.with_nanosecond(0).unwrap();
+        if let (Some(m), Some(last)) = (self.alarm, self.last) {
+            if now > last && now - last <= TimeDelta::minutes(1) {
+                let mut t = last;
+                while t < now {
+                    t += TimeDelta::seconds(1);
+                    if m.matches(&t) {
+                        self.matched = true;
+                    }
+                }
+            }
+        }
+        self.last = Some(now);
+    }
+}
+
+// Software clock counting from a monotonic millisecond timer
+// (e.g. `embassy_time::Instant::now().as_millis()`).
+pub struct SoftRtc {
+    base: NaiveDateTime,
+    base_ms: u64,
+    now_ms: fn() -> u64,
+    alarm1: SoftAlarm,
+    alarm2: SoftAlarm,
+    stopped: bool,
+}
+
+impl SoftRtc {
+    // Starts at 00:00:00 01/01/2000 with the oscillator-stopped flag set
+    pub fn new(now_ms: fn() -> u64) -> Self {
+        SoftRtc {
+            base: NaiveDate::from_ymd_opt(2000, 1, 1)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+            base_ms: now_ms(),
+            now_ms,
+            alarm1: SoftAlarm::new(),
+            alarm2: SoftAlarm::new(),
+            stopped: true,
+        }
+    }
+}
+
+impl RtcBackend for SoftRtc {
+    type Error = Infallible;
+
+    async fn init(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn datetime(&mut self) -> Result<NaiveDateTime, Self::Error> {
+        let elapsed = (self.now_ms)() - self.base_ms;
+        let now = self.base + TimeDelta::milliseconds(elapsed as i64);
+        self.alarm1.check(now);
+        self.alarm2.check(now);
+        Ok(now)
+    }
+
+    async fn set_datetime(&mut self, dt: &NaiveDateTime) -> Result<(), Self::Error> {
+        self.base = *dt;
+        self.base_ms = (self.now_ms)();
+        Ok(())
+    }
+
+    async fn alarm1(&mut self) -> Result<Option<AlarmMatch>, Self::Error> {
+        Ok(self.alarm1.alarm())
+    }
+
+    async fn set_alarm1(&mut self, m: AlarmMatch) -> Result<(), Self::Error> {
+        self.alarm1.set(m);
+        Ok(())
+    }
+
+    async fn clear_alarm1(&mut self) -> Result<(), Self::Error> {
+        self.alarm1.clear();
+        Ok(())
+    }
+
+    async fn alarm1_matched(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.alarm1.matched())
+    }
+
+    async fn clear_alarm1_matched(&mut self) -> Result<(), Self::Error> {
+        self.alarm1.clear_matched();
+        Ok(())
+    }
+
+    async fn alarm2(&mut self) -> Result<Option<NaiveTime>, Self::Error> {
+        Ok(self.alarm2.time())
+    }
+
+    async fn set_alarm2(&mut self, t: NaiveTime) -> Result<(), Self::Error> {
+        self.alarm2.set(AlarmMatch::Time(t.with_second(0).unwrap()));
+        Ok(())
+    }
+
+    async fn clear_alarm2(&mut self) -> Result<(), Self::Error> {
+        self.alarm2.clear();
+        Ok(())
+    }
+
+    async fn alarm2_matched(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.alarm2.matched())
+    }
+
+    async fn clear_alarm2_matched(&mut self) -> Result<(), Self::Error> {
+        self.alarm2.clear_matched();
+        Ok(())
+    }
+
+    async fn temperature(&mut self) -> Result<Option<f32>, Self::Error> {
+        Ok(None)
+    }
+
+    async fn start_temperature_conversion(&mut self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    async fn temperature_converting(&mut self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    async fn aging(&mut self) -> Result<Option<i8>, Self::Error> {
+        Ok(None)
+    }
+
+    async fn set_aging(&mut self, _offset: i8) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn enable_square_wave(&mut self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    async fn oscillator_stopped(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.stopped)
+    }
+
+    async fn clear_oscillator_stopped(&mut self) -> Result<(), Self::Error> {
+        self.stopped = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_on;
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    static NOW_MS: AtomicU64 = AtomicU64::new(0);
+
+    fn now_ms() -> u64 {
+        NOW_MS.load(Ordering::Relaxed)
+    }
+
+    fn dt(h: u32, m: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 3, 1)
+            .unwrap()
+            .and_hms_opt(h, m, s)
+            .unwrap()
+    }
+
+    #[test]
+    fn soft_alarm_matches_once() {
+        let mut alarm = SoftAlarm::new();
+        alarm.set(AlarmMatch::Time(NaiveTime::from_hms_opt(7, 0, 0).unwrap()));
+        alarm.check(dt(6, 59, 59));
+        assert!(!alarm.matched());
+        alarm.check(dt(7, 0, 0));
+        assert!(alarm.matched());
+        alarm.clear_matched();
+        alarm.check(dt(7, 0, 1));
+        assert!(!alarm.matched());
+        // Setting the clock past the alarm does not fire it
+        alarm.check(dt(8, 0, 0));
+        alarm.check(dt(6, 0, 0));
+        assert!(!alarm.matched());
+    }
+
+    #[test]
+    fn soft_rtc_counts_from_timer() {
+        block_on(async {
+            NOW_MS.store(5_000, Ordering::Relaxed);
+            let mut rtc = SoftRtc::new(now_ms);
+            assert_eq!(rtc.oscillator_stopped().await, Ok(true));
+            rtc.set_datetime(&dt(6, 59, 58)).await.unwrap();
+            rtc.clear_oscillator_stopped().await.unwrap();
+            rtc.set_alarm1(AlarmMatch::Time(NaiveTime::from_hms_opt(7, 0, 0).unwrap()))
+                .await
+                .unwrap();
+            // Alarm 2 ignores seconds
+            rtc.set_alarm2(NaiveTime::from_hms_opt(7, 0, 30).unwrap())
+                .await
+                .unwrap();
+            rtc.datetime().await.unwrap();
+            NOW_MS.store(7_500, Ordering::Relaxed);
+            assert_eq!(
+                rtc.datetime().await,
+                Ok(dt(7, 0, 0) + TimeDelta::milliseconds(500))
+            );
+            assert_eq!(rtc.alarm1_matched().await, Ok(true));
+            assert_eq!(rtc.alarm2_matched().await, Ok(true));
+            rtc.clear_alarm2().await.unwrap();
+            assert_eq!(rtc.alarm2().await, Ok(None));
+            assert_eq!(rtc.alarm2_matched().await, Ok(false));
+            assert_eq!(rtc.oscillator_stopped().await, Ok(false));
+            assert_eq!(rtc.temperature().await, Ok(None));
+        });
+    }
+}
@@ -0,0 +1,185 @@
+// RTC health, tracked by rtc_task for the display warning and `rtc status`
+//
+// The oscillator stop flag (OSF) is set when the oscillator stopped since the
+// time was last set, so the time is invalid until it is set again. Finding it
+// set at power up means the clock lost power completely - the backup battery
+// is flat or missing (or this is the first power up).
+use chrono::NaiveDateTime;
+use core::fmt;
+
+// Consecutive failed transfers before the RTC is reported as not responding
+pub const MAX_CONSECUTIVE_ERRORS: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RtcState {
+    Ok,
+    TimeInvalid,
+    NotResponding,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RtcHealth {
+    // OSF set - time invalid until set
+    pub oscillator_stopped: bool,
+    // OSF was set at power up
+    pub battery_suspect: bool,
+    pub read_errors: u32,
+    pub write_errors: u32,
+    // Errors since the last successful transfer
+    pub consecutive_errors: u32,
+    // Last successful read of the time (UTC)
+    pub last_good_read: Option<NaiveDateTime>,
+}
+
+impl RtcHealth {
+    pub const fn new() -> Self {
+        RtcHealth {
+            oscillator_stopped: false,
+            battery_suspect: false,
+            read_errors: 0,
+            write_errors: 0,
+            consecutive_errors: 0,
+            last_good_read: None,
+        }
+    }
+
+    // OSF as read at power up
+    pub fn powered_up(&mut self, stopped: bool) {
+        self.oscillator_stopped = stopped;
+        self.battery_suspect = stopped;
+    }
+
+    // OSF as read periodically - returns true if it has just been set
+    pub fn oscillator(&mut self, stopped: bool) -> bool {
+        let newly_stopped = stopped && !self.oscillator_stopped;
+        self.oscillator_stopped = stopped;
+        self.consecutive_errors = 0;
+        newly_stopped
+    }
+
+    // Time set (and OSF cleared). The battery stays suspect until the next
+    // power up shows the time was kept.
+    pub fn time_set(&mut self) {
+        self.oscillator_stopped = false;
+        self.consecutive_errors = 0;
+    }
+
+    pub fn read_ok(&mut self, now: NaiveDateTime) {
+        self.last_good_read = Some(now);
+        self.consecutive_errors = 0;
+    }
+
+    pub fn read_error(&mut self) {
+        self.read_errors = self.read_errors.saturating_add(1);
+        self.consecutive_errors = self.consecutive_errors.saturating_add(1);
+    }
+
+    pub fn write_error(&mut self) {
+        self.write_errors = self.write_errors.saturating_add(1);
+        self.consecutive_errors = self.consecutive_errors.saturating_add(1);
+    }
+
+    pub fn state(&self) -> RtcState {
+        if self.consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+            RtcState::NotResponding
+        } else if self.oscillator_stopped {
+            RtcState::TimeInvalid
+        } else {
+            RtcState::Ok
+        }
+    }
+
+    // Short warning for the display (None when healthy)
+    pub fn warning(&self) -> Option<&'static str> {
+        match self.state() {
+            RtcState::Ok => None,
+            RtcState::TimeInvalid => Some("Set time"),
+            RtcState::NotResponding => Some("RTC error"),
+        }
+    }
+}
+
+// `rtc status` report
+impl fmt::Display for RtcHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.state() {
+            RtcState::Ok => write!(f, "State: OK")?,
+            RtcState::TimeInvalid => write!(f, "State: Time invalid (set the time)")?,
+            RtcState::NotResponding => write!(f, "State: Not responding")?,
+        }
+        match self.oscillator_stopped {
+            true => write!(f, "\r\nOscillator: Stopped since the time was set")?,
+            false => write!(f, "\r\nOscillator: Running")?,
+        }
+        match self.battery_suspect {
+            true => write!(f, "\r\nBattery: Suspect (time lost at power up)")?,
+            false => write!(f, "\r\nBattery: OK")?,
+        }
+        write!(
+            f,
+            "\r\nErrors: {} read, {} write ({} consecutive)",
+            self.read_errors, self.write_errors, self.consecutive_errors
+        )?;
+        match self.last_good_read {
+            Some(t) => write!(f, "\r\nLast good read: {} UTC", t),
+            None => write!(f, "\r\nLast good read: Never"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(h: u32, m: u32, s: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 17)
+            .unwrap()
+            .and_hms_opt(h, m, s)
+            .unwrap()
+    }
+
+    #[test]
+    fn health_state() {
+        let mut health = RtcHealth::new();
+        health.powered_up(true);
+        assert_eq!(health.state(), RtcState::TimeInvalid);
+        assert_eq!(health.warning(), Some("Set time"));
+        health.time_set();
+        assert_eq!(health.state(), RtcState::Ok);
+        assert!(health.battery_suspect);
+
+        // Not responding after repeated errors, until a good read
+        health.read_ok(at(9, 0, 0));
+        health.read_error();
+        health.write_error();
+        assert_eq!(health.state(), RtcState::Ok);
+        health.read_error();
+        assert_eq!(health.state(), RtcState::NotResponding);
+        assert_eq!(health.warning(), Some("RTC error"));
+        health.read_ok(at(9, 0, 3));
+        assert_eq!(health.state(), RtcState::Ok);
+        assert_eq!((health.read_errors, health.write_errors), (2, 1));
+
+        // Oscillator stopping later is only reported once
+        assert!(health.oscillator(true));
+        assert!(!health.oscillator(true));
+        assert_eq!(health.state(), RtcState::TimeInvalid);
+    }
+
+    #[test]
+    fn status_report() {
+        let mut health = RtcHealth::new();
+        health.powered_up(false);
+        health.read_error();
+        health.read_ok(at(12, 30, 0));
+        assert_eq!(
+            health.to_string(),
+            "State: OK\r\n\
+             Oscillator: Running\r\n\
+             Battery: OK\r\n\
+             Errors: 1 read, 0 write (0 consecutive)\r\n\
+             Last good read: 2026-10-17 12:30:00 UTC"
+        );
+    }
+}
@@ -0,0 +1,469 @@
+use crate::alarm::{alarm_name, AlarmTable, Schedule, MAX_ALARMS};
+use crate::drift::{DriftLog, Measurement, MAX_MEASUREMENTS};
+use crate::tz::{TimeZone, TZ_LEN};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use core::fmt::{self, Debug};
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+mod eeprom;
+mod flash;
+pub use eeprom::EepromStore;
+pub use flash::FlashJournal;
+
+// User settings which survive a reset (alarm 2 lives in the RTC itself)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub alarms: AlarmTable<MAX_ALARMS>,
+    // Minutes
+    pub snooze: u8,
+    pub ring_time: u8,
+    pub date_format: DateFormat,
+    pub colour: Colour,
+    // Percent
+    pub brightness: u8,
+    // Local time zone (the RTC runs in UTC)
+    pub tz: TimeZone,
+    // Host sync history for RTC aging calibration
+    pub drift: DriftLog,
+    // Minutes between temperature samples
+    pub temp_interval: u8,
+}
+
+impl Settings {
+    pub const fn new() -> Self {
+        Settings {
+            alarms: AlarmTable::new(),
+            snooze: 9,
+            ring_time: 5,
+            date_format: DateFormat::Dmy,
+            colour: Colour::Green,
+            brightness: 100,
+            tz: TimeZone::utc(),
+            drift: DriftLog::new(),
+            temp_interval: 1,
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings::new()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error<E> {
+    Storage(E),
+    // The record doesn't fit the space for it
+    Encode,
+}
+
+// Persistent storage for the settings record. Like `RtcBackend` it is only
+// used from a single executor, so the futures don't need to be `Send`.
+#[allow(async_fn_in_trait)]
+pub trait SettingsStore {
+    type Error: Debug;
+
+    // None if nothing (valid) has been saved
+    async fn load(&mut self) -> Result<Option<Settings>, Self::Error>;
+    async fn save(&mut self, settings: &Settings) -> Result<(), Self::Error>;
+    // Erase saved settings
+    async fn reset(&mut self) -> Result<(), Self::Error>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DateFormat {
+    // DD/MM/YYYY
+    Dmy,
+    // YYYY-MM-DD
+    Ymd,
+    // MM/DD/YYYY
+    Mdy,
+}
+
+impl DateFormat {
+    pub const ALL: [DateFormat; 3] = [DateFormat::Dmy, DateFormat::Ymd, DateFormat::Mdy];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DateFormat::Dmy => "dmy",
+            DateFormat::Ymd => "ymd",
+            DateFormat::Mdy => "mdy",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    pub fn write<W: fmt::Write>(&self, w: &mut W, d: NaiveDate) -> fmt::Result {
+        let (y, m, d) = (d.year(), d.month(), d.day());
+        match self {
+            DateFormat::Dmy => write!(w, "{:02}/{:02}/{:04}", d, m, y),
+            DateFormat::Ymd => write!(w, "{:04}-{:02}-{:02}", y, m, d),
+            DateFormat::Mdy => write!(w, "{:02}/{:02}/{:04}", m, d, y),
+        }
+    }
+}
+
+// Clock digit colour (mapped to the display colour by the firmware)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Colour {
+    Green,
+    Red,
+    Blue,
+    Yellow,
+    Cyan,
+    Magenta,
+    Black,
+}
+
+impl Colour {
+    pub const ALL: [Colour; 7] = [
+        Colour::Green,
+        Colour::Red,
+        Colour::Blue,
+        Colour::Yellow,
+        Colour::Cyan,
+        Colour::Magenta,
+        Colour::Black,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Colour::Green => "green",
+            Colour::Red => "red",
+            Colour::Blue => "blue",
+            Colour::Yellow => "yellow",
+            Colour::Cyan => "cyan",
+            Colour::Magenta => "magenta",
+            Colour::Black => "black",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+}
+
+// Record layout (little endian):
+//
+//   magic: u16 | version: u8 | length: u16 | payload[length] | crc32: u32
+//
+// The CRC covers the header and payload. Records with an unknown version are
+// ignored (defaults are used) rather than misinterpreted. Version 2 appends the
+// time zone rule, version 3 the drift log and version 4 the temperature sample
+// interval; older records are still read.
+const MAGIC: u16 = 0x5354;
+const VERSION: u8 = 4;
+const HEADER_LEN: usize = 5;
+const CRC_LEN: usize = 4;
+// Largest encoded record (every alarm a one-shot with a full length name)
+pub const MAX_RECORD_LEN: usize =
+    HEADER_LEN + 6 + MAX_ALARMS * (2 + 12 + 8) + 1 + TZ_LEN + DRIFT_LEN + 1 + CRC_LEN;
+// Measurement count, measurements and the last sync
+const DRIFT_LEN: usize = 1 + MAX_MEASUREMENTS * 9 + 1 + 9;
+
+const CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Writer<'_> {
+    fn push(&mut self, bytes: &[u8]) -> Option<()> {
+        let end = self.len + bytes.len();
+        self.buf.get_mut(self.len..end)?.copy_from_slice(bytes);
+        self.len = end;
+        Some(())
+    }
+
+    fn time(&mut self, t: NaiveTime) -> Option<()> {
+        self.push(&[t.hour() as u8, t.minute() as u8, t.second() as u8])
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let (head, tail) = (self.buf.get(..n)?, self.buf.get(n..)?);
+        self.buf = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn time(&mut self) -> Option<NaiveTime> {
+        let b = self.take(3)?;
+        NaiveTime::from_hms_opt(b[0] as u32, b[1] as u32, b[2] as u32)
+    }
+}
+
+fn encode_schedule(w: &mut Writer, schedule: &Schedule) -> Option<()> {
+    match *schedule {
+        Schedule::Daily(t) => {
+            w.push(&[0])?;
+            w.time(t)
+        }
+        Schedule::Days(mask, t) => {
+            w.push(&[1, mask])?;
+            w.time(t)
+        }
+        Schedule::Once(dt) => {
+            w.push(&[2])?;
+            w.push(&(dt.year() as u16).to_le_bytes())?;
+            w.push(&[dt.month() as u8, dt.day() as u8])?;
+            w.time(dt.time())
+        }
+        Schedule::Hourly(m) => w.push(&[3, m]),
+    }
+}
+
+fn decode_schedule(r: &mut Reader) -> Option<Schedule> {
+    match r.u8()? {
+        0 => Some(Schedule::Daily(r.time()?)),
+        1 => Some(Schedule::Days(r.u8()?, r.time()?)),
+        2 => {
+            let (y, m, d) = (r.u16()?, r.u8()?, r.u8()?);
+            let date = NaiveDate::from_ymd_opt(y as i32, m as u32, d as u32)?;
+            Some(Schedule::Once(NaiveDateTime::new(date, r.time()?)))
+        }
+        3 => Some(Schedule::Hourly(r.u8()?)),
+        _ => None,
+    }
+}
+
+fn encode_drift(w: &mut Writer, drift: &DriftLog) -> Option<()> {
+    w.push(&[drift.measurements().count() as u8])?;
+    for m in drift.measurements() {
+        w.push(&m.interval_s.to_le_bytes())?;
+        w.push(&m.error_ms.to_le_bytes())?;
+        w.push(&[m.aging as u8])?;
+    }
+    match drift.last_sync() {
+        Some((at, aging)) => {
+            w.push(&[1])?;
+            w.push(&at.and_utc().timestamp().to_le_bytes())?;
+            w.push(&[aging as u8])
+        }
+        None => w.push(&[0]),
+    }
+}
+
+fn decode_drift(r: &mut Reader) -> Option<DriftLog> {
+    let mut drift = DriftLog::new();
+    for _ in 0..r.u8()? {
+        drift.push(Measurement {
+            interval_s: u32::from_le_bytes(r.bytes()?),
+            error_ms: i32::from_le_bytes(r.bytes()?),
+            aging: r.u8()? as i8,
+        });
+    }
+    if r.u8()? != 0 {
+        let at = DateTime::from_timestamp(i64::from_le_bytes(r.bytes()?), 0)?.naive_utc();
+        drift.set_last_sync(Some((at, r.u8()? as i8)));
+    }
+    Some(drift)
+}
+
+// Encode into `buf`, returning the record length (None if `buf` is too small)
+pub fn encode(settings: &Settings, buf: &mut [u8]) -> Option<usize> {
+    let mut w = Writer { buf, len: 0 };
+    w.push(&MAGIC.to_le_bytes())?;
+    w.push(&[VERSION, 0, 0])?;
+    w.push(&[
+        settings.snooze,
+        settings.ring_time,
+        settings.date_format as u8,
+        settings.colour as u8,
+        settings.brightness,
+        settings.alarms.len() as u8,
+    ])?;
+    for alarm in settings.alarms.iter() {
+        w.push(&[alarm.name.len() as u8])?;
+        w.push(alarm.name.as_bytes())?;
+        w.push(&[alarm.enabled as u8])?;
+        encode_schedule(&mut w, &alarm.schedule)?;
+    }
+    let rule = settings.tz.rule();
+    w.push(&[rule.len() as u8])?;
+    w.push(rule.as_bytes())?;
+    encode_drift(&mut w, &settings.drift)?;
+    w.push(&[settings.temp_interval])?;
+    let payload_len = (w.len - HEADER_LEN) as u16;
+    w.buf[3..5].copy_from_slice(&payload_len.to_le_bytes());
+    let crc = CRC.checksum(&w.buf[..w.len]);
+    w.push(&crc.to_le_bytes())?;
+    Some(w.len)
+}
+
+// Decode a record from the start of `buf` (None if missing, corrupt or from
+// an incompatible version)
+pub fn decode(buf: &[u8]) -> Option<Settings> {
+    let mut r = Reader { buf };
+    let (magic, version, len) = (r.u16()?, r.u8()?, r.u16()? as usize);
+    if magic != MAGIC || !(1..=VERSION).contains(&version) {
+        return None;
+    }
+    let payload = r.take(len)?;
+    let crc = r.take(CRC_LEN)?;
+    if CRC.checksum(&buf[..HEADER_LEN + len]).to_le_bytes() != crc {
+        return None;
+    }
+    let mut r = Reader { buf: payload };
+    let mut settings = Settings {
+        snooze: r.u8()?,
+        ring_time: r.u8()?,
+        date_format: *DateFormat::ALL.get(r.u8()? as usize)?,
+        colour: *Colour::ALL.get(r.u8()? as usize)?,
+        brightness: r.u8()?,
+        ..Settings::new()
+    };
+    for _ in 0..r.u8()? {
+        let len = r.u8()? as usize;
+        let name = alarm_name(core::str::from_utf8(r.take(len)?).ok()?)?;
+        let enabled = r.u8()? != 0;
+        let schedule = decode_schedule(&mut r)?;
+        settings.alarms.add(name.clone(), schedule).ok()?;
+        settings.alarms.set_enabled(&name, enabled).ok()?;
+    }
+    if version >= 2 {
+        let len = r.u8()? as usize;
+        settings.tz = TimeZone::parse(core::str::from_utf8(r.take(len)?).ok()?)?;
+    }
+    if version >= 3 {
+        settings.drift = decode_drift(&mut r)?;
+    }
+    if version >= 4 {
+        settings.temp_interval = r.u8()?;
+    }
+    Some(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alarm::WEEKDAYS;
+    use chrono::TimeDelta;
+
+    pub(crate) fn sample() -> Settings {
+        let t = NaiveTime::from_hms_opt(7, 0, 0).unwrap();
+        let mut settings = Settings {
+            snooze: 10,
+            date_format: DateFormat::Ymd,
+            colour: Colour::Cyan,
+            brightness: 40,
+            temp_interval: 5,
+            tz: TimeZone::parse("GMT0BST,M3.5.0/1,M10.5.0").unwrap(),
+            ..Settings::new()
+        };
+        let alarms = &mut settings.alarms;
+        alarms
+            .add(alarm_name("wake").unwrap(), Schedule::Days(WEEKDAYS, t))
+            .unwrap();
+        alarms
+            .add(alarm_name("hourly").unwrap(), Schedule::Hourly(30))
+            .unwrap();
+        let dt = NaiveDate::from_ymd_opt(2026, 12, 24).unwrap().and_time(t);
+        alarms
+            .add(alarm_name("xmas").unwrap(), Schedule::Once(dt))
+            .unwrap();
+        alarms.set_enabled("hourly", false).unwrap();
+        settings.drift.synced(dt, None, 0);
+        settings
+            .drift
+            .synced(dt + TimeDelta::days(1), Some(-432), 0);
+        settings
+    }
+
+    #[test]
+    fn round_trip() {
+        let settings = sample();
+        let mut buf = [0xff; MAX_RECORD_LEN];
+        let len = encode(&settings, &mut buf).unwrap();
+        assert_eq!(decode(&buf[..len]), Some(settings));
+        assert_eq!(decode(&buf), decode(&buf[..len]));
+        // Largest record fits
+        let mut full = Settings::new();
+        for i in 0..MAX_ALARMS {
+            let mut name = alarm_name("abcdefghij").unwrap();
+            name.push_str(if i < 10 { "0" } else { "1" }).unwrap();
+            name.push(char::from(b'0' + (i % 10) as u8)).unwrap();
+            let dt = NaiveDate::from_ymd_opt(2026, 1, 1)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap();
+            full.alarms.add(name, Schedule::Once(dt)).unwrap();
+        }
+        full.tz = TimeZone::parse("<+1030>-10:30<+11>-11,M10.1.0/02:00:00,M4.1.0/03").unwrap();
+        let dt = NaiveDate::from_ymd_opt(2026, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        for d in 0..=MAX_MEASUREMENTS as i64 {
+            full.drift.synced(dt + TimeDelta::days(d), Some(100), -5);
+        }
+        assert_eq!(encode(&full, &mut buf), Some(MAX_RECORD_LEN));
+    }
+
+    #[test]
+    fn version1_record() {
+        // Version 1 had no time zone (5 bytes), drift log (2 bytes when empty)
+        // or temperature interval (1 byte)
+        let mut settings = sample();
+        settings.tz = TimeZone::utc();
+        settings.drift = DriftLog::new();
+        settings.temp_interval = 1;
+        let mut buf = [0xff; MAX_RECORD_LEN];
+        let len = encode(&settings, &mut buf).unwrap() - CRC_LEN - 5 - 2 - 1;
+        buf[2] = 1;
+        buf[3..5].copy_from_slice(&((len - HEADER_LEN) as u16).to_le_bytes());
+        let crc = CRC.checksum(&buf[..len]);
+        buf[len..len + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+        assert_eq!(decode(&buf[..len + CRC_LEN]), Some(settings));
+    }
+
+    #[test]
+    fn corrupt_records_rejected() {
+        let mut buf = [0xff; MAX_RECORD_LEN];
+        assert_eq!(decode(&buf), None);
+        let len = encode(&sample(), &mut buf).unwrap();
+        buf[HEADER_LEN + 2] ^= 1;
+        assert_eq!(decode(&buf[..len]), None);
+        buf[HEADER_LEN + 2] ^= 1;
+        // Future version
+        buf[2] = VERSION + 1;
+        assert_eq!(decode(&buf[..len]), None);
+        assert_eq!(encode(&sample(), &mut buf[..20]), None);
+    }
+
+    #[test]
+    fn date_formats() {
+        let d = NaiveDate::from_ymd_opt(2026, 3, 4).unwrap();
+        let fmt = |f: DateFormat| {
+            let mut s: heapless::String<16> = heapless::String::new();
+            f.write(&mut s, d).unwrap();
+            s
+        };
+        assert_eq!(fmt(DateFormat::Dmy), "04/03/2026");
+        assert_eq!(fmt(DateFormat::Ymd), "2026-03-04");
+        assert_eq!(fmt(DateFormat::Mdy), "03/04/2026");
+        assert_eq!(DateFormat::from_name("ymd"), Some(DateFormat::Ymd));
+        assert_eq!(Colour::from_name("cyan"), Some(Colour::Cyan));
+        assert_eq!(Colour::from_name("white"), None);
+    }
+}
@@ -0,0 +1,93 @@
+use super::{decode, encode, Error, Settings, SettingsStore, MAX_RECORD_LEN};
+use crate::at24c32::{self, At24c32};
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::i2c::I2c;
+
+// Two copies of the record are written in turn so that a reset during a write
+// leaves the other copy intact.
+const COPY_SIZE: u32 = MAX_RECORD_LEN.next_multiple_of(32) as u32;
+
+pub struct EepromStore<I2C, D> {
+    eeprom: At24c32<I2C, D>,
+    offset: u32,
+}
+
+impl<I2C: I2c, D: DelayNs> EepromStore<I2C, D> {
+    // Uses 2 x COPY_SIZE bytes from `offset`
+    pub fn new(eeprom: At24c32<I2C, D>, offset: u32) -> Self {
+        EepromStore { eeprom, offset }
+    }
+
+    pub fn release(self) -> At24c32<I2C, D> {
+        self.eeprom
+    }
+
+    fn copy_offset(&self, copy: u32) -> u32 {
+        self.offset + copy * COPY_SIZE
+    }
+}
+
+impl<I2C: I2c, D: DelayNs> SettingsStore for EepromStore<I2C, D> {
+    type Error = Error<at24c32::Error<I2C::Error>>;
+
+    async fn load(&mut self) -> Result<Option<Settings>, Self::Error> {
+        let mut buf = [0; MAX_RECORD_LEN];
+        for copy in 0..2 {
+            self.eeprom
+                .read(self.copy_offset(copy), &mut buf)
+                .await
+                .map_err(Error::Storage)?;
+            if let Some(settings) = decode(&buf) {
+                return Ok(Some(settings));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn save(&mut self, settings: &Settings) -> Result<(), Self::Error> {
+        let mut buf = [0xff; MAX_RECORD_LEN];
+        let len = encode(settings, &mut buf).ok_or(Error::Encode)?;
+        for copy in 0..2 {
+            self.eeprom
+                .write(self.copy_offset(copy), &buf[..len])
+                .await
+                .map_err(Error::Storage)?;
+        }
+        Ok(())
+    }
+
+    // Invalidate both copies (erased magic)
+    async fn reset(&mut self) -> Result<(), Self::Error> {
+        for copy in 0..2 {
+            self.eeprom
+                .write(self.copy_offset(copy), &[0xff; 2])
+                .await
+                .map_err(Error::Storage)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::at24c32::emulator::{At24c32Emulator, NoDelay};
+    use crate::block_on;
+    use crate::settings::tests::sample;
+
+    #[test]
+    fn eeprom_round_trip() {
+        let mut store = EepromStore::new(At24c32::new(At24c32Emulator::new(), NoDelay), 0);
+        assert_eq!(block_on(store.load()), Ok(None));
+        let settings = sample();
+        block_on(store.save(&settings)).unwrap();
+        assert_eq!(block_on(store.load()), Ok(Some(settings.clone())));
+        // First copy corrupted - second copy used
+        let mut eeprom = store.release().release();
+        eeprom.memory_mut()[10] ^= 0xff;
+        let mut store = EepromStore::new(At24c32::new(eeprom, NoDelay), 0);
+        assert_eq!(block_on(store.load()), Ok(Some(settings)));
+        block_on(store.reset()).unwrap();
+        assert_eq!(block_on(store.load()), Ok(None));
+    }
+}
@@ -0,0 +1,250 @@
+use super::{decode, encode, Error, Settings, SettingsStore, MAX_RECORD_LEN};
+use embedded_storage::nor_flash::NorFlash;
+
+// Each save is appended to the next free slot of the current region, spreading
+// wear over the region. When it fills up the record goes into the other
+// (erased) region and only then is the full one erased, so a reset at any point
+// leaves a valid record. Slots start with a sequence number - the highest
+// valid record wins. It is written ahead of the record in the same write, so a
+// record which checks out has a complete sequence number.
+pub const SLOT_SIZE: usize = 512;
+const SEQ_LEN: usize = 4;
+
+const _: () = assert!(SEQ_LEN + MAX_RECORD_LEN <= SLOT_SIZE);
+
+// (sequence number, record)
+type Record = (u32, Settings);
+
+pub struct FlashJournal<F> {
+    flash: F,
+    // (offset, size)
+    regions: [(u32, u32); 2],
+}
+
+impl<F: NorFlash> FlashJournal<F> {
+    // Each region is given as (offset, size) and must be erase aligned (e.g. a
+    // whole flash sector). They needn't be the same size.
+    pub fn new(flash: F, regions: [(u32, u32); 2]) -> Self {
+        FlashJournal { flash, regions }
+    }
+
+    pub fn release(self) -> F {
+        self.flash
+    }
+
+    fn slots(&self, region: usize) -> u32 {
+        self.regions[region].1 / SLOT_SIZE as u32
+    }
+
+    fn slot_offset(&self, region: usize, slot: u32) -> u32 {
+        self.regions[region].0 + slot * SLOT_SIZE as u32
+    }
+
+    fn read_slot(
+        &mut self,
+        region: usize,
+        slot: u32,
+        buf: &mut [u8; SLOT_SIZE],
+    ) -> Result<(), F::Error> {
+        self.flash.read(self.slot_offset(region, slot), buf)
+    }
+
+    // (last valid record, first erased slot) of a region
+    fn scan(&mut self, region: usize) -> Result<(Option<Record>, Option<u32>), F::Error> {
+        let mut buf = [0; SLOT_SIZE];
+        let mut last = None;
+        for slot in 0..self.slots(region) {
+            self.read_slot(region, slot, &mut buf)?;
+            if buf.iter().all(|b| *b == 0xff) {
+                return Ok((last, Some(slot)));
+            }
+            // Skip records torn by a reset during a write
+            if let Some(settings) = decode(&buf[SEQ_LEN..]) {
+                let seq = u32::from_le_bytes(buf[..SEQ_LEN].try_into().unwrap());
+                last = Some((seq, settings));
+            }
+        }
+        Ok((last, None))
+    }
+
+    // (region, its first erased slot, newest record) - the region with the
+    // newest record is the one being written
+    fn current(&mut self) -> Result<(usize, Option<u32>, Option<Record>), F::Error> {
+        let (a, a_free) = self.scan(0)?;
+        let (b, b_free) = self.scan(1)?;
+        Ok(match (a, b) {
+            (Some(a), Some(b)) if a.0 >= b.0 => (0, a_free, Some(a)),
+            (None, None) => (0, a_free, None),
+            (Some(a), None) => (0, a_free, Some(a)),
+            (_, b) => (1, b_free, b),
+        })
+    }
+
+    // A reset part way through an erase can leave a region neither erased
+    // nor readable
+    fn is_erased(&mut self, region: usize) -> Result<bool, F::Error> {
+        let mut buf = [0; SLOT_SIZE];
+        for slot in 0..self.slots(region) {
+            self.read_slot(region, slot, &mut buf)?;
+            if buf.iter().any(|b| *b != 0xff) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn erase(&mut self, region: usize) -> Result<(), F::Error> {
+        let (offset, size) = self.regions[region];
+        self.flash.erase(offset, offset + size)
+    }
+
+    fn append(&mut self, settings: &Settings) -> Result<(), Error<F::Error>> {
+        let (region, free, last) = self.current().map_err(Error::Storage)?;
+        let seq = last.map_or(0, |(seq, _)| seq.wrapping_add(1));
+        let mut buf = [0xff; SLOT_SIZE];
+        buf[..SEQ_LEN].copy_from_slice(&seq.to_le_bytes());
+        let len = SEQ_LEN + encode(settings, &mut buf[SEQ_LEN..]).ok_or(Error::Encode)?;
+        // Pad to the flash write size (erased value)
+        let len = len.next_multiple_of(F::WRITE_SIZE).min(SLOT_SIZE);
+        let buf = &buf[..len];
+        match free {
+            Some(slot) => self.flash.write(self.slot_offset(region, slot), buf),
+            None => {
+                // Full - start the other region before erasing this one
+                let next = 1 - region;
+                if !self.is_erased(next).map_err(Error::Storage)? {
+                    self.erase(next).map_err(Error::Storage)?;
+                }
+                self.flash
+                    .write(self.slot_offset(next, 0), buf)
+                    .map_err(Error::Storage)?;
+                self.erase(region)
+            }
+        }
+        .map_err(Error::Storage)
+    }
+}
+
+impl<F: NorFlash> SettingsStore for FlashJournal<F> {
+    type Error = Error<F::Error>;
+
+    async fn load(&mut self) -> Result<Option<Settings>, Self::Error> {
+        self.current()
+            .map(|(_, _, last)| last.map(|(_, settings)| settings))
+            .map_err(Error::Storage)
+    }
+
+    async fn save(&mut self, settings: &Settings) -> Result<(), Self::Error> {
+        self.append(settings)
+    }
+
+    async fn reset(&mut self) -> Result<(), Self::Error> {
+        self.erase(0)
+            .and_then(|_| self.erase(1))
+            .map_err(Error::Storage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_on;
+    use crate::settings::tests::sample;
+    use embedded_storage::nor_flash::{ErrorType, NorFlashErrorKind, ReadNorFlash};
+
+    // Flash emulation - writes can only clear bits
+    struct RamFlash {
+        mem: [u8; 6144],
+        erases: usize,
+    }
+
+    impl ErrorType for RamFlash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for RamFlash {
+        const READ_SIZE: usize = 1;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            bytes.copy_from_slice(&self.mem[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.mem.len()
+        }
+    }
+
+    impl NorFlash for RamFlash {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = 2048;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            self.mem[from as usize..to as usize].fill(0xff);
+            self.erases += 1;
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            if !(offset as usize).is_multiple_of(Self::WRITE_SIZE)
+                || !bytes.len().is_multiple_of(Self::WRITE_SIZE)
+            {
+                return Err(NorFlashErrorKind::NotAligned);
+            }
+            for (m, b) in self.mem[offset as usize..].iter_mut().zip(bytes) {
+                *m &= *b;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn journal_alternates() {
+        let flash = RamFlash {
+            mem: [0xff; 6144],
+            erases: 0,
+        };
+        // Two regions of 4 slots after the first 2K
+        let mut journal = FlashJournal::new(flash, [(2048, 2048), (4096, 2048)]);
+        assert_eq!(block_on(journal.load()), Ok(None));
+        let mut settings = sample();
+        for snooze in 1..=8 {
+            settings.snooze = snooze;
+            block_on(journal.save(&settings)).unwrap();
+            assert_eq!(block_on(journal.load()), Ok(Some(settings.clone())));
+        }
+        let flash = journal.release();
+        // First region erased once the second was started, start untouched
+        assert_eq!(flash.erases, 1);
+        assert!(flash.mem[..2048].iter().all(|b| *b == 0xff));
+        assert!(flash.mem[2048..4096].iter().all(|b| *b == 0xff));
+
+        // Reset after starting the first region again but before the second
+        // is erased - the newer record wins
+        let before = flash.mem;
+        let mut journal = FlashJournal::new(flash, [(2048, 2048), (4096, 2048)]);
+        settings.snooze = 9;
+        block_on(journal.save(&settings)).unwrap();
+        journal.flash.mem[4096..].copy_from_slice(&before[4096..]);
+        assert_eq!(block_on(journal.load()).unwrap().unwrap().snooze, 9);
+        settings.snooze = 10;
+        block_on(journal.save(&settings)).unwrap();
+        assert_eq!(block_on(journal.load()).unwrap().unwrap().snooze, 10);
+
+        // Torn write in the next slot is skipped
+        journal.flash.mem[2048 + 2 * SLOT_SIZE] = 0x00;
+        assert_eq!(block_on(journal.load()).unwrap().unwrap().snooze, 10);
+        // The second region (still full) is erased before it is reused
+        settings.snooze = 11;
+        block_on(journal.save(&settings)).unwrap();
+        block_on(journal.save(&settings)).unwrap();
+        assert_eq!(block_on(journal.load()), Ok(Some(settings)));
+        assert!(journal.flash.mem[4096 + SLOT_SIZE..]
+            .iter()
+            .all(|b| *b == 0xff));
+
+        block_on(journal.reset()).unwrap();
+        assert_eq!(block_on(journal.load()), Ok(None));
+    }
+}
@@ -0,0 +1,112 @@
+// Host time sync (`sync <unix-epoch-ms>`, see tools/clock-sync)
+//
+// The host sends its UTC time compensated for half the measured round trip.
+// The firmware first measures the RTC error at a seconds edge, then sets the
+// RTC exactly on the next whole second (the DS3231 restarts its countdown chain
+// when the seconds register is written, so the sub-second phase is kept).
+//
+// Times are milliseconds from a monotonic timer (e.g. `embassy_time::Instant`).
+use crate::rtc::YEARS;
+use chrono::{DateTime, Datelike, NaiveDateTime};
+use core::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SyncRequest {
+    // Host UTC (Unix epoch ms)
+    pub epoch_ms: i64,
+    // Monotonic time the request was received
+    pub received_ms: u64,
+}
+
+impl SyncRequest {
+    // Host UTC (epoch ms) at monotonic time `now_ms`
+    pub fn utc_ms(&self, now_ms: u64) -> i64 {
+        self.epoch_ms + now_ms.wrapping_sub(self.received_ms) as i64
+    }
+
+    // Next whole second after `now_ms` and the monotonic time it occurs (None
+    // if the RTC can't hold that date)
+    pub fn next_second(&self, now_ms: u64) -> Option<(NaiveDateTime, u64)> {
+        let utc_ms = self.utc_ms(now_ms);
+        let next_ms = (utc_ms.div_euclid(1000) + 1) * 1000;
+        let at = DateTime::from_timestamp_millis(next_ms)?.naive_utc();
+        if !YEARS.contains(&at.year()) {
+            return None;
+        }
+        Some((at, now_ms + (next_ms - utc_ms) as u64))
+    }
+
+    // RTC error in ms (positive = RTC fast) from the RTC time read just after
+    // it ticked at `edge_ms`
+    pub fn rtc_error_ms(&self, rtc: NaiveDateTime, edge_ms: u64) -> i64 {
+        rtc.and_utc().timestamp_millis() - self.utc_ms(edge_ms)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyncResult {
+    // RTC set - error before the sync (ms) if it was measured
+    Synced(Option<i64>),
+    Failed,
+}
+
+// Reply parsed by the host tool
+impl fmt::Display for SyncResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncResult::Synced(Some(error)) => write!(f, "SYNC OK {}", error),
+            SyncResult::Synced(None) => write!(f, "SYNC OK"),
+            SyncResult::Failed => write!(f, "SYNC FAILED"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn align_to_second() {
+        // 2026-03-01 12:00:00.250 UTC received at 10s uptime
+        let epoch_ms = 1_772_366_400_250;
+        let req = SyncRequest {
+            epoch_ms,
+            received_ms: 10_000,
+        };
+        assert_eq!(req.utc_ms(10_100), epoch_ms + 100);
+        let (at, when) = req.next_second(10_100).unwrap();
+        let noon = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        assert_eq!(at, noon.and_hms_opt(12, 0, 1).unwrap());
+        assert_eq!(when, 10_750);
+        // Exactly on a second - wait for the next one
+        let (at, when) = req.next_second(10_750).unwrap();
+        assert_eq!(at, noon.and_hms_opt(12, 0, 2).unwrap());
+        assert_eq!(when, 11_750);
+        // RTC ticked to 12:00:03 at 12:00:02.950 host time (50ms fast)
+        let rtc = noon.and_hms_opt(12, 0, 3).unwrap();
+        assert_eq!(req.rtc_error_ms(rtc, 12_700), 50);
+        assert_eq!(req.rtc_error_ms(rtc, 12_800), -50);
+    }
+
+    #[test]
+    fn outside_rtc_years() {
+        let req = |epoch_ms| SyncRequest {
+            epoch_ms,
+            received_ms: 0,
+        };
+        // 1970, 1999-12-31 23:59:58 and 2200-01-01
+        assert_eq!(req(0).next_second(0), None);
+        assert_eq!(req(946_684_798_000).next_second(0), None);
+        assert_eq!(req(7_258_118_400_000).next_second(0), None);
+        // Into 2000
+        assert!(req(946_684_799_000).next_second(0).is_some());
+    }
+
+    #[test]
+    fn replies() {
+        assert_eq!(SyncResult::Synced(Some(-12)).to_string(), "SYNC OK -12");
+        assert_eq!(SyncResult::Synced(None).to_string(), "SYNC OK");
+        assert_eq!(SyncResult::Failed.to_string(), "SYNC FAILED");
+    }
+}
@@ -0,0 +1,161 @@
+// Temperature graph page geometry
+//
+// The graph shows the last few hours of TempLog minute samples, one column per
+// minute. New samples are drawn a column at a time. Once the last column is
+// used the window moves on an hour and the graph is redrawn, as it is when a
+// sample falls outside the temperature range.
+use crate::layout::{GRAPH_HEIGHT, GRAPH_WIDTH};
+use chrono::{NaiveDateTime, TimeDelta, Timelike};
+
+const COLUMNS: i64 = GRAPH_WIDTH as i64;
+// Smallest temperature range shown (degrees)
+const MIN_RANGE: i32 = 4;
+
+fn floor(t: f32) -> i32 {
+    let i = t as i32;
+    if (i as f32) > t {
+        i - 1
+    } else {
+        i
+    }
+}
+
+fn ceil(t: f32) -> i32 {
+    let i = t as i32;
+    if (i as f32) < t {
+        i + 1
+    } else {
+        i
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Graph {
+    // Time of the first column (UTC, on the hour)
+    pub start: NaiveDateTime,
+    // Temperature range (whole degrees)
+    pub low: i32,
+    pub high: i32,
+}
+
+impl Graph {
+    // Window ending in the hour of the `latest` sample, scaled to fit the
+    // samples (time, temperature) within it
+    pub fn fit(latest: NaiveDateTime, samples: impl Iterator<Item = (NaiveDateTime, f32)>) -> Self {
+        let hour = latest.date().and_hms_opt(latest.hour(), 0, 0).unwrap();
+        let start = hour - TimeDelta::minutes(COLUMNS - 60);
+        let end = hour + TimeDelta::hours(1);
+        let (low, high) = samples
+            .filter(|(t, _)| (start..end).contains(t))
+            .fold((i32::MAX, i32::MIN), |(low, high), (_, t)| {
+                (low.min(floor(t)), high.max(ceil(t)))
+            });
+        let (low, high) = if low > high { (20, 20) } else { (low, high) };
+        let pad = (MIN_RANGE - (high - low)).max(0);
+        Graph {
+            start,
+            low: low - pad / 2,
+            high: high + (pad + 1) / 2,
+        }
+    }
+
+    pub fn end(&self) -> NaiveDateTime {
+        self.start + TimeDelta::minutes(COLUMNS)
+    }
+
+    // Column for sample time `t` (None outside the window)
+    pub fn x(&self, t: NaiveDateTime) -> Option<i32> {
+        let column = (t - self.start).num_minutes();
+        (0..COLUMNS).contains(&column).then_some(column as i32)
+    }
+
+    // Row (from the top) for a temperature, clamped to the graph
+    pub fn y(&self, temp: f32) -> i32 {
+        let bottom = GRAPH_HEIGHT as f32 - 1.0;
+        let y = (self.high as f32 - temp) * bottom / (self.high - self.low) as f32;
+        (y.clamp(0.0, bottom) + 0.5) as i32
+    }
+
+    // Whether a new sample can be drawn without a redraw
+    pub fn contains(&self, t: NaiveDateTime, temp: f32) -> bool {
+        self.x(t).is_some() && (self.low as f32..=self.high as f32).contains(&temp)
+    }
+
+    // Temperatures to label (about five)
+    pub fn ticks(&self) -> impl Iterator<Item = i32> {
+        let step = match self.high - self.low {
+            0..=5 => 1,
+            6..=10 => 2,
+            11..=25 => 5,
+            _ => 10,
+        };
+        (self.low..=self.high).filter(move |t| t.rem_euclid(step) == 0)
+    }
+
+    // Hour lines: column and time (UTC), including the right hand edge
+    pub fn hours(&self) -> impl Iterator<Item = (i32, NaiveDateTime)> {
+        let start = self.start;
+        (0..=COLUMNS / 60).map(move |h| ((h * 60) as i32, start + TimeDelta::hours(h)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 17)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn window_and_scale() {
+        let samples = [
+            (at(11, 59), 30.0),
+            (at(12, 0), 21.25),
+            (at(13, 30), 22.5),
+            (at(14, 37), 20.75),
+        ];
+        let graph = Graph::fit(at(14, 37), samples.into_iter());
+        assert_eq!(graph.start, at(12, 0));
+        assert_eq!(graph.end(), at(15, 0));
+        // 20..23 padded to 4 degrees
+        assert_eq!((graph.low, graph.high), (20, 24));
+        assert_eq!(graph.x(at(12, 0)), Some(0));
+        assert_eq!(graph.x(at(14, 37)), Some(157));
+        assert_eq!(graph.x(at(15, 0)), None);
+        assert_eq!(graph.x(at(11, 59)), None);
+        assert_eq!(graph.y(24.0), 0);
+        assert_eq!(graph.y(20.0), GRAPH_HEIGHT as i32 - 1);
+        assert_eq!(graph.y(-5.0), GRAPH_HEIGHT as i32 - 1);
+        assert_eq!(graph.y(22.0), 90);
+
+        assert!(graph.contains(at(14, 59), 23.75));
+        assert!(!graph.contains(at(15, 0), 22.0));
+        assert!(!graph.contains(at(14, 38), 24.25));
+
+        assert_eq!(graph.ticks().collect::<Vec<_>>(), [20, 21, 22, 23, 24]);
+        assert_eq!(
+            graph.hours().collect::<Vec<_>>(),
+            [
+                (0, at(12, 0)),
+                (60, at(13, 0)),
+                (120, at(14, 0)),
+                (180, at(15, 0))
+            ]
+        );
+    }
+
+    #[test]
+    fn below_zero() {
+        let graph = Graph::fit(at(9, 0), [(at(8, 0), -3.5), (at(9, 0), 4.25)].into_iter());
+        assert_eq!((graph.low, graph.high), (-4, 5));
+        assert_eq!(graph.ticks().collect::<Vec<_>>(), [-4, -2, 0, 2, 4]);
+        // No samples yet
+        let graph = Graph::fit(at(9, 0), core::iter::empty());
+        assert_eq!((graph.low, graph.high), (18, 22));
+    }
+}
@@ -0,0 +1,419 @@
+// Temperature history
+//
+// rtc_task records a sample every minute (or every temp_interval minutes). The
+// last day is kept at minute resolution; older samples are compacted into
+// hourly summaries (min, max and average) for the week before that. Times are
+// UTC - converted to local time for display.
+//
+// Minute samples are stored as quarter degrees for consecutive minutes ending
+// at the newest, without timestamps. RAM: 1440 x 2 bytes of minute samples
+// plus 168 x 16 bytes of hourly summaries, about 5.5K.
+use crate::tz::TimeZone;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, TimeDelta, Timelike};
+use core::fmt;
+use core::iter::Peekable;
+use heapless::Deque;
+
+pub const MINUTE_SAMPLES: usize = 24 * 60;
+pub const HOURLY_SAMPLES: usize = 7 * 24;
+
+pub const CSV_HEADER: &str = "time,temp,min,max,samples";
+
+fn epoch() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2000, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+}
+
+// Minute without a sample (temp_interval over a minute, or a missed reading)
+const NO_SAMPLE: i16 = i16::MIN;
+
+// Quarter degrees (the DS3231 resolution)
+fn quarters(temp: f32) -> i16 {
+    let q = temp * 4.0;
+    (if q < 0.0 { q - 0.5 } else { q + 0.5 }) as i16
+}
+
+// Samples summarised over a period (a single sample for minute data).
+// Minutes are counted from 2000-01-01 00:00.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Block {
+    minute: u32,
+    min: i16,
+    max: i16,
+    sum: i32,
+    count: u16,
+}
+
+impl Block {
+    fn sample(minute: u32, temp: i16) -> Self {
+        Block {
+            minute,
+            min: temp,
+            max: temp,
+            sum: temp as i32,
+            count: 1,
+        }
+    }
+
+    fn hour(&self) -> Block {
+        Block {
+            minute: self.minute / 60 * 60,
+            ..*self
+        }
+    }
+
+    fn start(&self) -> NaiveDateTime {
+        epoch() + TimeDelta::minutes(self.minute as i64)
+    }
+
+    fn merge(&mut self, other: &Block) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count = self.count.saturating_add(other.count);
+    }
+
+    fn summary(&self) -> Summary {
+        Summary {
+            start: self.start(),
+            min: self.min as f32 / 4.0,
+            max: self.max as f32 / 4.0,
+            avg: self.sum as f32 / self.count as f32 / 4.0,
+            samples: self.count,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    // Start of the period (UTC)
+    pub start: NaiveDateTime,
+    pub min: f32,
+    pub max: f32,
+    pub avg: f32,
+    pub samples: u16,
+}
+
+impl Summary {
+    // CSV row (local time), see CSV_HEADER
+    pub fn write_csv<W: fmt::Write>(&self, w: &mut W, tz: &TimeZone) -> fmt::Result {
+        let t = tz.to_local(self.start);
+        write!(
+            w,
+            "{:04}-{:02}-{:02} {:02}:{:02},{:.2},{:.2},{:.2},{}",
+            t.year(),
+            t.month(),
+            t.day(),
+            t.hour(),
+            t.minute(),
+            self.avg,
+            self.min,
+            self.max,
+            self.samples
+        )
+    }
+}
+
+// Merges consecutive blocks with the same key
+struct Grouped<I: Iterator<Item = Block>, F> {
+    blocks: Peekable<I>,
+    key: F,
+}
+
+impl<I, F, K> Iterator for Grouped<I, F>
+where
+    I: Iterator<Item = Block>,
+    F: FnMut(&Block) -> K,
+    K: PartialEq,
+{
+    type Item = Summary;
+
+    fn next(&mut self) -> Option<Summary> {
+        let mut block = self.blocks.next()?;
+        let key = (self.key)(&block);
+        while let Some(next) = self.blocks.next_if(|b| (self.key)(b) == key) {
+            block.merge(&next);
+        }
+        Some(block.summary())
+    }
+}
+
+pub struct TempLog {
+    // Samples for consecutive minutes ending at `last_minute`
+    minutes: Deque<i16, MINUTE_SAMPLES>,
+    last_minute: u32,
+    hours: Deque<Block, HOURLY_SAMPLES>,
+}
+
+// Keep the RAM cost stated above honest
+const _: () = assert!(core::mem::size_of::<TempLog>() < 6 * 1024);
+
+impl Default for TempLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TempLog {
+    pub const fn new() -> Self {
+        TempLog {
+            minutes: Deque::new(),
+            last_minute: 0,
+            hours: Deque::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.minutes.clear();
+        self.hours.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.minutes.is_empty()
+    }
+
+    // Record a sample (UTC). Only one is kept per minute, and samples from
+    // `at` on are dropped if the clock has been set back.
+    pub fn add(&mut self, at: NaiveDateTime, temp: f32) {
+        let Ok(minute) = u32::try_from((at - epoch()).num_minutes()) else {
+            return;
+        };
+        if !self.minutes.is_empty() && minute <= self.last_minute {
+            if minute == self.last_minute {
+                return;
+            }
+            self.set_back(minute);
+        }
+        if !self.minutes.is_empty() {
+            if minute - self.last_minute > MINUTE_SAMPLES as u32 {
+                // Nothing recorded for a day
+                while !self.minutes.is_empty() {
+                    self.pop_minute();
+                }
+            } else {
+                for m in self.last_minute + 1..minute {
+                    self.push_minute(m, NO_SAMPLE);
+                }
+            }
+        }
+        self.push_minute(minute, quarters(temp));
+    }
+
+    // Drop the samples from `minute` on (the newest entry is left a sample),
+    // and hourly summaries for later hours. An hour already compacted keeps
+    // its later samples.
+    fn set_back(&mut self, minute: u32) {
+        while let Some(&temp) = self.minutes.back() {
+            if self.last_minute < minute && temp != NO_SAMPLE {
+                break;
+            }
+            self.minutes.pop_back();
+            self.last_minute = self.last_minute.saturating_sub(1);
+        }
+        while self
+            .hours
+            .back()
+            .is_some_and(|h| h.minute / 60 > minute / 60)
+        {
+            self.hours.pop_back();
+        }
+    }
+
+    // Append the sample for the minute after the last (or the first)
+    fn push_minute(&mut self, minute: u32, temp: i16) {
+        if self.minutes.is_full() {
+            self.pop_minute();
+        }
+        self.minutes.push_back(temp).ok();
+        self.last_minute = minute;
+    }
+
+    // Compact the oldest minute sample into the hourly summaries
+    fn pop_minute(&mut self) {
+        let minute = self.first_minute();
+        match self.minutes.pop_front() {
+            Some(NO_SAMPLE) | None => {}
+            Some(temp) => self.compact(Block::sample(minute, temp)),
+        }
+    }
+
+    fn first_minute(&self) -> u32 {
+        self.last_minute + 1 - self.minutes.len() as u32
+    }
+
+    fn compact(&mut self, block: Block) {
+        if let Some(last) = self.hours.back_mut() {
+            if last.minute / 60 == block.minute / 60 {
+                last.merge(&block);
+                return;
+            }
+        }
+        if self.hours.is_full() {
+            self.hours.pop_front();
+        }
+        self.hours.push_back(block.hour()).ok();
+    }
+
+    fn blocks(&self) -> impl Iterator<Item = Block> + '_ {
+        let minutes = (self.first_minute()..)
+            .zip(self.minutes.iter())
+            .filter(|(_, temp)| **temp != NO_SAMPLE)
+            .map(|(minute, temp)| Block::sample(minute, *temp));
+        self.hours.iter().copied().chain(minutes)
+    }
+
+    // The newest entry is always a sample
+    pub fn latest(&self) -> Option<Summary> {
+        self.minutes
+            .back()
+            .map(|temp| Block::sample(self.last_minute, *temp).summary())
+    }
+
+    // Everything recorded, oldest first: hourly summaries then minute samples
+    pub fn records(&self) -> impl Iterator<Item = Summary> + '_ {
+        self.blocks().map(|b| b.summary())
+    }
+
+    // Hourly summaries, oldest first
+    pub fn hourly(&self) -> impl Iterator<Item = Summary> + '_ {
+        Grouped {
+            blocks: self.blocks().map(|b| b.hour()).peekable(),
+            key: |b: &Block| b.minute,
+        }
+    }
+
+    // Daily summaries by local date, oldest first
+    pub fn daily<'a>(
+        &'a self,
+        tz: &'a TimeZone,
+    ) -> impl Iterator<Item = (NaiveDate, Summary)> + 'a {
+        let date = move |t: NaiveDateTime| tz.to_local(t).date();
+        Grouped {
+            blocks: self.blocks().peekable(),
+            key: move |b: &Block| date(b.start()),
+        }
+        .map(move |s| (date(s.start), s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 7, day)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn compaction() {
+        let mut log = TempLog::new();
+        assert!(log.is_empty());
+        // Two days of samples, +0.25 degrees each hour
+        let start = at(1, 0, 0);
+        for minute in 0..2 * 24 * 60 {
+            let t = start + TimeDelta::minutes(minute);
+            log.add(t, 20.0 + (minute / 60) as f32 * 0.25);
+        }
+        // Repeats within the minute are ignored
+        log.add(at(2, 23, 59) + TimeDelta::seconds(30), 50.0);
+
+        assert_eq!(log.records().count(), 24 + MINUTE_SAMPLES);
+        let hours: Vec<_> = log.hourly().collect();
+        assert_eq!(hours.len(), 48);
+        assert_eq!(
+            hours[1],
+            Summary {
+                start: at(1, 1, 0),
+                min: 20.25,
+                max: 20.25,
+                avg: 20.25,
+                samples: 60,
+            }
+        );
+        assert_eq!(hours[47].start, at(2, 23, 0));
+        assert_eq!(hours[47].max, 31.75);
+        assert_eq!(log.latest().map(|s| s.start), Some(at(2, 23, 59)));
+
+        // Setting the clock back drops the samples from then on
+        log.add(at(2, 12, 0), -5.1);
+        assert_eq!(log.records().count(), 24 + 12 * 60 + 1);
+        assert_eq!(log.latest().map(|s| s.min), Some(-5.0));
+        assert_eq!(log.hourly().count(), 37);
+        // ...and the hourly summaries after it
+        log.add(at(1, 0, 30), 20.0);
+        assert_eq!(log.records().count(), 2);
+        assert_eq!(log.hourly().count(), 1);
+    }
+
+    #[test]
+    fn set_back() {
+        let mut log = TempLog::new();
+        // Every 5 minutes from 00:00 to 01:30
+        for minute in (0..=90).step_by(5) {
+            log.add(at(1, 0, 0) + TimeDelta::minutes(minute), 20.0);
+        }
+        // Host sync sets the clock back 3 minutes - 01:30 goes, 01:25 stays
+        log.add(at(1, 1, 27), 21.0);
+        assert_eq!(log.records().count(), 18 + 1);
+        assert_eq!(
+            log.latest().map(|s| (s.start, s.max)),
+            Some((at(1, 1, 27), 21.0))
+        );
+        let hours: Vec<_> = log.hourly().collect();
+        assert_eq!(hours.len(), 2);
+        assert_eq!((hours[1].samples, hours[1].max), (7, 21.0));
+        // Next sample on time
+        log.add(at(1, 1, 30), 22.0);
+        assert_eq!(log.records().count(), 18 + 2);
+    }
+
+    #[test]
+    fn gaps() {
+        let mut log = TempLog::new();
+        // Every 5 minutes for a day and a half
+        let start = at(1, 0, 0);
+        for minute in (0..36 * 60).step_by(5) {
+            log.add(start + TimeDelta::minutes(minute), 20.0);
+        }
+        assert_eq!(log.records().count(), 12 + MINUTE_SAMPLES / 5);
+        let hours: Vec<_> = log.hourly().collect();
+        assert_eq!(hours.len(), 36);
+        assert!(hours.iter().all(|h| h.samples == 12));
+        assert_eq!(log.latest().map(|s| s.start), Some(at(2, 11, 55)));
+
+        // Over a day with nothing recorded - every sample is compacted
+        log.add(at(4, 0, 0), 21.0);
+        assert_eq!(log.records().count(), 36 + 1);
+        assert_eq!(log.hourly().count(), 37);
+        assert_eq!(log.latest().map(|s| s.start), Some(at(4, 0, 0)));
+    }
+
+    #[test]
+    fn daily_stats() {
+        let tz = TimeZone::parse("CET-1").unwrap();
+        let mut log = TempLog::new();
+        log.add(at(1, 22, 0), 18.0);
+        log.add(at(1, 22, 59), 19.0);
+        // Next day in local time
+        log.add(at(1, 23, 0), 21.0);
+        log.add(at(2, 1, 0), 22.0);
+        let days: Vec<_> = log.daily(&tz).collect();
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].0, NaiveDate::from_ymd_opt(2026, 7, 1).unwrap());
+        assert_eq!(
+            (days[0].1.min, days[0].1.max, days[0].1.avg),
+            (18.0, 19.0, 18.5)
+        );
+        assert_eq!(days[1].0, NaiveDate::from_ymd_opt(2026, 7, 2).unwrap());
+        assert_eq!(days[1].1.samples, 2);
+
+        let mut s = String::new();
+        log.latest().unwrap().write_csv(&mut s, &tz).unwrap();
+        assert_eq!(s, "2026-07-02 02:00,22.00,22.00,22.00,1");
+    }
+}
@@ -0,0 +1,429 @@
+// POSIX TZ rules (e.g. `GMT0BST,M3.5.0/1,M10.5.0`) for converting the UTC
+// RTC time to local time:
+//
+//   std offset [dst [offset] ,start[/time],end[/time]]
+//
+// Offsets are hours west of UTC ([+-]hh[:mm[:ss]]); the DST offset defaults to
+// one hour ahead of standard time. Transition dates are `Mm.w.d` (day d of
+// week w of month m, w = 5 for the last), `Jn` (1-365, Feb 29 not counted) or
+// `n` (0-365). Transition times are local and default to 02:00.
+use chrono::{Datelike, NaiveDate, NaiveDateTime, TimeDelta, Weekday};
+use core::fmt;
+use heapless::String;
+use nom::branch::alt;
+use nom::bytes::complete::{take_while1, take_while_m_n};
+use nom::character::complete::{char, one_of, u16 as u16_parser, u8 as u8_parser};
+use nom::combinator::{all_consuming, map, map_opt, opt};
+use nom::error::Error;
+use nom::sequence::{delimited, pair, preceded, tuple};
+use nom::IResult;
+
+// Longest rule accepted (`set tz`)
+pub const TZ_LEN: usize = 48;
+pub type TzName = String<8>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransitionDate {
+    // Day (0 = Sunday) of week (1-5, 5 = last) of month
+    MonthWeekDay { month: u8, week: u8, weekday: u8 },
+    // Day of year 1-365 ignoring Feb 29
+    Julian1(u16),
+    // Day of year 0-365 including Feb 29
+    Julian0(u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transition {
+    pub date: TransitionDate,
+    // Local time (seconds after midnight, may be negative or > 24h)
+    pub time: i32,
+}
+
+impl Transition {
+    // Local date/time of the transition in `year`
+    pub fn at(&self, year: i32) -> Option<NaiveDateTime> {
+        let date = match self.date {
+            TransitionDate::MonthWeekDay {
+                month,
+                week,
+                weekday,
+            } => {
+                let weekday = Weekday::try_from((weekday + 6) % 7).ok()?;
+                NaiveDate::from_weekday_of_month_opt(year, month as u32, weekday, week).or_else(
+                    || {
+                        // Week 5 is the last week (there may only be 4)
+                        NaiveDate::from_weekday_of_month_opt(year, month as u32, weekday, 4)
+                    },
+                )?
+            }
+            TransitionDate::Julian1(n) => {
+                let leap = NaiveDate::from_ymd_opt(year, 2, 29).is_some();
+                let n = if leap && n >= 60 { n } else { n - 1 };
+                NaiveDate::from_yo_opt(year, 1)? + TimeDelta::days(n as i64)
+            }
+            TransitionDate::Julian0(n) => {
+                NaiveDate::from_yo_opt(year, 1)? + TimeDelta::days(n as i64)
+            }
+        };
+        Some(date.and_hms_opt(0, 0, 0)? + TimeDelta::seconds(self.time as i64))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dst {
+    pub name: TzName,
+    // Seconds east of UTC
+    pub offset: i32,
+    pub start: Transition,
+    pub end: Transition,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeZone {
+    rule: String<TZ_LEN>,
+    pub name: TzName,
+    // Seconds east of UTC (local = UTC + offset)
+    pub offset: i32,
+    pub dst: Option<Dst>,
+}
+
+impl Default for TimeZone {
+    fn default() -> Self {
+        Self::utc()
+    }
+}
+
+impl fmt::Display for TimeZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.rule())
+    }
+}
+
+fn tz_name(input: &str) -> IResult<&str, TzName, Error<&str>> {
+    map_opt(
+        alt((
+            delimited(
+                char('<'),
+                take_while1(|c: char| c.is_ascii_alphanumeric() || c == '+' || c == '-'),
+                char('>'),
+            ),
+            take_while_m_n(3, 8, |c: char| c.is_ascii_alphabetic()),
+        )),
+        |name: &str| TzName::try_from(name).ok(),
+    )(input)
+}
+
+// [+-]hh[:mm[:ss]] in seconds
+fn hms(input: &str) -> IResult<&str, i32, Error<&str>> {
+    map_opt(
+        tuple((
+            opt(one_of("+-")),
+            u8_parser,
+            opt(preceded(char(':'), u8_parser)),
+            opt(preceded(char(':'), u8_parser)),
+        )),
+        |(sign, h, m, s)| {
+            let (m, s) = (m.unwrap_or(0), s.unwrap_or(0));
+            if h > 167 || m > 59 || s > 59 {
+                return None;
+            }
+            let secs = h as i32 * 3600 + m as i32 * 60 + s as i32;
+            Some(if sign == Some('-') { -secs } else { secs })
+        },
+    )(input)
+}
+
+// POSIX offsets are west of UTC
+fn offset(input: &str) -> IResult<&str, i32, Error<&str>> {
+    map_opt(hms, |secs| (secs.abs() <= 24 * 3600).then_some(-secs))(input)
+}
+
+fn transition(input: &str) -> IResult<&str, Transition, Error<&str>> {
+    map(
+        pair(
+            alt((
+                map_opt(
+                    tuple((
+                        preceded(char('M'), u8_parser),
+                        preceded(char('.'), u8_parser),
+                        preceded(char('.'), u8_parser),
+                    )),
+                    |(month, week, weekday)| {
+                        ((1..=12).contains(&month) && (1..=5).contains(&week) && weekday <= 6)
+                            .then_some(TransitionDate::MonthWeekDay {
+                                month,
+                                week,
+                                weekday,
+                            })
+                    },
+                ),
+                map_opt(preceded(char('J'), u16_parser), |n| {
+                    (1..=365).contains(&n).then_some(TransitionDate::Julian1(n))
+                }),
+                map_opt(u16_parser, |n| {
+                    (n <= 365).then_some(TransitionDate::Julian0(n))
+                }),
+            )),
+            opt(preceded(char('/'), hms)),
+        ),
+        |(date, time)| Transition {
+            date,
+            time: time.unwrap_or(2 * 3600),
+        },
+    )(input)
+}
+
+fn tz_parser(input: &str) -> IResult<&str, (TzName, i32, Option<Dst>), Error<&str>> {
+    let (input, (name, offset)) = pair(tz_name, self::offset)(input)?;
+    let (input, dst) = opt(map(
+        tuple((
+            tz_name,
+            opt(self::offset),
+            preceded(char(','), transition),
+            preceded(char(','), transition),
+        )),
+        |(name, dst_offset, start, end)| Dst {
+            name,
+            offset: dst_offset.unwrap_or(offset + 3600),
+            start,
+            end,
+        },
+    ))(input)?;
+    Ok((input, (name, offset, dst)))
+}
+
+impl TimeZone {
+    // Empty rule/name (const for `Settings::new`)
+    pub const fn utc() -> Self {
+        TimeZone {
+            rule: String::new(),
+            name: String::new(),
+            offset: 0,
+            dst: None,
+        }
+    }
+
+    // None if the rule is invalid or too long
+    pub fn parse(rule: &str) -> Option<Self> {
+        let (_, (name, offset, dst)) = all_consuming(tz_parser)(rule).ok()?;
+        Some(TimeZone {
+            rule: String::try_from(rule).ok()?,
+            name,
+            offset,
+            dst,
+        })
+    }
+
+    pub fn rule(&self) -> &str {
+        if self.rule.is_empty() {
+            "UTC0"
+        } else {
+            &self.rule
+        }
+    }
+
+    // DST in effect at `utc`
+    pub fn is_dst(&self, utc: NaiveDateTime) -> bool {
+        let Some(dst) = &self.dst else {
+            return false;
+        };
+        let year = (utc + TimeDelta::seconds(self.offset as i64)).year();
+        // Start is given in standard time, end in DST
+        let (Some(start), Some(end)) = (dst.start.at(year), dst.end.at(year)) else {
+            return false;
+        };
+        let start = start - TimeDelta::seconds(self.offset as i64);
+        let end = end - TimeDelta::seconds(dst.offset as i64);
+        if start <= end {
+            start <= utc && utc < end
+        } else {
+            // Southern hemisphere (DST over the new year)
+            utc < end || start <= utc
+        }
+    }
+
+    // Seconds east of UTC at `utc`
+    pub fn offset_at(&self, utc: NaiveDateTime) -> i32 {
+        match &self.dst {
+            Some(dst) if self.is_dst(utc) => dst.offset,
+            _ => self.offset,
+        }
+    }
+
+    // Zone abbreviation at `utc` (e.g. GMT/BST)
+    pub fn name_at(&self, utc: NaiveDateTime) -> &str {
+        match &self.dst {
+            Some(dst) if self.is_dst(utc) => &dst.name,
+            _ if self.name.is_empty() => "UTC",
+            _ => &self.name,
+        }
+    }
+
+    pub fn to_local(&self, utc: NaiveDateTime) -> NaiveDateTime {
+        utc + TimeDelta::seconds(self.offset_at(utc) as i64)
+    }
+
+    // Local times repeated when DST ends resolve to the first (DST) instance.
+    // Local times skipped when DST starts are moved forward by the gap.
+    pub fn to_utc(&self, local: NaiveDateTime) -> NaiveDateTime {
+        let std = local - TimeDelta::seconds(self.offset as i64);
+        let Some(dst) = &self.dst else {
+            return std;
+        };
+        let daylight = local - TimeDelta::seconds(dst.offset as i64);
+        match (self.is_dst(daylight), !self.is_dst(std)) {
+            (true, true) => daylight.min(std),
+            (true, false) => daylight,
+            _ => std,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dt(m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn parse_rules() {
+        let uk = TimeZone::parse("GMT0BST,M3.5.0/1,M10.5.0").unwrap();
+        assert_eq!(uk.name, "GMT");
+        assert_eq!(uk.offset, 0);
+        let dst = uk.dst.as_ref().unwrap();
+        assert_eq!((dst.name.as_str(), dst.offset), ("BST", 3600));
+        assert_eq!(dst.start.time, 3600);
+        assert_eq!(dst.end.time, 7200);
+        assert_eq!(uk.rule(), "GMT0BST,M3.5.0/1,M10.5.0");
+
+        let ny = TimeZone::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        assert_eq!(ny.offset, -5 * 3600);
+        assert_eq!(ny.dst.unwrap().offset, -4 * 3600);
+        let india = TimeZone::parse("IST-5:30").unwrap();
+        assert_eq!(india.offset, 5 * 3600 + 30 * 60);
+        assert_eq!(india.dst, None);
+        let numeric = TimeZone::parse("<+1030>-10:30<+11>-11,M10.1.0,M4.1.0").unwrap();
+        assert_eq!(numeric.name, "+1030");
+        assert_eq!(numeric.dst.unwrap().offset, 11 * 3600);
+        assert!(TimeZone::parse("CET-1CEST,J60,300/-1").is_some());
+        assert_eq!(TimeZone::utc().rule(), "UTC0");
+        assert_eq!(TimeZone::parse("UTC0").unwrap().name, "UTC");
+
+        for bad in [
+            "",
+            "GMT",
+            "GM0",
+            "GMT0BST",
+            "GMT0BST,M3.5.0",
+            "GMT0BST,M13.5.0,M10.5.0",
+            "GMT0BST,M3.6.0,M10.5.0",
+            "GMT0BST,M3.5.7,M10.5.0",
+            "GMT0BST,J0,M10.5.0",
+            "GMT25",
+            "GMT0 ",
+            "GMT0BST,M3.5.0/1,M10.5.0,M11.1.0",
+        ] {
+            assert_eq!(TimeZone::parse(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn transitions() {
+        let date = |t: Transition| t.at(2026).unwrap();
+        let mwd = |month, week, weekday| Transition {
+            date: TransitionDate::MonthWeekDay {
+                month,
+                week,
+                weekday,
+            },
+            time: 0,
+        };
+        // Last Sunday in March / October, second Sunday in March (2026)
+        assert_eq!(date(mwd(3, 5, 0)), dt(3, 29, 0, 0));
+        assert_eq!(date(mwd(10, 5, 0)), dt(10, 25, 0, 0));
+        assert_eq!(date(mwd(3, 2, 0)), dt(3, 8, 0, 0));
+        // Fifth Sunday exists in March 2026
+        assert_eq!(date(mwd(3, 5, 0)), date(mwd(3, 4, 0)) + TimeDelta::days(7));
+        let julian = |date| Transition { date, time: 3600 };
+        assert_eq!(date(julian(TransitionDate::Julian1(60))), dt(3, 1, 1, 0));
+        assert_eq!(date(julian(TransitionDate::Julian0(59))), dt(3, 1, 1, 0));
+        let leap = |date| Transition { date, time: 0 }.at(2028).unwrap().date();
+        assert_eq!(
+            leap(TransitionDate::Julian1(60)),
+            NaiveDate::from_ymd_opt(2028, 3, 1).unwrap()
+        );
+        assert_eq!(
+            leap(TransitionDate::Julian0(59)),
+            NaiveDate::from_ymd_opt(2028, 2, 29).unwrap()
+        );
+    }
+
+    #[test]
+    fn uk_transition_edges() {
+        let uk = TimeZone::parse("GMT0BST,M3.5.0/1,M10.5.0").unwrap();
+        let s = |n| TimeDelta::seconds(n);
+        // DST starts 2026-03-29 01:00 UTC (01:00 GMT -> 02:00 BST)
+        assert_eq!(uk.to_local(dt(3, 29, 1, 0) - s(1)), dt(3, 29, 1, 0) - s(1));
+        assert_eq!(uk.to_local(dt(3, 29, 1, 0)), dt(3, 29, 2, 0));
+        assert_eq!(uk.name_at(dt(3, 29, 0, 59)), "GMT");
+        assert_eq!(uk.name_at(dt(3, 29, 1, 0)), "BST");
+        // DST ends 2026-10-25 01:00 UTC (02:00 BST -> 01:00 GMT)
+        assert_eq!(
+            uk.to_local(dt(10, 25, 1, 0) - s(1)),
+            dt(10, 25, 2, 0) - s(1)
+        );
+        assert_eq!(uk.to_local(dt(10, 25, 1, 0)), dt(10, 25, 1, 0));
+        assert_eq!(uk.offset_at(dt(7, 1, 12, 0)), 3600);
+        assert_eq!(uk.offset_at(dt(12, 1, 12, 0)), 0);
+
+        // Local to UTC: unambiguous, skipped and repeated times
+        assert_eq!(uk.to_utc(dt(7, 1, 12, 0)), dt(7, 1, 11, 0));
+        assert_eq!(uk.to_utc(dt(12, 1, 12, 0)), dt(12, 1, 12, 0));
+        assert_eq!(uk.to_utc(dt(3, 29, 1, 30)), dt(3, 29, 1, 30));
+        assert_eq!(uk.to_utc(dt(3, 29, 2, 0)), dt(3, 29, 1, 0));
+        assert_eq!(uk.to_utc(dt(10, 25, 1, 30)), dt(10, 25, 0, 30));
+        assert_eq!(uk.to_utc(dt(10, 25, 2, 0)), dt(10, 25, 2, 0));
+        // Round trip every 15 minutes through both transition days
+        for day in [(3, 29), (10, 25)] {
+            for q in 0..96 {
+                let utc = dt(day.0, day.1, 0, 0) + TimeDelta::minutes(15 * q);
+                let local = uk.to_local(utc);
+                // Repeated hour maps to the first instance
+                let expected = if uk.to_local(utc - TimeDelta::hours(1)) == local {
+                    utc - TimeDelta::hours(1)
+                } else {
+                    utc
+                };
+                assert_eq!(uk.to_utc(local), expected, "{}", utc);
+            }
+        }
+    }
+
+    #[test]
+    fn other_zones() {
+        // US: 2026-03-08 02:00 EST = 07:00 UTC, 2026-11-01 02:00 EDT = 06:00 UTC
+        let ny = TimeZone::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        assert_eq!(ny.to_local(dt(3, 8, 6, 59)), dt(3, 8, 1, 59));
+        assert_eq!(ny.to_local(dt(3, 8, 7, 0)), dt(3, 8, 3, 0));
+        assert_eq!(ny.to_local(dt(11, 1, 5, 59)), dt(11, 1, 1, 59));
+        assert_eq!(ny.to_local(dt(11, 1, 6, 0)), dt(11, 1, 1, 0));
+        // Sydney: DST (AEDT) over the new year, ends 2026-04-05 03:00 AEDT
+        let syd = TimeZone::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(syd.offset_at(dt(1, 15, 0, 0)), 11 * 3600);
+        assert_eq!(syd.offset_at(dt(6, 15, 0, 0)), 10 * 3600);
+        assert_eq!(syd.to_local(dt(4, 4, 15, 59)), dt(4, 5, 2, 59));
+        assert_eq!(syd.to_local(dt(4, 4, 16, 0)), dt(4, 5, 2, 0));
+        // DST starts 2026-10-04 02:00 AEST
+        assert_eq!(syd.to_local(dt(10, 3, 15, 59)), dt(10, 4, 1, 59));
+        assert_eq!(syd.to_local(dt(10, 3, 16, 0)), dt(10, 4, 3, 0));
+        // Fixed offset
+        let india = TimeZone::parse("IST-5:30").unwrap();
+        assert_eq!(india.to_local(dt(1, 1, 0, 0)), dt(1, 1, 5, 30));
+        assert_eq!(india.to_utc(dt(1, 1, 5, 30)), dt(1, 1, 0, 0));
+    }
+}
@@ -0,0 +1,104 @@
+// Sets the clock from the host's UTC time over the CDC-ACM serial port:
+//
+//   cargo sync-host [/dev/ttyACM0]
+//
+// The round trip is measured with `hello` (best of several), then
+// `sync <unix-epoch-ms>` is sent with the host time advanced by half the round
+// trip. The clock replies `SYNC OK [error-ms]` once the RTC has been set.
+//
+// The port is put into raw mode with `stty` so that only std is needed.
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::process::{Command, ExitCode};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_PORT: &str = "/dev/ttyACM0";
+const PINGS: usize = 5;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn raw_mode(port: &str) -> io::Result<()> {
+    let flag = if cfg!(target_os = "macos") { "-f" } else { "-F" };
+    // Reads return after 0.1s without data (VTIME) so timeouts can be checked
+    let status = Command::new("stty")
+        .args([flag, port, "raw", "-echo", "min", "0", "time", "1"])
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("stty failed for {}", port)))
+    }
+}
+
+// Send a command and wait for a reply line containing `marker`
+fn command(port: &mut File, cmd: &str, marker: &str) -> io::Result<String> {
+    port.write_all(cmd.as_bytes())?;
+    port.write_all(b"\r")?;
+    port.flush()?;
+    let start = Instant::now();
+    let mut received = Vec::new();
+    let mut buf = [0; 256];
+    while start.elapsed() < TIMEOUT {
+        let n = port.read(&mut buf)?;
+        received.extend_from_slice(&buf[..n]);
+        // The echoed command line does not contain the marker
+        let text = String::from_utf8_lossy(&received);
+        if let Some(line) = text.split(['\r', '\n']).find(|l| l.starts_with(marker)) {
+            return Ok(line.to_string());
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("no reply to `{}`", cmd),
+    ))
+}
+
+fn drain(port: &mut File) -> io::Result<()> {
+    let mut buf = [0; 256];
+    while port.read(&mut buf)? > 0 {}
+    Ok(())
+}
+
+fn sync(path: &str) -> io::Result<()> {
+    raw_mode(path)?;
+    let mut port = OpenOptions::new().read(true).write(true).open(path)?;
+    // Discard any partial line and pending output
+    port.write_all(b"\r")?;
+    drain(&mut port)?;
+
+    let mut rtt = Duration::MAX;
+    for _ in 0..PINGS {
+        let start = Instant::now();
+        command(&mut port, "hello", "Hello!")?;
+        rtt = rtt.min(start.elapsed());
+        drain(&mut port)?;
+    }
+    println!("Round trip: {:.1}ms", rtt.as_secs_f64() * 1e3);
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(io::Error::other)?;
+    let epoch_ms = (now + rtt / 2).as_millis();
+    let reply = command(&mut port, &format!("sync {}", epoch_ms), "SYNC")?;
+    match reply.strip_prefix("SYNC OK") {
+        Some(error) if !error.trim().is_empty() => {
+            println!("Clock set (RTC error was {}ms)", error.trim());
+            Ok(())
+        }
+        Some(_) => {
+            println!("Clock set");
+            Ok(())
+        }
+        None => Err(io::Error::other(reply)),
+    }
+}
+
+fn main() -> ExitCode {
+    let port = std::env::args().nth(1);
+    match sync(port.as_deref().unwrap_or(DEFAULT_PORT)) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("clock-sync: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}